@@ -0,0 +1,55 @@
+// Throughput of the buffered single-threaded batch path against the streaming
+// pipeline: `cargo bench --bench batch`
+use portgen::{batch, calculate_address, PortgenError};
+use std::{
+    io::{self, BufRead, Write},
+    time::Instant,
+};
+
+const LINES: usize = 500_000;
+const NAMES: &[&str] = &[
+    "rpc-polkadot-01",
+    "val-asset-hub-kusama-03",
+    "boot-paseo-00",
+];
+
+fn format(line: &str) -> Result<String, PortgenError> {
+    Ok(calculate_address(line)?.to_string())
+}
+
+// The previous model: read everything, then resolve and print one by one
+fn buffered(input: &[u8], out: &mut impl Write) -> Result<(), PortgenError> {
+    let names: Vec<String> = input.lines().map_while(Result::ok).collect();
+    let records = names
+        .iter()
+        .map(|name| format(name))
+        .collect::<Result<Vec<_>, _>>()?;
+    for record in records {
+        writeln!(out, "{record}").map_err(|_| "failed to write output")?;
+    }
+    Ok(())
+}
+
+fn report(label: &str, run: impl FnOnce()) {
+    let start = Instant::now();
+    run();
+    let secs = start.elapsed().as_secs_f64();
+    println!("{label:<24} {:>10.0} lines/s", LINES as f64 / secs);
+}
+
+fn main() {
+    let input: String = (0..LINES)
+        .map(|i| format!("{}\n", NAMES[i % NAMES.len()]))
+        .collect();
+    let jobs = std::thread::available_parallelism().map_or(1, |n| n.get());
+
+    report("buffered", || {
+        buffered(input.as_bytes(), &mut io::sink()).unwrap()
+    });
+    report("streaming, 1 worker", || {
+        batch::process(input.as_bytes(), &mut io::sink(), 1, '\n', format).unwrap()
+    });
+    report(&format!("streaming, {jobs} workers"), || {
+        batch::process(input.as_bytes(), &mut io::sink(), jobs, '\n', format).unwrap()
+    });
+}
@@ -0,0 +1,124 @@
+// Node names spliced into free text next to the addresses they own, for
+// reading proxy and firewall logs. Anything that does not decode to a node
+// under the schema, including an ip with a port the node does not use, is
+// passed through as it was.
+use crate::{PortKind, PortSchema};
+use std::net::Ipv4Addr;
+
+pub const DEFAULT_TEMPLATE: &str = "{name}({addr})";
+
+pub struct AnnotateOptions<'a> {
+    pub schema: &'a PortSchema,
+    // `{name}` and `{addr}`, the address as it was written
+    pub template: &'a str,
+}
+
+// Node owning `ip`, and `port` too when one is given
+fn decode(schema: &PortSchema, ip: Ipv4Addr, port: Option<u16>) -> Option<String> {
+    let name = schema.reverse_lookup(ip).ok()?;
+    if let Some(port) = port {
+        let address = schema.address(&name).ok()?;
+        let owned = PortKind::ALL
+            .iter()
+            .any(|&kind| address.port_of(kind).is_ok_and(|p| p.0 == port));
+        if !owned {
+            return None;
+        }
+    }
+    Some(name)
+}
+
+// The digits after `:` at `at`, if they make a port and end there
+fn port_at(line: &str, at: usize) -> Option<(u16, usize)> {
+    let digits = line.get(at + 1..)?;
+    let len = digits.bytes().take_while(u8::is_ascii_digit).count();
+    if line.as_bytes().get(at) != Some(&b':') || len == 0 {
+        return None;
+    }
+    Some((digits[..len].parse().ok()?, at + 1 + len))
+}
+
+pub fn annotate_line(line: &str, options: &AnnotateOptions) -> String {
+    let bytes = line.as_bytes();
+    let mut annotated = String::with_capacity(line.len());
+    let mut copied = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        // An address starts at a digit that does not continue a word or number
+        let starts = bytes[i].is_ascii_digit()
+            && (i == 0 || !(bytes[i - 1].is_ascii_alphanumeric() || bytes[i - 1] == b'.'));
+        if !starts {
+            i += 1;
+            continue;
+        }
+        let run = bytes[i..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit() || **b == b'.')
+            .count();
+        // A sentence may end right after an address
+        let candidate = line[i..i + run].trim_end_matches('.');
+        let Ok(ip) = candidate.parse::<Ipv4Addr>() else {
+            i += run;
+            continue;
+        };
+        let ip_end = i + candidate.len();
+        let (port, end) = match port_at(line, ip_end) {
+            Some((port, end)) => (Some(port), end),
+            None => (None, ip_end),
+        };
+        if let Some(name) = decode(options.schema, ip, port) {
+            annotated.push_str(&line[copied..i]);
+            annotated.push_str(
+                &options
+                    .template
+                    .replace("{name}", &name)
+                    .replace("{addr}", &line[i..end]),
+            );
+            copied = end;
+        }
+        i = end;
+    }
+    annotated.push_str(&line[copied..]);
+    annotated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PORT_SCHEMA_V1;
+
+    #[test]
+    fn annotates_node_addresses_and_passes_the_rest_through() {
+        let options = AnnotateOptions {
+            schema: &PORT_SCHEMA_V1,
+            template: DEFAULT_TEMPLATE,
+        };
+        for (line, annotated) in [
+            (
+                "upstream 192.168.121.11:32011 ok",
+                "upstream rpc-asset-hub-kusama-01(192.168.121.11:32011) ok",
+            ),
+            // rpc port of the same node, and a bare ip ending a sentence
+            (
+                "to 192.168.121.11:42011 from 192.168.111.10.",
+                "to rpc-asset-hub-kusama-01(192.168.121.11:42011) from rpc-polkadot-01(192.168.111.10).",
+            ),
+            // A port the node does not use, foreign and partial addresses
+            (
+                "192.168.121.11:8080 10.0.0.1:32011 1192.168.111.10 v192.168.111.10",
+                "192.168.121.11:8080 10.0.0.1:32011 1192.168.111.10 v192.168.111.10",
+            ),
+        ] {
+            assert_eq!(annotate_line(line, &options), annotated);
+        }
+
+        let names_only = AnnotateOptions {
+            schema: &PORT_SCHEMA_V1,
+            template: "{name}",
+        };
+        assert_eq!(
+            annotate_line("SRC=192.168.111.10 DST=192.168.10.10:31000", &names_only),
+            "SRC=rpc-polkadot-01 DST=boot-polkadot-00"
+        );
+    }
+}
@@ -0,0 +1,292 @@
+// Address fields inside existing node files brought in line with the formula:
+// each configured key path is compared with its computed value and only the
+// differing scalars are replaced. serde_yaml writes the document back, so
+// comments and formatting of a rewritten file are lost.
+use crate::{
+    atomic::write_output, calculate_address, lint, selection::NameFilter, NodeAddress, PortKind,
+    PortgenError,
+};
+use serde_yaml::{Mapping, Value};
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+// Value computed from the node name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Computed {
+    Ip,
+    // The p2p port
+    Port,
+    RpcPort,
+    WsPort,
+    PrometheusPort,
+    Address,
+    Multiaddr,
+}
+
+impl FromStr for Computed {
+    type Err = PortgenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ip" => Ok(Self::Ip),
+            "port" | "p2p_port" => Ok(Self::Port),
+            "rpc_port" => Ok(Self::RpcPort),
+            "ws_port" => Ok(Self::WsPort),
+            "prometheus_port" => Ok(Self::PrometheusPort),
+            "address" => Ok(Self::Address),
+            "multiaddr" => Ok(Self::Multiaddr),
+            _ => Err("unknown field, expected ip, port, rpc_port, ws_port, \
+                      prometheus_port, address or multiaddr"
+                .into()),
+        }
+    }
+}
+
+impl Computed {
+    pub fn value(self, address: &NodeAddress) -> Result<Value, PortgenError> {
+        let port = |kind| Ok(Value::from(address.port_of(kind)?.0));
+        match self {
+            Self::Ip => Ok(Value::from(address.ip.to_string())),
+            Self::Port => port(PortKind::P2p),
+            Self::RpcPort => port(PortKind::Rpc),
+            Self::WsPort => port(PortKind::Ws),
+            Self::PrometheusPort => port(PortKind::Prometheus),
+            Self::Address => Ok(Value::from(address.to_string())),
+            Self::Multiaddr => Ok(Value::from(address.multiaddr())),
+        }
+    }
+}
+
+// `ip=network.ip`: a computed field and the dotted key path holding it. A bare
+// `network.ip` names the field by its last key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMapping {
+    pub field: Computed,
+    pub path: String,
+}
+
+impl FromStr for KeyMapping {
+    type Err = PortgenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (field, path) = match s.split_once('=') {
+            Some((field, path)) => (field.parse()?, path),
+            None => {
+                let last = s.rsplit('.').next().unwrap_or(s);
+                let field = last
+                    .parse()
+                    .map_err(|_| "the last key names no field, use FIELD=KEY.PATH")?;
+                (field, s)
+            }
+        };
+        if path.split('.').any(str::is_empty) {
+            return Err("empty key in KEY.PATH".into());
+        }
+        Ok(Self {
+            field,
+            path: path.to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    pub path: String,
+    pub old: String,
+    pub new: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileReport {
+    pub path: PathBuf,
+    pub changes: Vec<FieldChange>,
+    // Key paths that are not in the file or do not hold a scalar
+    pub missing: Vec<String>,
+    // The file is not a YAML mapping, nothing in it was looked at
+    pub unparsable: bool,
+    // The rewrite drops comments the file had
+    pub comments_dropped: bool,
+    // Unified diff of the rewrite, empty without changes
+    pub diff: String,
+}
+
+impl fmt::Display for FileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if self.unparsable {
+            parts.push("not a YAML mapping".to_string());
+        }
+        for change in &self.changes {
+            parts.push(format!("{} {} -> {}", change.path, change.old, change.new));
+        }
+        if !self.missing.is_empty() {
+            parts.push(format!("missing {}", self.missing.join(", ")));
+        }
+        if self.comments_dropped {
+            parts.push("comments dropped".to_string());
+        }
+        if parts.is_empty() {
+            parts.push("ok".to_string());
+        }
+        write!(f, "{}: {}", self.path.display(), parts.join("; "))
+    }
+}
+
+pub fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn lookup<'a>(doc: &'a mut Value, path: &str) -> Option<&'a mut Value> {
+    path.split('.')
+        .try_fold(doc, |value, key| value.as_mapping_mut()?.get_mut(key))
+}
+
+// Comment lines and trailing comments after a space, good enough to warn
+fn has_comments(text: &str) -> bool {
+    text.lines()
+        .any(|line| line.trim_start().starts_with('#') || line.contains(" #"))
+}
+
+pub fn apply_file(
+    path: &Path,
+    name: &str,
+    mappings: &[KeyMapping],
+    write: bool,
+    backup: bool,
+) -> Result<FileReport, PortgenError> {
+    let address = calculate_address(name)?;
+    let text = fs::read_to_string(path).map_err(|_| "failed to read node file")?;
+    let mut report = FileReport {
+        path: path.to_path_buf(),
+        changes: Vec::new(),
+        missing: Vec::new(),
+        unparsable: false,
+        comments_dropped: false,
+        diff: String::new(),
+    };
+    let mut doc: Value = match serde_yaml::from_str(&text) {
+        Ok(doc @ Value::Mapping(_)) => doc,
+        // An empty file is an empty mapping with every key missing
+        Ok(Value::Null) => Value::Mapping(Mapping::new()),
+        _ => {
+            report.unparsable = true;
+            return Ok(report);
+        }
+    };
+
+    for mapping in mappings {
+        let computed = mapping.field.value(&address)?;
+        let Some(value) = lookup(&mut doc, &mapping.path) else {
+            report.missing.push(mapping.path.clone());
+            continue;
+        };
+        let (Some(old), Some(new)) = (scalar(value), scalar(&computed)) else {
+            report.missing.push(mapping.path.clone());
+            continue;
+        };
+        // "31001" and 31001 are the same port, quoting alone is no reason to churn
+        if old != new {
+            *value = computed;
+            report.changes.push(FieldChange {
+                path: mapping.path.clone(),
+                old,
+                new,
+            });
+        }
+    }
+
+    if report.changes.is_empty() {
+        return Ok(report);
+    }
+    let updated = serde_yaml::to_string(&doc).map_err(|_| "failed to serialize node file")?;
+    report.comments_dropped = has_comments(&text);
+    let label = path.display().to_string();
+    report.diff = similar::TextDiff::from_lines(&text, &updated)
+        .unified_diff()
+        .header(&label, &label)
+        .to_string();
+    if write {
+        write_output(path, updated.as_bytes(), backup).map_err(|_| "failed to write node file")?;
+    }
+    Ok(report)
+}
+
+// Every `.yaml` in `dir` whose file name is a node name the filter keeps, and
+// the paths skipped for not being one
+pub fn apply_dir(
+    dir: &Path,
+    mappings: &[KeyMapping],
+    names: &NameFilter,
+    write: bool,
+    backup: bool,
+) -> Result<(Vec<FileReport>, Vec<PathBuf>), PortgenError> {
+    let mut reports = Vec::new();
+    let mut skipped = Vec::new();
+    for path in lint::node_files(dir)? {
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("");
+        if calculate_address(name).is_err() {
+            skipped.push(path);
+            continue;
+        }
+        if !names.keeps(name) {
+            continue;
+        }
+        reports.push(apply_file(&path, name, mappings, write, backup)?);
+    }
+    Ok((reports, skipped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrites_only_differing_values_and_reports_missing_keys() {
+        let dir = std::env::temp_dir().join(format!("portgen-apply-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("rpc-polkadot-01.yaml");
+        fs::write(
+            &path,
+            "# hand-typed\nnetwork:\n  ip: 192.168.111.99\n  p2p_port: \"31001\"\nimage: polkadot\n",
+        )
+        .unwrap();
+        let mappings: Vec<KeyMapping> =
+            ["network.ip", "port=network.p2p_port", "rpc_port=rpc.port"]
+                .iter()
+                .map(|s| s.parse().unwrap())
+                .collect();
+
+        let report = apply_file(&path, "rpc-polkadot-01", &mappings, true, false).unwrap();
+        assert_eq!(
+            report.changes,
+            vec![FieldChange {
+                path: "network.ip".into(),
+                old: "192.168.111.99".into(),
+                new: "192.168.111.10".into(),
+            }]
+        );
+        assert_eq!(report.missing, vec!["rpc.port".to_string()]);
+        assert!(report.comments_dropped);
+
+        let doc: Value = serde_yaml::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(doc["network"]["ip"], Value::from("192.168.111.10"));
+        assert_eq!(doc["network"]["p2p_port"], Value::from("31001"));
+        assert_eq!(doc["image"], Value::from("polkadot"));
+
+        // Nothing left to change the second time
+        let report = apply_file(&path, "rpc-polkadot-01", &mappings, true, false).unwrap();
+        assert!(report.changes.is_empty() && report.diff.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
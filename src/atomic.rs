@@ -0,0 +1,199 @@
+// File replacement that never leaves a half-written destination behind
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Written {
+    // The destination held the contents already and was not touched
+    Unchanged,
+    Replaced,
+}
+
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// A fresh temp file next to `path`, its name unique to this process and call
+// so concurrent writers of one destination never share one. Names left by a
+// crashed run with the same pid are skipped.
+fn create_tmp(path: &Path) -> io::Result<(PathBuf, fs::File)> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    loop {
+        let tmp_name = format!(
+            ".{}.{}.{}.portgen-tmp",
+            file_name.to_string_lossy(),
+            std::process::id(),
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let tmp = match path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            Some(dir) => dir.join(tmp_name),
+            None => tmp_name.into(),
+        };
+        match OpenOptions::new().write(true).create_new(true).open(&tmp) {
+            Ok(file) => return Ok((tmp, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// `<path>.bak`, the single backup --backup keeps
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".bak");
+    name.into()
+}
+
+// The temp file `fill` writes is synced and renamed over `path` (keeping its
+// permissions) only if `fill` succeeds; otherwise it is removed
+fn replace_with(path: &Path, fill: impl FnOnce(&mut fs::File) -> io::Result<()>) -> io::Result<()> {
+    let (tmp, mut file) = create_tmp(path)?;
+    let result = (|| {
+        fill(&mut file)?;
+        file.sync_all()?;
+        if let Ok(meta) = fs::metadata(path) {
+            fs::set_permissions(&tmp, meta.permissions())?;
+        }
+        fs::rename(&tmp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+// Writes next to the destination and renames over it, keeping its permissions
+pub fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    replace_with(path, |file| file.write_all(contents.as_bytes()))
+}
+
+// Creates `path` complete or not at all, never replacing it: the synced temp
+// file is hard linked into place, which fails with AlreadyExists when
+// something is there, even if it arrived while the temp file was written
+pub fn write_new(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let (tmp, mut file) = create_tmp(path)?;
+    let result = (|| {
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::hard_link(&tmp, path)
+    })();
+    let _ = fs::remove_file(&tmp);
+    result
+}
+
+// Generated output: left alone when unchanged, else replaced atomically, with
+// the previous contents kept in `<path>.bak` first when `backup` is set
+pub fn write_output(path: &Path, contents: &[u8], backup: bool) -> io::Result<Written> {
+    let previous = match fs::read(path) {
+        Ok(previous) => Some(previous),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    if previous.as_deref() == Some(contents) {
+        return Ok(Written::Unchanged);
+    }
+    if let Some(previous) = previous.filter(|_| backup) {
+        replace_with(&backup_path(path), |file| file.write_all(&previous))?;
+    }
+    replace_with(path, |file| file.write_all(contents))?;
+    Ok(Written::Replaced)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("portgen-atomic-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn interrupted_write_leaves_destination_and_no_temp_file() {
+        let dir = scratch("interrupted");
+        let path = dir.join("hosts");
+        fs::write(&path, "old\n").unwrap();
+
+        // Half the new contents, then the write dies (disk full, ^C, ...)
+        let result = replace_with(&path, |file| {
+            file.write_all(b"ne")?;
+            Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"))
+        });
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old\n");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn new_files_are_never_replaced() {
+        let dir = scratch("new");
+        let path = dir.join("rpc-polkadot-01.yaml");
+        write_new(&path, b"one\n").unwrap();
+        let e = write_new(&path, b"two\n").unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "one\n");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn concurrent_writers_never_mix_their_contents() {
+        let dir = scratch("concurrent");
+        let path = dir.join("hosts");
+        let contents: Vec<String> = (0..8)
+            .map(|writer| format!("{writer}\n").repeat(64 * 1024))
+            .collect();
+        std::thread::scope(|scope| {
+            for contents in &contents {
+                let path = &path;
+                scope.spawn(move || {
+                    for _ in 0..4 {
+                        write_atomic(path, contents).unwrap();
+                    }
+                });
+            }
+        });
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(contents.contains(&written));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn output_is_skipped_when_unchanged_and_backed_up_when_replaced() {
+        let dir = scratch("output");
+        let path = dir.join("units.service");
+        assert_eq!(
+            write_output(&path, b"one\n", true).unwrap(),
+            Written::Replaced
+        );
+        // Nothing to back up the first time
+        assert!(!backup_path(&path).exists());
+
+        assert_eq!(
+            write_output(&path, b"one\n", true).unwrap(),
+            Written::Unchanged
+        );
+        assert_eq!(
+            write_output(&path, b"two\n", true).unwrap(),
+            Written::Replaced
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "two\n");
+        assert_eq!(fs::read_to_string(backup_path(&path)).unwrap(), "one\n");
+
+        // Only one backup is kept, and only with `backup`
+        write_output(&path, b"three\n", false).unwrap();
+        assert_eq!(fs::read_to_string(backup_path(&path)).unwrap(), "one\n");
+        write_output(&path, b"four\n", true).unwrap();
+        assert_eq!(fs::read_to_string(backup_path(&path)).unwrap(), "three\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+// Append-only JSONL record of the addresses portgen handed out
+use crate::{canonical_name, config::Setting, time, NodeInfo, PortgenError};
+use serde_json::{json, Value};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::Path,
+};
+
+// FNV-1a of the effective settings as `key=value` lines, in hex: the same
+// settings hash the same whichever file, variable or flag they came from
+pub fn settings_hash(settings: &[Setting]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for setting in settings {
+        let line = format!("{}={}\n", setting.key, setting.value);
+        for byte in line.bytes() {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    format!("{hash:016x}")
+}
+
+// One line per node, appended in a single write under an exclusive lock so
+// concurrent invocations never interleave partial lines
+pub fn append(
+    path: &Path,
+    subcommand: &str,
+    schema_version: u8,
+    settings_hash: &str,
+    nodes: &[NodeInfo],
+) -> Result<(), PortgenError> {
+    let timestamp = time::now_rfc3339();
+    let mut lines = String::new();
+    for node in nodes {
+        let entry = json!({
+            "timestamp": timestamp,
+            "name": canonical_name(&node.name)?,
+            "address": node.address.to_string(),
+            "schema": schema_version,
+            "settings_hash": settings_hash,
+            "subcommand": subcommand,
+        });
+        lines.push_str(&entry.to_string());
+        lines.push('\n');
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|_| "failed to open audit log")?;
+    file.lock().map_err(|_| "failed to lock audit log")?;
+    let written = file
+        .write_all(lines.as_bytes())
+        .and_then(|()| file.sync_data());
+    let _ = file.unlock();
+    written.map_err(|_| "failed to write audit log".into())
+}
+
+// Entries recorded for a node, oldest first; aliases match their canonical name
+pub fn history(path: &Path, name: &str) -> Result<Vec<Value>, PortgenError> {
+    let wanted = canonical_name(name)?;
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(_) => return Err("failed to read audit log".into()),
+    };
+    let mut entries = Vec::new();
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let entry: Value = serde_json::from_str(line).map_err(|_| "corrupt audit log line")?;
+        if entry.get("name").and_then(Value::as_str) == Some(wanted.as_str()) {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Source;
+    use std::path::PathBuf;
+
+    #[test]
+    fn settings_hash_follows_values_not_sources() {
+        let setting = |value: &str, source| Setting {
+            key: "schema",
+            command: None,
+            arg: "schema",
+            value: value.to_string(),
+            source,
+            line: None,
+        };
+        let from_file = [setting("v2", Source::File(PathBuf::from("config.toml")))];
+        let from_env = [setting("v2", Source::Env("PORTGEN_SCHEMA".to_string()))];
+        assert_eq!(settings_hash(&from_file), settings_hash(&from_env));
+        assert_ne!(
+            settings_hash(&from_file),
+            settings_hash(&[setting("v1", Source::Builtin)])
+        );
+        assert_eq!(settings_hash(&[]).len(), 16);
+    }
+}
@@ -0,0 +1,181 @@
+// AWS security group ingress rules for node ports: Terraform
+// aws_security_group_rule blocks or `aws ec2 authorize-security-group-ingress`
+// input. Ports of one role, chain and network are folded into ranges so that
+// the rules stay within the per-group limit.
+use crate::{calculate_address, ranges::Cidr, ChainId, NodeName, PortKind, PortgenError};
+use serde_json::json;
+use std::{fmt::Write, ops::RangeInclusive};
+
+// Default inbound rules per security group
+pub const RULE_LIMIT: usize = 60;
+
+// AWS rejects longer rule descriptions
+const DESCRIPTION_MAX: usize = 255;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub role: String,
+    // Canonical chain name, `relay` for the relay chain
+    pub chain: String,
+    pub network: String,
+    pub kind: PortKind,
+    pub ports: RangeInclusive<u16>,
+    pub nodes: Vec<String>,
+}
+
+impl Rule {
+    fn description(&self) -> String {
+        let description = format!("portgen {} {}", self.kind, self.nodes.join(", "));
+        if description.len() <= DESCRIPTION_MAX {
+            return description;
+        }
+        let mut cut = DESCRIPTION_MAX - 3;
+        while !description.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        format!("{}...", &description[..cut])
+    }
+
+    fn tags(&self) -> [(&str, &str); 3] {
+        [
+            ("role", self.role.as_str()),
+            ("chain", self.chain.as_str()),
+            ("network", self.network.as_str()),
+        ]
+    }
+}
+
+pub struct SgOptions<'a> {
+    // Security group id; Terraform falls back to var.security_group_id
+    pub security_group: Option<&'a str>,
+    pub source_cidr: Cidr,
+}
+
+// One rule per run of contiguous ports of a kind, within a role, chain and
+// network so that the tags hold for every node of the rule
+pub fn rules(nodes: &[String], kinds: &[PortKind]) -> Result<Vec<Rule>, PortgenError> {
+    let mut ports = Vec::new();
+    for name in nodes {
+        let name = name.trim_end_matches(".yaml");
+        let node = NodeName::parse(name)?;
+        let chain = ChainId::from_str(node.chain.as_deref())?
+            .name()?
+            .unwrap_or("relay")
+            .to_string();
+        let addr = calculate_address(name)?;
+        for &kind in kinds {
+            let group = (node.role.clone(), chain.clone(), node.network.clone());
+            ports.push((group, kind, addr.port_of(kind)?.0, name.to_string()));
+        }
+    }
+    ports.sort();
+    ports.dedup();
+
+    let mut rules: Vec<Rule> = Vec::new();
+    for ((role, chain, network), kind, port, name) in ports {
+        match rules.last_mut() {
+            Some(rule)
+                if rule.role == role
+                    && rule.chain == chain
+                    && rule.network == network
+                    && rule.kind == kind
+                    && *rule.ports.end() + 1 >= port =>
+            {
+                rule.ports = *rule.ports.start()..=port;
+                rule.nodes.push(name);
+            }
+            _ => rules.push(Rule {
+                role,
+                chain,
+                network,
+                kind,
+                ports: port..=port,
+                nodes: vec![name],
+            }),
+        }
+    }
+    Ok(rules)
+}
+
+fn quote(s: &str) -> String {
+    serde_json::to_string(s).unwrap_or_default()
+}
+
+// aws_security_group_rule takes no tags, so they go in a comment above it
+pub fn to_terraform(rules: &[Rule], options: &SgOptions) -> String {
+    let security_group = options
+        .security_group
+        .map_or("var.security_group_id".to_string(), quote);
+    let mut out = String::new();
+    for rule in rules {
+        let resource = format!(
+            "{}_{}_{}_{}_{}",
+            rule.role,
+            rule.chain,
+            rule.network,
+            rule.kind,
+            rule.ports.start()
+        )
+        .replace('-', "_");
+        let tags: Vec<String> = rule
+            .tags()
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect();
+        let _ = writeln!(out, "# {}", tags.join(" "));
+        let _ = writeln!(
+            out,
+            "resource \"aws_security_group_rule\" \"{resource}\" {{"
+        );
+        let _ = writeln!(out, "  type              = \"ingress\"");
+        let _ = writeln!(out, "  security_group_id = {security_group}");
+        let _ = writeln!(out, "  protocol          = \"tcp\"");
+        let _ = writeln!(out, "  from_port         = {}", rule.ports.start());
+        let _ = writeln!(out, "  to_port           = {}", rule.ports.end());
+        let _ = writeln!(out, "  cidr_blocks       = [\"{}\"]", options.source_cidr);
+        let _ = writeln!(out, "  description       = {}", quote(&rule.description()));
+        let _ = writeln!(out, "}}\n");
+    }
+    out
+}
+
+// One --cli-input-json document per line, per role, chain and network since
+// the tags apply to every rule of a call
+pub fn to_cli(rules: &[Rule], options: &SgOptions) -> Result<String, PortgenError> {
+    let group_id = options
+        .security_group
+        .ok_or("--security-group is required for --format cli")?;
+    let mut out = String::new();
+    for group in rules.chunk_by(|a, b| a.tags() == b.tags()) {
+        let permissions: Vec<_> = group
+            .iter()
+            .map(|rule| {
+                json!({
+                    "IpProtocol": "tcp",
+                    "FromPort": rule.ports.start(),
+                    "ToPort": rule.ports.end(),
+                    "IpRanges": [{
+                        "CidrIp": options.source_cidr.to_string(),
+                        "Description": rule.description(),
+                    }],
+                })
+            })
+            .collect();
+        let tags: Vec<_> = group[0]
+            .tags()
+            .iter()
+            .map(|(key, value)| json!({ "Key": key, "Value": value }))
+            .collect();
+        let input = json!({
+            "GroupId": group_id,
+            "IpPermissions": permissions,
+            "TagSpecifications": [{
+                "ResourceType": "security-group-rule",
+                "Tags": tags,
+            }],
+        });
+        let line = serde_json::to_string(&input).map_err(|_| "failed to serialize rules")?;
+        let _ = writeln!(out, "{line}");
+    }
+    Ok(out)
+}
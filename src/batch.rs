@@ -0,0 +1,249 @@
+// Streaming batch pipeline: a reader thread cuts input lines into chunks, a
+// worker pool formats them and the caller's thread writes them back in input
+// order. At most `IN_FLIGHT_PER_WORKER * jobs` chunks exist at any time, so
+// memory stays flat however long the input is.
+use crate::PortgenError;
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, Write},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+const CHUNK_LINES: usize = 1024;
+const IN_FLIGHT_PER_WORKER: usize = 4;
+
+type Chunk = (usize, Vec<String>);
+
+fn read_chunks<R: BufRead>(
+    input: R,
+    work: mpsc::SyncSender<Chunk>,
+    tokens: mpsc::Receiver<()>,
+) -> Result<(), PortgenError> {
+    let mut seq = 0;
+    let mut chunk = Vec::with_capacity(CHUNK_LINES);
+    let mut lines = input.lines();
+    loop {
+        let line = lines
+            .next()
+            .transpose()
+            .map_err(|_| "failed to read stdin")?;
+        if let Some(line) = &line {
+            let line = line.trim();
+            if !line.is_empty() {
+                chunk.push(line.to_string());
+            }
+        }
+        let done = line.is_none();
+        if chunk.len() == CHUNK_LINES || (done && !chunk.is_empty()) {
+            // The writer hands a token back per written chunk; a closed channel
+            // means it stopped early, so stop reading too
+            if tokens.recv().is_err() {
+                return Ok(());
+            }
+            let full = std::mem::replace(&mut chunk, Vec::with_capacity(CHUNK_LINES));
+            if work.send((seq, full)).is_err() {
+                return Ok(());
+            }
+            seq += 1;
+        }
+        if done {
+            return Ok(());
+        }
+    }
+}
+
+// Formats every non-empty line of `input` with `format`, each record followed
+// by `terminator`. Stops at the first failing line; everything before it has
+// been written by then.
+pub fn process<R, W, F>(
+    input: R,
+    out: &mut W,
+    jobs: usize,
+    terminator: char,
+    format: F,
+) -> Result<(), PortgenError>
+where
+    R: BufRead + Send,
+    W: Write + ?Sized,
+    F: Fn(&str) -> Result<String, PortgenError> + Sync,
+{
+    let jobs = jobs.max(1);
+    let in_flight = jobs * IN_FLIGHT_PER_WORKER;
+    let (work_tx, work_rx) = mpsc::sync_channel::<Chunk>(in_flight);
+    let (result_tx, result_rx) = mpsc::sync_channel(in_flight);
+    let (token_tx, token_rx) = mpsc::sync_channel(in_flight);
+    for _ in 0..in_flight {
+        let _ = token_tx.send(());
+    }
+    let work_rx = Arc::new(Mutex::new(work_rx));
+    let format = &format;
+
+    thread::scope(|scope| {
+        let reader = scope.spawn(move || read_chunks(input, work_tx, token_rx));
+        for _ in 0..jobs {
+            let work_rx = Arc::clone(&work_rx);
+            let result_tx = result_tx.clone();
+            scope.spawn(move || loop {
+                let next = work_rx.lock().ok().and_then(|rx| rx.recv().ok());
+                let Some((seq, lines)) = next else {
+                    break;
+                };
+                let mut rendered = String::new();
+                let result = lines
+                    .iter()
+                    .try_for_each(|line| -> Result<(), PortgenError> {
+                        rendered.push_str(&format(line)?);
+                        rendered.push(terminator);
+                        Ok(())
+                    });
+                // Records before a failing line still get written
+                if result_tx.send((seq, rendered, result)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(result_tx);
+
+        let written = (|| -> Result<(), PortgenError> {
+            let mut next = 0;
+            let mut pending = BTreeMap::new();
+            for (seq, rendered, result) in &result_rx {
+                pending.insert(seq, (rendered, result));
+                while let Some((rendered, result)) = pending.remove(&next) {
+                    out.write_all(rendered.as_bytes())
+                        .map_err(|_| "failed to write output")?;
+                    result?;
+                    let _ = token_tx.send(());
+                    next += 1;
+                }
+            }
+            Ok(())
+        })();
+        // Unblocks the reader and workers when writing stopped early
+        drop(token_tx);
+        drop(result_rx);
+
+        let read = reader.join().map_err(|_| "batch reader panicked")?;
+        written.and(read)
+    })
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::calculate_address;
+    use std::io::{self, Read};
+
+    // Endless cycle of valid node names, cut off after `remaining` lines
+    struct Names {
+        remaining: usize,
+        pending: Vec<u8>,
+    }
+
+    const NAMES: &[&str] = &[
+        "rpc-polkadot-01",
+        "val-asset-hub-kusama-03",
+        "boot-paseo-00",
+    ];
+
+    impl Read for Names {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            while self.pending.len() < buf.len() && self.remaining > 0 {
+                self.remaining -= 1;
+                self.pending
+                    .extend_from_slice(NAMES[self.remaining % NAMES.len()].as_bytes());
+                self.pending.push(b'\n');
+            }
+            let n = buf.len().min(self.pending.len());
+            buf[..n].copy_from_slice(&self.pending[..n]);
+            self.pending.drain(..n);
+            Ok(n)
+        }
+    }
+
+    fn peak_rss_kib() -> usize {
+        std::fs::read_to_string("/proc/self/status")
+            .unwrap()
+            .lines()
+            .find_map(|line| line.strip_prefix("VmHWM:"))
+            .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+            .unwrap()
+    }
+
+    // Counts output records without keeping them
+    struct Counter(usize);
+
+    impl Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0 += buf.iter().filter(|&&b| b == b'\n').count();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // VmHWM is the peak of the whole process, which other tests running on
+    // their threads would raise: the measurement runs alone, in a copy of the
+    // test binary filtered down to it
+    #[test]
+    fn million_lines_with_bounded_memory() {
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([
+                "batch::tests::million_lines_in_own_process",
+                "--exact",
+                "--ignored",
+                "--test-threads=1",
+            ])
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success(), "{stdout}");
+        assert!(stdout.contains("1 passed"), "{stdout}");
+    }
+
+    #[test]
+    #[ignore = "run by million_lines_with_bounded_memory in its own process"]
+    fn million_lines_in_own_process() {
+        const LINES: usize = 1_000_000;
+        let before = peak_rss_kib();
+        let input = io::BufReader::new(Names {
+            remaining: LINES,
+            pending: Vec::new(),
+        });
+        let mut out = Counter(0);
+        process(input, &mut out, 4, '\n', |line| {
+            Ok(calculate_address(line)?.to_string())
+        })
+        .unwrap();
+        assert_eq!(out.0, LINES);
+        // The whole input alone would be ~20 MiB of names plus their output
+        assert!(peak_rss_kib() - before < 16 * 1024);
+    }
+
+    #[test]
+    fn keeps_input_order() {
+        let input: String = (0..5000)
+            .map(|i| format!("rpc-polkadot-0{}\n", i % 3 + 1))
+            .collect();
+        let mut out = Vec::new();
+        process(input.as_bytes(), &mut out, 8, '\n', |line| {
+            Ok(line.to_string())
+        })
+        .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), input);
+    }
+
+    #[test]
+    fn stops_at_first_invalid_line() {
+        let input = "rpc-polkadot-01\nnope\nrpc-polkadot-02\n";
+        let mut out = Vec::new();
+        let result = process(input.as_bytes(), &mut out, 2, '\n', |line| {
+            Ok(calculate_address(line)?.to_string())
+        });
+        assert!(result.is_err());
+        assert_eq!(String::from_utf8(out).unwrap(), "192.168.111.10:31001\n");
+    }
+}
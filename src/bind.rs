@@ -0,0 +1,55 @@
+// Local TCP port availability, checked by binding and releasing a listener
+use std::{
+    fmt,
+    io::ErrorKind,
+    net::{SocketAddrV4, TcpListener},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindStatus {
+    Free,
+    InUse,
+    // Usually a privileged (< 1024) port without the capability to bind it
+    AccessDenied,
+    // The IP is not configured on any local interface
+    NotLocal,
+    Failed(ErrorKind),
+}
+
+impl BindStatus {
+    // Distinct exit codes, 1 and 2 stay reserved for errors and usage errors
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Self::Free => 0,
+            Self::InUse => 3,
+            Self::AccessDenied => 4,
+            Self::NotLocal => 5,
+            Self::Failed(_) => 1,
+        }
+    }
+}
+
+impl fmt::Display for BindStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Free => f.write_str("free"),
+            Self::InUse => f.write_str("in use"),
+            Self::AccessDenied => f.write_str("permission denied (privileged port?)"),
+            Self::NotLocal => f.write_str("address not assigned to this host"),
+            Self::Failed(kind) => write!(f, "bind failed: {kind}"),
+        }
+    }
+}
+
+pub fn bind_check(addr: SocketAddrV4) -> BindStatus {
+    match TcpListener::bind(addr) {
+        // Dropping the listener releases the port straight away
+        Ok(_) => BindStatus::Free,
+        Err(e) => match e.kind() {
+            ErrorKind::AddrInUse => BindStatus::InUse,
+            ErrorKind::PermissionDenied => BindStatus::AccessDenied,
+            ErrorKind::AddrNotAvailable => BindStatus::NotLocal,
+            kind => BindStatus::Failed(kind),
+        },
+    }
+}
@@ -0,0 +1,131 @@
+// Prometheus scrape config for probing node ports through blackbox_exporter:
+// one job per module, with the relabelling that hands each target to the
+// exporter as ?target= and keeps it as the instance label
+use crate::{ChainId, NodeInfo, NodeName, PortKind, PortgenError};
+use serde_json::{json, Value};
+
+pub const DEFAULT_EXPORTER: &str = "127.0.0.1:9115";
+pub const DEFAULT_MODULE: &str = "tcp_connect";
+
+pub struct BlackboxOptions<'a> {
+    // host:port of blackbox_exporter
+    pub exporter: &'a str,
+    // Module probing the p2p ports, when no kinds are given
+    pub module: &'a str,
+    // Probe these ports, each with the module that suits it
+    pub kinds: &'a [PortKind],
+}
+
+// Module and target of a port: TCP connects for p2p and ws, HTTP for the RPC
+// health endpoint and the metrics page
+fn probe(kind: PortKind, ip: &str, port: u16) -> (&'static str, String) {
+    match kind {
+        PortKind::P2p | PortKind::Ws => ("tcp_connect", format!("{ip}:{port}")),
+        PortKind::Rpc => ("http_2xx", format!("http://{ip}:{port}/health")),
+        PortKind::Prometheus => ("http_2xx", format!("http://{ip}:{port}/metrics")),
+    }
+}
+
+// Labels of a node's targets, shared with the Grafana dashboard's variables
+pub fn labels(name: &str) -> Result<Value, PortgenError> {
+    let node = NodeName::parse(name)?;
+    let chain = ChainId::from_str(node.chain.as_deref())?
+        .name()?
+        .unwrap_or("relay");
+    Ok(json!({
+        "node": name,
+        "role": node.role,
+        "chain": chain,
+        "network": node.network,
+    }))
+}
+
+pub fn scrape_config(
+    nodes: &[NodeInfo],
+    options: &BlackboxOptions,
+) -> Result<String, PortgenError> {
+    // Static configs per module, modules in order of first appearance
+    let mut jobs: Vec<(String, Vec<Value>)> = Vec::new();
+    for node in nodes {
+        let name = node.name.trim_end_matches(".yaml");
+        let labels = labels(name)?;
+        let ip = node.address.ip.to_string();
+        let mut probes = Vec::new();
+        if options.kinds.is_empty() {
+            probes.push((options.module, format!("{ip}:{}", node.address.port)));
+        } else {
+            for &kind in options.kinds {
+                probes.push(probe(kind, &ip, node.address.port_of(kind)?.0));
+            }
+        }
+        for (module, target) in probes {
+            let static_config = json!({ "targets": [target], "labels": labels });
+            match jobs.iter_mut().find(|(known, _)| known == module) {
+                Some((_, configs)) => configs.push(static_config),
+                None => jobs.push((module.to_string(), vec![static_config])),
+            }
+        }
+    }
+
+    let scrape_configs: Vec<Value> = jobs
+        .into_iter()
+        .map(|(module, static_configs)| {
+            json!({
+                "job_name": format!("blackbox_{module}"),
+                "metrics_path": "/probe",
+                "params": { "module": [module] },
+                "static_configs": static_configs,
+                "relabel_configs": [
+                    { "source_labels": ["__address__"], "target_label": "__param_target" },
+                    { "source_labels": ["__param_target"], "target_label": "instance" },
+                    { "target_label": "__address__", "replacement": options.exporter },
+                ],
+            })
+        })
+        .collect();
+    let yaml = serde_yaml::to_string(&json!({ "scrape_configs": scrape_configs }))
+        .map_err(|_| "failed to serialize scrape config")?;
+    Ok(format!("# Generated by portgen\n{yaml}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, process::Command};
+
+    // Needs promtool: cargo test -- --ignored
+    #[test]
+    #[ignore]
+    fn scrape_config_passes_promtool_check_config() {
+        let nodes: Vec<NodeInfo> = [
+            "boot-polkadot-00",
+            "rpc-asset-hub-kusama-01",
+            "val-westend-04",
+            "col-people-paseo-01",
+        ]
+        .into_iter()
+        .map(|name| NodeInfo::new(name.to_string()).unwrap())
+        .collect();
+        let path =
+            std::env::temp_dir().join(format!("portgen-blackbox-{}.yml", std::process::id()));
+        for kinds in [&[][..], &[PortKind::P2p, PortKind::Rpc]] {
+            let options = BlackboxOptions {
+                exporter: DEFAULT_EXPORTER,
+                module: DEFAULT_MODULE,
+                kinds,
+            };
+            fs::write(&path, scrape_config(&nodes, &options).unwrap()).unwrap();
+            let output = Command::new("promtool")
+                .args(["check", "config"])
+                .arg(&path)
+                .output()
+                .unwrap();
+            assert!(
+                output.status.success(),
+                "{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        fs::remove_file(&path).unwrap();
+    }
+}
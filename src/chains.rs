@@ -0,0 +1,271 @@
+// Chains from the config file added to the built-in registry. Like the roles,
+// the merged registry is installed once at start-up, before any name is
+// parsed, and everything chain-related reads it through `table`.
+use crate::{ChainId, PortgenError, CHAINS, MAX_CHAIN_ID};
+use std::{ops::RangeInclusive, sync::OnceLock};
+
+// Ids handed to chains registered without one, clear of the built-in ids and
+// room for more of them below
+pub const AUTO_IDS: RangeInclusive<u16> = 50..=MAX_CHAIN_ID;
+
+// A `[chains.<name>]` table of the config file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainDef {
+    pub name: String,
+    // None has one assigned above the highest registered id
+    pub id: Option<u16>,
+    // `file:line` of the table
+    pub location: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chain {
+    pub id: u16,
+    // The first is the canonical name
+    pub names: &'static [&'static str],
+    // `builtin` or the location of the definition
+    pub source: String,
+    pub auto_assigned: bool,
+}
+
+struct Registry {
+    table: Vec<(u16, &'static [&'static str])>,
+    chains: Vec<Chain>,
+}
+
+static REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+// The effective registry by id: the built-in chains until `install` ran
+pub fn table() -> &'static [(u16, &'static [&'static str])] {
+    REGISTRY.get().map_or(CHAINS, |registry| &registry.table)
+}
+
+fn find(id: u16) -> Option<&'static Chain> {
+    REGISTRY.get()?.chains.iter().find(|chain| chain.id == id)
+}
+
+// Where the chain was defined, `builtin` or `file:line`
+pub fn source(id: u16) -> Option<&'static str> {
+    match REGISTRY.get() {
+        Some(_) => find(id).map(|chain| chain.source.as_str()),
+        None => CHAINS
+            .iter()
+            .any(|&(known, _)| known == id)
+            .then_some("builtin"),
+    }
+}
+
+pub fn is_auto_assigned(id: u16) -> bool {
+    find(id).is_some_and(|chain| chain.auto_assigned)
+}
+
+// The id a chain registered without one gets: FNV-1a of its name into
+// AUTO_IDS, so adding or removing other chains never moves it
+pub fn auto_id(name: &str) -> u16 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in name.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    let slots = u64::from(AUTO_IDS.end() - AUTO_IDS.start() + 1);
+    AUTO_IDS.start() + (hash % slots) as u16
+}
+
+// Ids for chains registered without one, by name. Two chains on one id are an
+// error rather than one of them moving: the first needs an explicit id.
+pub(crate) fn assign(
+    names: &[&str],
+    registry: &[(u16, &[&str])],
+) -> Result<Vec<(String, ChainId)>, PortgenError> {
+    let mut names = names.to_vec();
+    names.sort_unstable();
+    names.dedup();
+    let mut assigned: Vec<(String, ChainId)> = Vec::new();
+    for name in names {
+        ChainId::validate_name(name)?;
+        if registry.iter().any(|(_, known)| known.contains(&name)) {
+            return Err("chain name is already registered".into());
+        }
+        let id = auto_id(name);
+        let taken = registry
+            .iter()
+            .find(|&&(known, _)| known == id)
+            .map(|(_, names)| names[0])
+            .or_else(|| {
+                assigned
+                    .iter()
+                    .find(|(_, known)| known.0 == id)
+                    .map(|(other, _)| other.as_str())
+            });
+        if let Some(other) = taken {
+            return Err(PortgenError::ChainIdTaken {
+                name: name.to_string(),
+                id,
+                other: other.to_string(),
+            });
+        }
+        assigned.push((name.to_string(), ChainId::new(id)?));
+    }
+    Ok(assigned)
+}
+
+fn def_error(def: &ChainDef, message: String) -> PortgenError {
+    PortgenError::Config {
+        location: def.location.clone(),
+        key: Some(format!("chains.{}", def.name)),
+        message,
+    }
+}
+
+fn leak_name(name: &str) -> &'static [&'static str] {
+    let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+    Box::leak(vec![name].into_boxed_slice())
+}
+
+// The built-in chains with `defs` added, by id. Chains with an id are placed
+// first, the rest get the one derived from their name.
+pub fn merge(defs: &[ChainDef]) -> Result<Vec<Chain>, PortgenError> {
+    let mut chains: Vec<Chain> = CHAINS
+        .iter()
+        .map(|&(id, names)| Chain {
+            id,
+            names,
+            source: "builtin".to_string(),
+            auto_assigned: false,
+        })
+        .collect();
+    for def in defs {
+        ChainId::validate_name(&def.name).map_err(|e| def_error(def, e.to_string()))?;
+        if let Some(known) = chains
+            .iter()
+            .find(|chain| chain.names.contains(&&*def.name))
+        {
+            return Err(def_error(
+                def,
+                format!("'{}' is already a name of chain {}", def.name, known.id),
+            ));
+        }
+        let Some(id) = def.id else {
+            continue;
+        };
+        if id == 0 {
+            return Err(def_error(def, "id 0 is the relay chain".to_string()));
+        }
+        ChainId::new(id).map_err(|e| def_error(def, e.to_string()))?;
+        if let Some(known) = chains.iter().find(|chain| chain.id == id) {
+            return Err(def_error(
+                def,
+                format!("id {id} is already used by chain '{}'", known.names[0]),
+            ));
+        }
+        chains.push(Chain {
+            id,
+            names: leak_name(&def.name),
+            source: def.location.clone(),
+            auto_assigned: false,
+        });
+    }
+
+    let without_id: Vec<&ChainDef> = defs.iter().filter(|def| def.id.is_none()).collect();
+    if let Some(&first) = without_id.first() {
+        let placed: Vec<(u16, &[&str])> =
+            chains.iter().map(|chain| (chain.id, chain.names)).collect();
+        let names: Vec<&str> = without_id.iter().map(|def| def.name.as_str()).collect();
+        let def_of = |name: &str| {
+            without_id
+                .iter()
+                .find(|def| def.name == name)
+                .copied()
+                .unwrap_or(first)
+        };
+        let assigned = assign(&names, &placed).map_err(|e| match &e {
+            PortgenError::ChainIdTaken { name, .. } => def_error(def_of(name), e.to_string()),
+            _ => def_error(first, e.to_string()),
+        })?;
+        for (name, id) in assigned {
+            chains.push(Chain {
+                id: id.0,
+                names: leak_name(&name),
+                source: def_of(&name).location.clone(),
+                auto_assigned: true,
+            });
+        }
+    }
+    chains.sort_by_key(|chain| chain.id);
+    Ok(chains)
+}
+
+// Merges `defs` and installs the result for the rest of the process
+pub fn install(defs: &[ChainDef]) -> Result<(), PortgenError> {
+    let chains = merge(defs)?;
+    let table = chains.iter().map(|chain| (chain.id, chain.names)).collect();
+    REGISTRY
+        .set(Registry { table, chains })
+        .map_err(|_| "chains are already installed".into())
+}
+
+// The id `name` has or would get in the config file without one
+pub fn suggest(name: &str) -> Result<ChainId, PortgenError> {
+    let configured = REGISTRY.get().and_then(|registry| {
+        registry
+            .chains
+            .iter()
+            .find(|chain| chain.auto_assigned && chain.names[0] == name)
+    });
+    match configured {
+        Some(chain) => ChainId::new(chain.id),
+        None => Ok(assign(&[name], table())?[0].1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn def(name: &str, id: Option<u16>) -> ChainDef {
+        ChainDef {
+            name: name.to_string(),
+            id,
+            location: "config.toml:1".to_string(),
+        }
+    }
+
+    #[test]
+    fn auto_ids_do_not_move_when_chains_are_added() {
+        let id = |defs: &[ChainDef], name: &str| {
+            let merged = merge(defs).unwrap();
+            let chain = merged.iter().find(|chain| chain.names[0] == name).unwrap();
+            (chain.id, chain.auto_assigned)
+        };
+        let defs = [def("my-chain", None), def("fixed", Some(40))];
+        assert_eq!(id(&defs, "my-chain"), (67, true));
+        assert_eq!(id(&defs, "fixed"), (40, false));
+        // A chain sorting before it, or an explicit id above it, changes nothing
+        let more = [
+            def("aaa", None),
+            def("my-chain", None),
+            def("high", Some(99)),
+            def("zeta", None),
+        ];
+        assert_eq!(id(&more, "my-chain"), (67, true));
+        assert_eq!(id(&more, "aaa"), (94, true));
+        assert_eq!(id(&more, "zeta"), (53, true));
+
+        // Two names on one id are reported, not resolved by moving either
+        assert_eq!(
+            merge(&[def("my-chain", None), def("chain-39", None)])
+                .unwrap_err()
+                .to_string(),
+            "config error at config.toml:1: chains.my-chain: chain 'my-chain' would get id 67, \
+             already used by chain 'chain-39'; give it an id"
+        );
+        assert_eq!(
+            merge(&[def("moonriver", None)]).unwrap_err().to_string(),
+            "config error at config.toml:1: chains.moonriver: 'moonriver' is already a name of chain 20"
+        );
+        assert_eq!(
+            merge(&[def("mine", Some(1))]).unwrap_err().to_string(),
+            "config error at config.toml:1: chains.mine: id 1 is already used by chain 'asset-hub'"
+        );
+    }
+}
@@ -0,0 +1,432 @@
+// Layered settings: flags over PORTGEN_* environment variables over the
+// --config file over the XDG config file over built-in defaults. Flags and
+// variables are clap's; the files below them end up as clap defaults, so clap
+// still parses the values.
+use crate::{chains::ChainDef, roles::RoleDef, PortgenError};
+use std::{
+    env, fmt, fs, io,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+use toml_edit::{ImDocument, Item, TableLike};
+
+// Config file key, the subcommand owning the argument it sets (None for the
+// top level) and the argument's id
+pub const KEYS: &[(&str, Option<&str>, &str)] = &[
+    ("schema", None, "schema"),
+    ("format", None, "output"),
+    ("binary", None, "binary"),
+    ("image", None, "image"),
+    ("docker_subnet", None, "docker_subnet"),
+    ("reservations", None, "reservations"),
+    ("role_synonyms", None, "role_synonyms"),
+    ("denylist", None, "denylist"),
+    ("nomad.datacenters", None, "datacenters"),
+    ("nomad.region", None, "region"),
+    ("nomad.namespace", None, "namespace"),
+    ("nomad.job_type", None, "job_type"),
+    ("rpc_proxy.max_batch_size", None, "max_batch_size"),
+    ("rpc_proxy.timeout_ms", None, "timeout_ms"),
+    ("systemd.user", Some("systemd-unit"), "user"),
+    ("systemd.group", Some("systemd-unit"), "group"),
+    ("systemd.restart", Some("systemd-unit"), "restart"),
+    ("systemd.restart_sec", Some("systemd-unit"), "restart_sec"),
+    ("systemd.limit_nofile", Some("systemd-unit"), "limit_nofile"),
+    ("systemd.memory_max", Some("systemd-unit"), "memory_max"),
+    ("systemd.cpu_quota", Some("systemd-unit"), "cpu_quota"),
+    ("selinux.type", Some("selinux"), "port_type"),
+    ("selinux.role_types", Some("selinux"), "role_types"),
+    ("remote_audit.user", Some("remote-audit"), "user"),
+    (
+        "remote_audit.identity_file",
+        Some("remote-audit"),
+        "identity_file",
+    ),
+    ("remote_audit.jump", Some("remote-audit"), "jump"),
+    ("remote_audit.command", Some("remote-audit"), "command"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Flag,
+    Env(String),
+    File(PathBuf),
+    Builtin,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Flag => f.write_str("flag"),
+            Self::Env(var) => write!(f, "env {var}"),
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Builtin => f.write_str("builtin"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Setting {
+    pub key: &'static str,
+    pub command: Option<&'static str>,
+    pub arg: &'static str,
+    // Lists are joined with commas, as on the command line
+    pub value: String,
+    pub source: Source,
+    // Line of the key, for values read from a file
+    pub line: Option<usize>,
+}
+
+impl Setting {
+    // Where the value came from, as `file:line` or the variable name
+    pub fn location(&self) -> String {
+        match (&self.source, self.line) {
+            (Source::File(path), Some(line)) => format!("{}:{line}", path.display()),
+            (source, _) => source.to_string(),
+        }
+    }
+}
+
+// Variable setting a flag: PORTGEN_<FLAG> at the top level,
+// PORTGEN_<SUBCOMMAND>_<FLAG> under a subcommand, e.g.
+// PORTGEN_BIND_CHECK_PORT_BASE for `bind-check --port-base`
+pub fn env_var(subcommands: &[&str], long: &str) -> String {
+    let mut var = String::from("PORTGEN");
+    for part in subcommands.iter().chain([&long]) {
+        var.push('_');
+        var.push_str(&part.replace('-', "_").to_uppercase());
+    }
+    var
+}
+
+// $XDG_CONFIG_HOME/portgen/config.toml, falling back to ~/.config
+pub fn xdg_path() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("portgen").join("config.toml"))
+}
+
+// The --config path, read before clap runs since it decides clap's defaults
+pub fn explicit_path(args: impl IntoIterator<Item = String>) -> Option<PathBuf> {
+    let mut args = args.into_iter().take_while(|arg| arg != "--");
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    env::var_os("PORTGEN_CONFIG").map(PathBuf::from)
+}
+
+fn line_of(text: &str, offset: usize) -> usize {
+    text[..offset.min(text.len())].matches('\n').count() + 1
+}
+
+fn config_error(location: String, key: Option<&str>, message: &str) -> PortgenError {
+    PortgenError::Config {
+        location,
+        key: key.map(str::to_string),
+        message: message.to_string(),
+    }
+}
+
+fn scalar(item: &Item) -> Option<String> {
+    if let Some(array) = item.as_array() {
+        let items: Option<Vec<&str>> = array.iter().map(|value| value.as_str()).collect();
+        return items.map(|items| items.join(","));
+    }
+    let value = item.as_value()?;
+    value
+        .as_str()
+        .map(str::to_string)
+        .or_else(|| value.as_integer().map(|n| n.to_string()))
+}
+
+fn collect(
+    table: &dyn TableLike,
+    prefix: &str,
+    path: &Path,
+    text: &str,
+    settings: &mut Vec<Setting>,
+) -> Result<(), PortgenError> {
+    for (name, item) in table.iter() {
+        let key = format!("{prefix}{name}");
+        let line = table
+            .key(name)
+            .and_then(|key| key.span())
+            .map(|span| line_of(text, span.start));
+        let location = match line {
+            Some(line) => format!("{}:{line}", path.display()),
+            None => path.display().to_string(),
+        };
+        // Role and chain definitions are no flags, `load_roles` and
+        // `load_chains` read them
+        if prefix.is_empty() && (key == "roles" || key == "chains") {
+            continue;
+        }
+        if let Some(section) = item.as_table_like() {
+            if prefix.is_empty() {
+                collect(section, &format!("{key}."), path, text, settings)?;
+                continue;
+            }
+        }
+        let Some(&(key, command, arg)) = KEYS.iter().find(|(known, ..)| *known == key) else {
+            return Err(config_error(location, Some(&key), "unknown key"));
+        };
+        let value = scalar(item).ok_or_else(|| {
+            config_error(
+                location,
+                Some(key),
+                "expected a string, an integer or a list of strings",
+            )
+        })?;
+        settings.push(Setting {
+            key,
+            command,
+            arg,
+            value,
+            source: Source::File(path.to_path_buf()),
+            line,
+        });
+    }
+    Ok(())
+}
+
+// Text and document of one config file, None when it is missing and not
+// `required`
+fn parse_file(
+    path: &Path,
+    required: bool,
+) -> Result<Option<(String, ImDocument<String>)>, PortgenError> {
+    let location = path.display().to_string();
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound && !required => return Ok(None),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(config_error(location, None, "file not found"))
+        }
+        Err(_) => return Err(config_error(location, None, "failed to read file")),
+    };
+    let doc = ImDocument::parse(text.clone()).map_err(|e| {
+        let message = e.message().trim_end().replace('\n', "; ");
+        let Some(span) = e.span() else {
+            return config_error(location.clone(), None, &message);
+        };
+        // The key of a `key = value` line the parser gave up on
+        let line = line_of(&text, span.start);
+        let key = text
+            .lines()
+            .nth(line - 1)
+            .and_then(|line| line.split_once('='))
+            .map(|(key, _)| key.trim())
+            .filter(|key| !key.is_empty());
+        config_error(format!("{location}:{line}"), key, &message)
+    })?;
+    Ok(Some((text, doc)))
+}
+
+// Settings of one config file; a missing file is only an error when `required`
+pub fn load_file(path: &Path, required: bool) -> Result<Vec<Setting>, PortgenError> {
+    let mut settings = Vec::new();
+    if let Some((text, doc)) = parse_file(path, required)? {
+        collect(doc.as_table(), "", path, &text, &mut settings)?;
+    }
+    Ok(settings)
+}
+
+// `7` or `7-9`
+fn digit_range(item: &Item) -> Option<RangeInclusive<u8>> {
+    let value = item.as_value()?;
+    if let Some(n) = value.as_integer() {
+        let n = u8::try_from(n).ok()?;
+        return Some(n..=n);
+    }
+    let (first, last) = value.as_str()?.split_once('-')?;
+    Some(first.trim().parse().ok()?..=last.trim().parse().ok()?)
+}
+
+// `[roles.<token>]` tables of one config file
+pub fn roles_of_file(path: &Path, required: bool) -> Result<Vec<RoleDef>, PortgenError> {
+    let Some((text, doc)) = parse_file(path, required)? else {
+        return Ok(Vec::new());
+    };
+    let Some(item) = doc.as_table().get("roles") else {
+        return Ok(Vec::new());
+    };
+    let at = |key: Option<&toml_edit::Key>| match key.and_then(|key| key.span()) {
+        Some(span) => format!("{}:{}", path.display(), line_of(&text, span.start)),
+        None => path.display().to_string(),
+    };
+    let roles = item.as_table_like().ok_or_else(|| {
+        config_error(
+            at(doc.as_table().key("roles")),
+            Some("roles"),
+            "expected a table",
+        )
+    })?;
+
+    let mut defs = Vec::new();
+    for (token, item) in roles.iter() {
+        let location = at(roles.key(token));
+        let key = format!("roles.{token}");
+        let table = item
+            .as_table_like()
+            .ok_or_else(|| config_error(location.clone(), Some(&key), "expected a table"))?;
+        let (mut name, mut parachain_only, mut override_builtin) = (None, false, false);
+        let (mut instances, mut port_digits, mut ip_digit) = (None, None, None);
+        for (field, value) in table.iter() {
+            let key = format!("{key}.{field}");
+            let field_location = at(table.key(field));
+            let invalid = |message| config_error(field_location.clone(), Some(&key), message);
+            let flag = || {
+                value
+                    .as_bool()
+                    .ok_or_else(|| invalid("expected true or false"))
+            };
+            let range = || digit_range(value).ok_or_else(|| invalid("expected N or \"N-M\""));
+            match field {
+                "name" => {
+                    let value = value.as_str().ok_or_else(|| invalid("expected a string"))?;
+                    name = Some(value.to_string());
+                }
+                "instances" => instances = Some(range()?),
+                "port_digits" => {
+                    let digits = range()?;
+                    port_digits = Some(u16::from(*digits.start())..=u16::from(*digits.end()));
+                }
+                "ip_digit" => {
+                    let digit = value.as_integer().and_then(|n| u8::try_from(n).ok());
+                    ip_digit = Some(digit.ok_or_else(|| invalid("expected an integer"))?);
+                }
+                "parachain_only" => parachain_only = flag()?,
+                "override" => override_builtin = flag()?,
+                _ => return Err(invalid("unknown key")),
+            }
+        }
+        let (Some(instances), Some(port_digits), Some(ip_digit)) =
+            (instances, port_digits, ip_digit)
+        else {
+            return Err(config_error(
+                location,
+                Some(&key),
+                "roles need instances, port_digits and ip_digit",
+            ));
+        };
+        defs.push(RoleDef {
+            token: token.to_string(),
+            name,
+            instances,
+            port_digits,
+            ip_digit,
+            parachain_only,
+            override_builtin,
+            location,
+        });
+    }
+    Ok(defs)
+}
+
+// Roles defined in the config files, the --config file's replacing the XDG
+// file's definition of the same token
+pub fn load_roles(explicit: Option<&Path>) -> Result<Vec<RoleDef>, PortgenError> {
+    let mut defs: Vec<RoleDef> = Vec::new();
+    let layers = xdg_path()
+        .map(|path| (path, false))
+        .into_iter()
+        .chain(explicit.map(|path| (path.to_path_buf(), true)));
+    for (path, required) in layers {
+        for def in roles_of_file(&path, required)? {
+            defs.retain(|earlier| earlier.token != def.token);
+            defs.push(def);
+        }
+    }
+    Ok(defs)
+}
+
+// `[chains.<name>]` tables of one config file, each with an optional `id`
+pub fn chains_of_file(path: &Path, required: bool) -> Result<Vec<ChainDef>, PortgenError> {
+    let Some((text, doc)) = parse_file(path, required)? else {
+        return Ok(Vec::new());
+    };
+    let Some(item) = doc.as_table().get("chains") else {
+        return Ok(Vec::new());
+    };
+    let at = |key: Option<&toml_edit::Key>| match key.and_then(|key| key.span()) {
+        Some(span) => format!("{}:{}", path.display(), line_of(&text, span.start)),
+        None => path.display().to_string(),
+    };
+    let chains = item.as_table_like().ok_or_else(|| {
+        config_error(
+            at(doc.as_table().key("chains")),
+            Some("chains"),
+            "expected a table",
+        )
+    })?;
+
+    let mut defs = Vec::new();
+    for (name, item) in chains.iter() {
+        let location = at(chains.key(name));
+        let key = format!("chains.{name}");
+        let table = item
+            .as_table_like()
+            .ok_or_else(|| config_error(location.clone(), Some(&key), "expected a table"))?;
+        let mut id = None;
+        for (field, value) in table.iter() {
+            let key = format!("{key}.{field}");
+            let invalid = |message| config_error(at(table.key(field)), Some(&key), message);
+            match field {
+                "id" => {
+                    let value = value.as_integer().and_then(|n| u16::try_from(n).ok());
+                    id = Some(value.ok_or_else(|| invalid("expected an integer"))?);
+                }
+                _ => return Err(invalid("unknown key")),
+            }
+        }
+        defs.push(ChainDef {
+            name: name.to_string(),
+            id,
+            location,
+        });
+    }
+    Ok(defs)
+}
+
+// Chains defined in the config files, the --config file's replacing the XDG
+// file's definition of the same name
+pub fn load_chains(explicit: Option<&Path>) -> Result<Vec<ChainDef>, PortgenError> {
+    let mut defs: Vec<ChainDef> = Vec::new();
+    let layers = xdg_path()
+        .map(|path| (path, false))
+        .into_iter()
+        .chain(explicit.map(|path| (path.to_path_buf(), true)));
+    for (path, required) in layers {
+        for def in chains_of_file(&path, required)? {
+            defs.retain(|earlier| earlier.name != def.name);
+            defs.push(def);
+        }
+    }
+    Ok(defs)
+}
+
+// Every setting given in a config file, the winning layer per key
+pub fn load(explicit: Option<&Path>) -> Result<Vec<Setting>, PortgenError> {
+    let mut layers = Vec::new();
+    if let Some(path) = xdg_path() {
+        layers.extend(load_file(&path, false)?);
+    }
+    if let Some(path) = explicit {
+        layers.extend(load_file(path, true)?);
+    }
+    // Later layers win
+    let mut settings: Vec<Setting> = Vec::new();
+    for setting in layers {
+        settings.retain(|earlier| earlier.key != setting.key);
+        settings.push(setting);
+    }
+    settings.sort_by_key(|setting| KEYS.iter().position(|(key, ..)| *key == setting.key));
+    Ok(settings)
+}
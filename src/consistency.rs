@@ -0,0 +1,97 @@
+// Smoke test of the formula itself: a node computes to the same address every
+// time, and its port and IP decompose back into the parts of its name. Both
+// hold for the pure formula today; a violation means something slipped in.
+use crate::{decompose_port, ChainId, Network, NodeName, PortSchema, PortgenError, Role};
+
+fn violation(name: &str, message: String) -> PortgenError {
+    PortgenError::ConsistencyViolation {
+        name: name.to_string(),
+        message,
+    }
+}
+
+pub fn check(schema: &PortSchema, node_str: &str) -> Result<(), PortgenError> {
+    let first = schema.address(node_str)?;
+    let second = schema.address(node_str)?;
+    if first.port != second.port || first.ip != second.ip {
+        return Err(violation(
+            node_str,
+            format!("computed {first} and then {second}"),
+        ));
+    }
+    let port = schema.port(node_str)?;
+    if port != first.port {
+        return Err(violation(
+            node_str,
+            format!("port {port} differs from the address's port {}", first.port),
+        ));
+    }
+
+    let node = NodeName::parse(node_str)?;
+    let network = node.network.parse::<Network>()? as u8;
+    let chain_id = ChainId::from_str(node.chain.as_deref())?.0 as u8;
+    let role = Role::from_str(&node.role, &node.instance)?;
+
+    let expected = (network, chain_id, role.to_digit() as u8);
+    let decoded = decompose_port(port.0, schema.port_base)?;
+    if decoded != expected {
+        return Err(violation(
+            node_str,
+            format!(
+                "port {port} decodes to network {}, chain {}, role digit {}, \
+                 expected {}, {}, {}",
+                decoded.0, decoded.1, decoded.2, expected.0, expected.1, expected.2
+            ),
+        ));
+    }
+
+    let expected = (
+        role.to_ip_digit(),
+        network,
+        role.get_instance_number(),
+        chain_id,
+    );
+    let decoded = schema.decompose_ip(first.ip)?;
+    if decoded != expected {
+        return Err(violation(
+            node_str,
+            format!(
+                "ip {} decodes to role digit {}, network {}, instance {}, chain {}, \
+                 expected {}, {}, {}, {}",
+                first.ip,
+                decoded.0,
+                decoded.1,
+                decoded.2,
+                decoded.3,
+                expected.0,
+                expected.1,
+                expected.2,
+                expected.3
+            ),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{enumerate_nodes, PORT_SCHEMA_V1};
+
+    #[test]
+    fn every_slot_is_deterministic_and_invertible() {
+        for schema in PortSchema::ALL {
+            for network in Network::ALL {
+                for name in enumerate_nodes(network) {
+                    // Other errors are slots the scheme enumerates but refuses
+                    if let Err(e @ PortgenError::ConsistencyViolation { .. }) =
+                        check(&schema, &name)
+                    {
+                        panic!("{e}");
+                    }
+                }
+            }
+        }
+        assert!(check(&PORT_SCHEMA_V1, "rpc-statemint-polkadot-01").is_ok());
+    }
+}
@@ -0,0 +1,194 @@
+// Ports and addresses taken by other software or reserved for appliances. A
+// node whose ip or any of its ports lands on one is refused, with the reason
+// the entry was given.
+use crate::{enumerate_nodes, Network, NodeInfo, PortKind, PortgenError};
+use std::{fmt, net::Ipv4Addr, ops::RangeInclusive, str::FromStr};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Denied {
+    Ports(RangeInclusive<u16>),
+    Ip(Ipv4Addr),
+    // Fourth octet on every subnet, `.250`
+    Host(u8),
+}
+
+// `31337`, `31300-31309`, `192.168.111.250` or `.250`, each with an optional
+// `=reason`; reasons can't hold commas, which separate entries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DenyEntry {
+    pub denied: Denied,
+    pub reason: String,
+}
+
+impl FromStr for DenyEntry {
+    type Err = PortgenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (target, reason) = s.split_once('=').unwrap_or((s, ""));
+        let target = target.trim();
+        let denied = if let Some(host) = target.strip_prefix('.') {
+            Denied::Host(
+                host.parse()
+                    .map_err(|_| "denylisted host must be .0-.255")?,
+            )
+        } else if target.contains('.') {
+            Denied::Ip(
+                target
+                    .parse()
+                    .map_err(|_| "invalid denylisted IP address")?,
+            )
+        } else {
+            let (first, last) = target.split_once('-').unwrap_or((target, target));
+            let first: u16 = first.parse().map_err(|_| "invalid denylisted port")?;
+            let last: u16 = last.parse().map_err(|_| "invalid denylisted port")?;
+            if first > last {
+                return Err("denylisted port range runs backwards".into());
+            }
+            Denied::Ports(first..=last)
+        };
+        Ok(Self {
+            denied,
+            reason: reason.trim().to_string(),
+        })
+    }
+}
+
+impl fmt::Display for DenyEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.denied {
+            Denied::Ports(ports) if ports.start() == ports.end() => write!(f, "{}", ports.start()),
+            Denied::Ports(ports) => write!(f, "{}-{}", ports.start(), ports.end()),
+            Denied::Ip(ip) => write!(f, "{ip}"),
+            Denied::Host(host) => write!(f, ".{host}"),
+        }?;
+        if !self.reason.is_empty() {
+            write!(f, "={}", self.reason)?;
+        }
+        Ok(())
+    }
+}
+
+// A node value on the denylist, `port 31337` or `ip 192.168.111.250`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hit {
+    pub value: String,
+    pub reason: String,
+}
+
+impl fmt::Display for Hit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is denylisted", self.value)?;
+        if !self.reason.is_empty() {
+            write!(f, " ({})", self.reason)?;
+        }
+        Ok(())
+    }
+}
+
+// Every denylisted value of the node: its ip and the ports of all four bands
+pub fn hits(entries: &[DenyEntry], node: &NodeInfo) -> Result<Vec<Hit>, PortgenError> {
+    let ip = node.address.ip;
+    let mut ports = Vec::new();
+    for kind in PortKind::ALL {
+        ports.push(node.address.port_of(kind)?.0);
+    }
+    let mut hits = Vec::new();
+    for entry in entries {
+        let value = match &entry.denied {
+            Denied::Ports(range) => ports
+                .iter()
+                .find(|port| range.contains(port))
+                .map(|port| format!("port {port}")),
+            Denied::Ip(denied) => (*denied == ip).then(|| format!("ip {ip}")),
+            Denied::Host(host) => (ip.octets()[3] == *host).then(|| format!("ip {ip}")),
+        };
+        if let Some(value) = value {
+            hits.push(Hit {
+                value,
+                reason: entry.reason.clone(),
+            });
+        }
+    }
+    Ok(hits)
+}
+
+// The node's first denylist hit as an error
+pub fn check(entries: &[DenyEntry], node: &NodeInfo) -> Result<(), PortgenError> {
+    match hits(entries, node)?.into_iter().next() {
+        Some(hit) => Err(PortgenError::Denylisted {
+            name: node.name.clone(),
+            value: hit.value,
+            reason: hit.reason,
+        }),
+        None => Ok(()),
+    }
+}
+
+// Every slot of every network that lands on the denylist, to plan around
+pub fn collisions(entries: &[DenyEntry]) -> Result<Vec<(String, Hit)>, PortgenError> {
+    let mut collisions = Vec::new();
+    if entries.is_empty() {
+        return Ok(collisions);
+    }
+    for network in Network::ALL {
+        for name in enumerate_nodes(network) {
+            // Slots the scheme enumerates but refuses, like relay chain collators
+            let Ok(node) = NodeInfo::new(name) else {
+                continue;
+            };
+            for hit in hits(entries, &node)? {
+                collisions.push((node.name.clone(), hit));
+            }
+        }
+    }
+    Ok(collisions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(specs: &[&str]) -> Vec<DenyEntry> {
+        specs.iter().map(|spec| spec.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn parses_ports_ranges_ips_and_hosts_with_reasons() {
+        for spec in [
+            "31337=squatted by metrics-relay",
+            "31300-31309",
+            "192.168.111.250=appliance",
+            ".250=load balancer",
+        ] {
+            assert_eq!(spec.parse::<DenyEntry>().unwrap().to_string(), spec);
+        }
+        for spec in ["31309-31300", "70000", "192.168.1", ".256", "=reason"] {
+            assert!(spec.parse::<DenyEntry>().is_err(), "{spec}");
+        }
+    }
+
+    #[test]
+    fn refuses_nodes_on_any_band_or_their_ip() {
+        let node = NodeInfo::new("rpc-polkadot-01".to_string()).unwrap();
+        let denied = entries(&["41001=reverse proxy", ".10"]);
+        assert_eq!(
+            hits(&denied, &node).unwrap(),
+            [
+                Hit {
+                    value: "port 41001".into(),
+                    reason: "reverse proxy".into(),
+                },
+                Hit {
+                    value: "ip 192.168.111.10".into(),
+                    reason: String::new(),
+                },
+            ]
+        );
+        assert_eq!(
+            check(&denied, &node).unwrap_err().to_string(),
+            "rpc-polkadot-01: port 41001 is denylisted (reverse proxy), \
+             use --ignore-denylist to override"
+        );
+        assert!(check(&entries(&["31002-31009"]), &node).is_ok());
+    }
+}
@@ -0,0 +1,348 @@
+// A/PTR verification of published DNS records against generated addresses,
+// with a minimal UDP DNS client instead of a resolver crate
+use crate::{calculate_address, pool, PortgenError};
+use std::{
+    fmt, fs,
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const RCODE_NXDOMAIN: u8 = 3;
+const TIMEOUT: Duration = Duration::from_secs(2);
+const ATTEMPTS: usize = 3;
+
+// First `nameserver` of /etc/resolv.conf
+pub fn system_resolver() -> Result<SocketAddr, PortgenError> {
+    let conf = fs::read_to_string("/etc/resolv.conf").map_err(|_| "failed to read resolv.conf")?;
+    conf.lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .find_map(|ip| ip.trim().parse::<std::net::IpAddr>().ok())
+        .map(|ip| SocketAddr::new(ip, 53))
+        .ok_or("no nameserver in resolv.conf".into())
+}
+
+fn encode_query(id: u16, name: &str, qtype: u16) -> Result<Vec<u8>, PortgenError> {
+    let mut packet = id.to_be_bytes().to_vec();
+    // Recursion desired, one question, no other records
+    packet.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err("invalid DNS name".into());
+        }
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    packet.extend_from_slice(&qtype.to_be_bytes());
+    packet.extend_from_slice(&1u16.to_be_bytes()); // IN
+    Ok(packet)
+}
+
+// Decodes a possibly compressed name at `pos`, returns it and the offset after it
+fn decode_name(packet: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    // Bounds pointer loops in malformed answers
+    for _ in 0..128 {
+        let len = *packet.get(pos)? as usize;
+        match len {
+            0 => {
+                let name = labels.join(".");
+                return Some((name, end.unwrap_or(pos + 1)));
+            }
+            l if l & 0xC0 == 0xC0 => {
+                let target = ((l & 0x3F) << 8) | *packet.get(pos + 1)? as usize;
+                end.get_or_insert(pos + 2);
+                pos = target;
+            }
+            l => {
+                let label = packet.get(pos + 1..pos + 1 + l)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                pos += 1 + l;
+            }
+        }
+    }
+    None
+}
+
+fn read_u16(packet: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([
+        *packet.get(pos)?,
+        *packet.get(pos + 1)?,
+    ]))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Answer {
+    Records(Vec<String>),
+    NxDomain,
+}
+
+fn parse_response(packet: &[u8], id: u16, qtype: u16) -> Option<Answer> {
+    if read_u16(packet, 0)? != id {
+        return None;
+    }
+    let rcode = packet.get(3)? & 0x0F;
+    if rcode == RCODE_NXDOMAIN {
+        return Some(Answer::NxDomain);
+    }
+    if rcode != 0 {
+        return None;
+    }
+    let questions = read_u16(packet, 4)?;
+    let answers = read_u16(packet, 6)?;
+
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = decode_name(packet, pos)?.1 + 4;
+    }
+    let mut records = Vec::new();
+    for _ in 0..answers {
+        pos = decode_name(packet, pos)?.1;
+        let rtype = read_u16(packet, pos)?;
+        let rdlength = read_u16(packet, pos + 8)? as usize;
+        let rdata = pos + 10;
+        packet.get(rdata..rdata + rdlength)?;
+        // CNAMEs on the way are skipped, only the final records count
+        match rtype {
+            TYPE_A if qtype == TYPE_A && rdlength == 4 => {
+                let octets: [u8; 4] = packet[rdata..rdata + 4].try_into().ok()?;
+                records.push(Ipv4Addr::from(octets).to_string());
+            }
+            TYPE_PTR if qtype == TYPE_PTR => records.push(decode_name(packet, rdata)?.0),
+            _ => {}
+        }
+        pos = rdata + rdlength;
+    }
+    Some(Answer::Records(records))
+}
+
+pub fn query(resolver: SocketAddr, name: &str, qtype: u16) -> Result<Answer, PortgenError> {
+    let bind: SocketAddr = match resolver {
+        SocketAddr::V4(_) => "0.0.0.0:0".parse().map_err(|_| "invalid bind address")?,
+        SocketAddr::V6(_) => "[::]:0".parse().map_err(|_| "invalid bind address")?,
+    };
+    let socket = UdpSocket::bind(bind).map_err(|_| "failed to open UDP socket")?;
+
+    let id = (std::process::id() as u16) ^ (name.len() as u16).rotate_left(8);
+    let request = encode_query(id, name, qtype)?;
+    let mut buf = [0u8; 1500];
+    for _ in 0..ATTEMPTS {
+        socket
+            .send_to(&request, resolver)
+            .map_err(|_| "failed to send DNS query")?;
+        // Replies carrying another id, late ones to an earlier query of this
+        // socket's port, are skipped until the attempt times out
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                break;
+            }
+            socket
+                .set_read_timeout(Some(left))
+                .map_err(|_| "failed to set DNS timeout")?;
+            let Ok((len, _)) = socket.recv_from(&mut buf) else {
+                break;
+            };
+            if read_u16(&buf[..len], 0) == Some(id) {
+                return parse_response(&buf[..len], id, qtype)
+                    .ok_or("malformed DNS response".into());
+            }
+        }
+    }
+    Err("DNS query timed out".into())
+}
+
+pub fn resolve_a(resolver: SocketAddr, name: &str) -> Result<Answer, PortgenError> {
+    query(resolver, name, TYPE_A)
+}
+
+pub fn resolve_ptr(resolver: SocketAddr, ip: Ipv4Addr) -> Result<Answer, PortgenError> {
+    let [a, b, c, d] = ip.octets();
+    query(resolver, &format!("{d}.{c}.{b}.{a}.in-addr.arpa"), TYPE_PTR)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordStatus {
+    Ok,
+    Mismatch(Vec<String>),
+    Missing,
+    Error(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct DnsReport {
+    pub fqdn: String,
+    pub ip: Ipv4Addr,
+    pub a: RecordStatus,
+    // Only checked with `--ptr`
+    pub ptr: Option<RecordStatus>,
+}
+
+impl DnsReport {
+    pub fn is_ok(&self) -> bool {
+        self.a == RecordStatus::Ok && self.ptr.as_ref().is_none_or(|ptr| *ptr == RecordStatus::Ok)
+    }
+}
+
+fn write_status(f: &mut fmt::Formatter<'_>, record: &str, status: &RecordStatus) -> fmt::Result {
+    match status {
+        RecordStatus::Ok => write!(f, " {record} ok"),
+        RecordStatus::Mismatch(found) => write!(f, " {record} mismatch ({})", found.join(", ")),
+        RecordStatus::Missing => write!(f, " {record} missing"),
+        RecordStatus::Error(e) => write!(f, " {record} error ({e})"),
+    }
+}
+
+impl fmt::Display for DnsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.fqdn, self.ip)?;
+        write_status(f, "A", &self.a)?;
+        if let Some(ptr) = &self.ptr {
+            write_status(f, "PTR", ptr)?;
+        }
+        Ok(())
+    }
+}
+
+fn compare(answer: Result<Answer, PortgenError>, expected: &str) -> RecordStatus {
+    let normalize = |s: &str| s.trim_end_matches('.').to_ascii_lowercase();
+    match answer {
+        Err(e) => RecordStatus::Error(e.to_string()),
+        Ok(Answer::NxDomain) => RecordStatus::Missing,
+        Ok(Answer::Records(records)) if records.is_empty() => RecordStatus::Missing,
+        Ok(Answer::Records(records)) => {
+            if records.iter().any(|r| normalize(r) == normalize(expected)) {
+                RecordStatus::Ok
+            } else {
+                RecordStatus::Mismatch(records)
+            }
+        }
+    }
+}
+
+pub fn check_node(
+    name: &str,
+    domain: &str,
+    resolver: SocketAddr,
+    ptr: bool,
+) -> Result<DnsReport, PortgenError> {
+    let ip = calculate_address(name)?.ip;
+    let fqdn = format!(
+        "{}.{}",
+        name.trim_end_matches(".yaml"),
+        domain.trim_end_matches('.')
+    );
+    let a = compare(resolve_a(resolver, &fqdn), &ip.to_string());
+    let ptr = ptr.then(|| compare(resolve_ptr(resolver, ip), &fqdn));
+    Ok(DnsReport { fqdn, ip, a, ptr })
+}
+
+// Checks every node on at most `jobs` threads, reports come back in input order
+pub fn check_all(
+    names: &[String],
+    domain: &str,
+    resolver: SocketAddr,
+    ptr: bool,
+    jobs: usize,
+) -> Result<Vec<DnsReport>, PortgenError> {
+    pool::map_ordered(names, jobs, |name| check_node(name, domain, resolver, ptr))
+        .ok_or("DNS worker panicked")?
+        .into_iter()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    // A response to `name`: the query turned around, with `rcode` and the
+    // answers appended, each `rdata` as given
+    fn response(id: u16, name: &str, qtype: u16, rcode: u8, answers: &[(u16, &[u8])]) -> Vec<u8> {
+        let mut packet = encode_query(id, name, qtype).unwrap();
+        packet[2] = 0x81;
+        packet[3] = 0x80 | rcode;
+        packet[7] = answers.len() as u8;
+        for (rtype, rdata) in answers {
+            // Owner name as a pointer to the question's name at offset 12
+            packet.extend_from_slice(&[0xC0, 12]);
+            packet.extend_from_slice(&rtype.to_be_bytes());
+            packet.extend_from_slice(&[0, 1, 0, 0, 0x0E, 0x10]);
+            packet.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+            packet.extend_from_slice(rdata);
+        }
+        packet
+    }
+
+    #[test]
+    fn parses_canned_responses() {
+        let a = response(
+            7,
+            "rpc-polkadot-01.example",
+            TYPE_A,
+            0,
+            &[(TYPE_A, &[192, 168, 111, 10])],
+        );
+        assert_eq!(
+            parse_response(&a, 7, TYPE_A),
+            Some(Answer::Records(vec!["192.168.111.10".to_string()]))
+        );
+        // Truncated anywhere in the answer
+        assert_eq!(parse_response(&a[..a.len() - 1], 7, TYPE_A), None);
+        assert_eq!(parse_response(&a[..a.len() - 12], 7, TYPE_A), None);
+
+        let ptr = response(
+            7,
+            "10.111.168.192.in-addr.arpa",
+            TYPE_PTR,
+            0,
+            &[(TYPE_PTR, b"\x0frpc-polkadot-01\x07example\x00")],
+        );
+        assert_eq!(
+            parse_response(&ptr, 7, TYPE_PTR),
+            Some(Answer::Records(vec!["rpc-polkadot-01.example".to_string()]))
+        );
+
+        // A label, then a pointer to `example` of the question at offset 12
+        let mut compressed = a.clone();
+        let pos = compressed.len();
+        compressed.extend_from_slice(b"\x03rpc\xC0\x1C");
+        assert_eq!(
+            decode_name(&compressed, pos),
+            Some(("rpc.example".to_string(), pos + 6))
+        );
+
+        let nx = response(7, "nowhere.example", TYPE_A, RCODE_NXDOMAIN, &[]);
+        assert_eq!(parse_response(&nx, 7, TYPE_A), Some(Answer::NxDomain));
+
+        // A pointer to itself is given up on, not followed forever
+        let mut looped = a[..12].to_vec();
+        looped.extend_from_slice(&[0xC0, 12]);
+        assert_eq!(decode_name(&looped, 12), None);
+    }
+
+    #[test]
+    fn late_replies_to_other_queries_are_skipped() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let resolver = server.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            let (len, client) = server.recv_from(&mut buf).unwrap();
+            let id = read_u16(&buf, 0).unwrap();
+            let name = decode_name(&buf[..len], 12).unwrap().0;
+            let stale = response(id ^ 1, &name, TYPE_A, 0, &[(TYPE_A, &[10, 0, 0, 1])]);
+            let fresh = response(id, &name, TYPE_A, 0, &[(TYPE_A, &[192, 168, 111, 10])]);
+            server.send_to(&stale, client).unwrap();
+            server.send_to(&fresh, client).unwrap();
+        });
+        assert_eq!(
+            resolve_a(resolver, "rpc-polkadot-01.example").unwrap(),
+            Answer::Records(vec!["192.168.111.10".to_string()])
+        );
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,94 @@
+// `docker run` commands and a Compose file for nodes, optionally pinned to
+// their generated IP on an existing bridge network
+use crate::{
+    ranges::Cidr, script_args::container_args, ssh::shell_quote, NodeInfo, PortKind, PortgenError,
+};
+use serde_json::{json, Map, Value};
+
+#[derive(Debug, Clone)]
+pub struct DockerNetwork {
+    pub name: String,
+    // Subnet the bridge was created with, a /16 unless told otherwise
+    pub subnet: Cidr,
+}
+
+// Nodes whose generated IP Docker would refuse on `network`
+pub fn outside_subnet<'a>(nodes: &'a [NodeInfo], network: &DockerNetwork) -> Vec<&'a NodeInfo> {
+    nodes
+        .iter()
+        .filter(|node| !network.subnet.contains(node.address.ip))
+        .collect()
+}
+
+fn published_ports(node: &NodeInfo) -> Result<Vec<String>, PortgenError> {
+    let p2p = node.address.port;
+    let rpc = node.address.port_of(PortKind::Rpc)?;
+    Ok(vec![format!("{p2p}:{p2p}"), format!("{rpc}:{rpc}")])
+}
+
+pub fn run_command(
+    node: &NodeInfo,
+    image: &str,
+    network: Option<&DockerNetwork>,
+) -> Result<String, PortgenError> {
+    let name = node.name.trim_end_matches(".yaml");
+    let mut words = vec![
+        "docker".to_string(),
+        "run".to_string(),
+        "-d".to_string(),
+        "--name".to_string(),
+        name.to_string(),
+        "--restart".to_string(),
+        "unless-stopped".to_string(),
+    ];
+    for port in published_ports(node)? {
+        words.extend(["-p".to_string(), port]);
+    }
+    if let Some(network) = network {
+        words.extend(["--network".to_string(), network.name.clone()]);
+        words.extend(["--ip".to_string(), node.address.ip.to_string()]);
+    }
+    words.extend(["-v".to_string(), format!("{name}-data:/data")]);
+    words.push(image.to_string());
+    words.extend(container_args(node)?);
+    let quoted: Vec<String> = words.iter().map(|word| shell_quote(word)).collect();
+    Ok(quoted.join(" "))
+}
+
+pub fn compose(
+    nodes: &[NodeInfo],
+    image: &str,
+    network: Option<&DockerNetwork>,
+) -> Result<String, PortgenError> {
+    let mut services = Map::new();
+    let mut volumes = Map::new();
+    for node in nodes {
+        let name = node.name.trim_end_matches(".yaml");
+        let mut service = json!({
+            "image": image,
+            "container_name": name,
+            "restart": "unless-stopped",
+            "command": container_args(node)?,
+            "ports": published_ports(node)?,
+            "volumes": [format!("{name}-data:/data")],
+        });
+        if let (Some(network), Value::Object(service)) = (network, &mut service) {
+            service.insert(
+                "networks".into(),
+                json!({ network.name.as_str(): { "ipv4_address": node.address.ip.to_string() } }),
+            );
+        }
+        services.insert(name.to_string(), service);
+        volumes.insert(format!("{name}-data"), json!({}));
+    }
+
+    let mut document = json!({ "services": services, "volumes": volumes });
+    if let (Some(network), Value::Object(document)) = (network, &mut document) {
+        // The bridge is managed outside the Compose project
+        document.insert(
+            "networks".into(),
+            json!({ network.name.as_str(): { "external": true } }),
+        );
+    }
+    serde_yaml::to_string(&document).map_err(|_| "failed to serialize compose file".into())
+}
@@ -0,0 +1,32 @@
+// RPC endpoint lists for light clients and tooling that talk to the relay chain
+use crate::{compose_name, Network, NodeInfo, PortKind, PortgenError, Role};
+use serde_json::json;
+
+// Every RPC node of a chain in instance order. Anything describing an RPC
+// pool (endpoint lists, load balancers) enumerates it here so they agree.
+pub fn rpc_pool(network: Network, chain: Option<&str>) -> Result<Vec<NodeInfo>, PortgenError> {
+    Role::instance_range("rpc")
+        .ok_or("invalid role")?
+        .map(|instance| NodeInfo::new(compose_name("rpc", chain, network, instance)))
+        .collect()
+}
+
+// `ws://` URL of every relay chain RPC node of the network, in instance order
+pub fn relay_endpoints(network: Network) -> Result<Vec<String>, PortgenError> {
+    rpc_pool(network, None)?
+        .iter()
+        .map(|node| {
+            let port = node.address.port_of(PortKind::Rpc)?;
+            Ok(format!("ws://{}:{port}", node.address.ip))
+        })
+        .collect()
+}
+
+// Fragment for a smoldot-based client config, keyed like its relay chain options
+pub fn smoldot_config(network: Network, endpoints: &[String]) -> Result<String, PortgenError> {
+    let document = json!({
+        "relayChain": network.to_string(),
+        "endpoints": endpoints,
+    });
+    serde_json::to_string_pretty(&document).map_err(|_| "failed to serialize".into())
+}
@@ -0,0 +1,178 @@
+// Per-node dotenv files kept in sync under `<out>/<network>/<name>.env`
+use crate::{
+    atomic::write_output, calculate_address, ChainId, Network, NodeName, PortKind, PortgenError,
+};
+use std::{
+    collections::HashSet,
+    fmt::{self, Write},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+// First line of every file portgen writes, the mark of the ones it may prune
+const HEADER: &str = "# generated by portgen";
+
+pub fn env_file(node_str: &str) -> Result<String, PortgenError> {
+    let name = node_str.trim_end_matches(".yaml");
+    let node = NodeName::parse(name)?;
+    let chain = ChainId::from_str(node.chain.as_deref())?.name()?;
+    let addr = calculate_address(name)?;
+
+    let mut out = String::new();
+    let _ = writeln!(out, "{HEADER}, do not edit");
+    let _ = writeln!(out, "NODE_NAME={name}");
+    let _ = writeln!(out, "NODE_ROLE={}", node.role);
+    let _ = writeln!(out, "NODE_CHAIN={}", chain.unwrap_or("relay"));
+    let _ = writeln!(out, "NODE_NETWORK={}", node.network.parse::<Network>()?);
+    let _ = writeln!(out, "NODE_INSTANCE={}", node.instance);
+    let _ = writeln!(out, "NODE_IP={}", addr.ip);
+    let _ = writeln!(out, "NODE_P2P_PORT={}", addr.port);
+    let _ = writeln!(out, "NODE_RPC_PORT={}", addr.port_of(PortKind::Rpc)?);
+    let _ = writeln!(
+        out,
+        "NODE_PROMETHEUS_PORT={}",
+        addr.port_of(PortKind::Prometheus)?
+    );
+    let _ = writeln!(out, "NODE_LISTEN_ADDR={}", addr.multiaddr());
+    Ok(out)
+}
+
+fn env_path(out_dir: &Path, node_str: &str) -> Result<PathBuf, PortgenError> {
+    let name = node_str.trim_end_matches(".yaml");
+    let network = NodeName::parse(name)?.network.parse::<Network>()?;
+    Ok(out_dir
+        .join(network.to_string())
+        .join(format!("{name}.env")))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Created,
+    Updated,
+    Unchanged,
+    Pruned,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Created => "created",
+            Self::Updated => "updated",
+            Self::Unchanged => "unchanged",
+            Self::Pruned => "pruned",
+        };
+        f.write_str(name)
+    }
+}
+
+// A file a previous run wrote: named for a node and starting with the header.
+// Anything else in the tree, like a hand-written secrets.env, is left alone.
+fn is_generated(path: &Path) -> bool {
+    let named_for_node = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| NodeName::parse(stem).is_ok());
+    named_for_node
+        && fs::read_to_string(path).is_ok_and(|text| {
+            text.lines()
+                .next()
+                .is_some_and(|line| line.starts_with(HEADER))
+        })
+}
+
+// Generated `.env` files one level below `out_dir`, the ones a previous run wrote
+fn existing_env_files(out_dir: &Path) -> Result<Vec<PathBuf>, PortgenError> {
+    let entries = match fs::read_dir(out_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(_) => return Err("failed to read env directory".into()),
+    };
+    let mut files = Vec::new();
+    for dir in entries.filter_map(|e| e.ok().map(|e| e.path())) {
+        if !dir.is_dir() {
+            continue;
+        }
+        let inner = fs::read_dir(&dir).map_err(|_| "failed to read env directory")?;
+        files.extend(
+            inner
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "env"))
+                .filter(|path| is_generated(path)),
+        );
+    }
+    files.sort();
+    Ok(files)
+}
+
+// Brings the tree in line with `nodes`; with `dry_run` only reports what would change
+pub fn sync(
+    out_dir: &Path,
+    nodes: &[String],
+    prune: bool,
+    dry_run: bool,
+    backup: bool,
+) -> Result<Vec<(PathBuf, Change)>, PortgenError> {
+    let mut changes = Vec::new();
+    let mut wanted = HashSet::new();
+    for name in nodes {
+        let path = env_path(out_dir, name)?;
+        let contents = env_file(name)?;
+        let change = match fs::read_to_string(&path) {
+            Ok(current) if current == contents => Change::Unchanged,
+            Ok(_) => Change::Updated,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Change::Created,
+            Err(_) => return Err("failed to read env file".into()),
+        };
+        if change != Change::Unchanged && !dry_run {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|_| "failed to create env directory")?;
+            }
+            write_output(&path, contents.as_bytes(), backup)
+                .map_err(|_| "failed to write env file")?;
+        }
+        wanted.insert(path.clone());
+        changes.push((path, change));
+    }
+
+    if prune {
+        for path in existing_env_files(out_dir)? {
+            if wanted.contains(&path) {
+                continue;
+            }
+            if !dry_run {
+                fs::remove_file(&path).map_err(|_| "failed to remove env file")?;
+            }
+            changes.push((path, Change::Pruned));
+        }
+    }
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prune_leaves_foreign_env_files() {
+        let dir = std::env::temp_dir().join(format!("portgen-env-tree-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let nodes = ["rpc-polkadot-01".to_string(), "rpc-polkadot-02".to_string()];
+        sync(&dir, &nodes, false, false, false).unwrap();
+        let secrets = dir.join("polkadot").join("secrets.env");
+        fs::write(&secrets, "API_KEY=1\n").unwrap();
+        // Named for a node, but not written by portgen
+        let own = dir.join("polkadot").join("rpc-polkadot-03.env");
+        fs::write(&own, "NODE_IP=10.0.0.1\n").unwrap();
+
+        let changes = sync(&dir, &nodes[..1], true, false, false).unwrap();
+        let pruned: Vec<&PathBuf> = changes
+            .iter()
+            .filter(|(_, change)| *change == Change::Pruned)
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(pruned, [&dir.join("polkadot").join("rpc-polkadot-02.env")]);
+        assert!(secrets.exists());
+        assert!(own.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
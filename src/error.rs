@@ -0,0 +1,148 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PortgenError {
+    // Malformed node names, out-of-scheme values and failed I/O, as a message
+    Invalid(&'static str),
+    InvalidChainName {
+        name: String,
+        reason: &'static str,
+    },
+    // Firewall policy that does not fit the scheme, names what is wrong with it
+    InvalidPolicy(String),
+    // Registry dump that does not parse or breaks the registry's rules
+    InvalidRegistry(String),
+    // Node whose ip or one of its ports is on the denylist
+    Denylisted {
+        name: String,
+        value: String,
+        reason: String,
+    },
+    // Node the formula computed differently twice or that does not decode
+    // back into its own name
+    ConsistencyViolation {
+        name: String,
+        message: String,
+    },
+    // Nodes whose /etc/services names come out the same once shortened
+    ServiceNameCollision {
+        service: String,
+        nodes: Vec<String>,
+    },
+    // ping ran but could not send an echo request, with its stderr
+    PingFailed(String),
+    // Three-part name whose network slot holds a chain name
+    AmbiguousChain {
+        chain: String,
+        role: String,
+        instance: String,
+    },
+    // Name whose tokens do not make exactly one node name in any order
+    Normalize {
+        name: String,
+        reason: String,
+    },
+    // Chain without an id whose name-derived id another chain already has
+    ChainIdTaken {
+        name: String,
+        id: u16,
+        other: String,
+    },
+    // Config file or variable holding a bad value, located as `file:line` or
+    // the variable name
+    Config {
+        location: String,
+        key: Option<String>,
+        message: String,
+    },
+    // Template that failed to parse or render, located as `file:line:column`
+    Template {
+        location: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for PortgenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid(message) => f.write_str(message),
+            Self::InvalidChainName { name, reason } => {
+                write!(f, "invalid chain name '{name}': {reason}")
+            }
+            Self::InvalidPolicy(message) => write!(f, "invalid firewall policy: {message}"),
+            Self::InvalidRegistry(message) => write!(f, "invalid registry: {message}"),
+            Self::Denylisted {
+                name,
+                value,
+                reason,
+            } => {
+                write!(f, "{name}: {value} is denylisted")?;
+                if !reason.is_empty() {
+                    write!(f, " ({reason})")?;
+                }
+                f.write_str(", use --ignore-denylist to override")
+            }
+            Self::ConsistencyViolation { name, message } => {
+                write!(f, "{name}: consistency violation: {message}")
+            }
+            Self::ServiceNameCollision { service, nodes } => write!(
+                f,
+                "service name '{service}' would be shared by {}",
+                nodes.join(", ")
+            ),
+            Self::PingFailed(message) => {
+                write!(f, "ping failed: {message}, use --tcp-only")
+            }
+            Self::AmbiguousChain {
+                chain,
+                role,
+                instance,
+            } => {
+                let title: Vec<String> = chain
+                    .split('-')
+                    .map(|word| {
+                        let mut chars = word.chars();
+                        chars
+                            .next()
+                            .map(|first| first.to_uppercase().chain(chars).collect())
+                            .unwrap_or_default()
+                    })
+                    .collect();
+                write!(
+                    f,
+                    "'{chain}' could be a chain name; use '{role}-{chain}-<network>-{instance}' \
+                     if you mean the {} chain",
+                    title.join(" ")
+                )
+            }
+            Self::Normalize { name, reason } => {
+                write!(f, "cannot normalize '{name}': {reason}")
+            }
+            Self::ChainIdTaken { name, id, other } => write!(
+                f,
+                "chain '{name}' would get id {id}, already used by chain '{other}'; give it an id"
+            ),
+            Self::Config {
+                location,
+                key: Some(key),
+                message,
+            } => write!(f, "config error at {location}: {key}: {message}"),
+            Self::Config {
+                location,
+                key: None,
+                message,
+            } => write!(f, "config error at {location}: {message}"),
+            Self::Template { location, message } => {
+                write!(f, "template error at {location}: {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PortgenError {}
+
+impl From<&'static str> for PortgenError {
+    fn from(message: &'static str) -> Self {
+        Self::Invalid(message)
+    }
+}
@@ -0,0 +1,257 @@
+// Pairwise allow rules expanded from a role-level policy against concrete nodes
+use crate::{
+    calculate_address, ChainId, Network, NodeAddress, NodeName, PortKind, PortgenError, Role,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    net::Ipv4Addr,
+};
+
+// Which source nodes of a role a destination accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    // Same chain on the same network
+    Chain,
+    // Any chain of the same network
+    Network,
+    All,
+}
+
+#[derive(Debug, Clone)]
+pub enum Source {
+    Role(String),
+    // Name of a `[cidrs]` entry
+    Cidrs(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct AllowRule {
+    pub from: Source,
+    pub to: String,
+    pub kind: PortKind,
+    pub scope: Scope,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    pub cidrs: BTreeMap<String, Vec<String>>,
+    pub rules: Vec<AllowRule>,
+}
+
+fn invalid(message: String) -> PortgenError {
+    PortgenError::InvalidPolicy(message)
+}
+
+fn check_cidr(cidr: &str) -> Result<(), PortgenError> {
+    let valid = match cidr.split_once('/') {
+        Some((ip, prefix)) => {
+            ip.parse::<Ipv4Addr>().is_ok() && prefix.parse::<u8>().is_ok_and(|p| p <= 32)
+        }
+        None => cidr.parse::<Ipv4Addr>().is_ok(),
+    };
+    if !valid {
+        return Err(invalid(format!("'{cidr}' is not an IPv4 address or CIDR")));
+    }
+    Ok(())
+}
+
+fn check_role(role: &str, rule: usize) -> Result<(), PortgenError> {
+    if Role::instance_range(role).is_none() {
+        return Err(invalid(format!("rule {rule}: unknown role '{role}'")));
+    }
+    Ok(())
+}
+
+fn field<'a>(table: &'a toml::Table, key: &str, rule: usize) -> Result<&'a str, PortgenError> {
+    table
+        .get(key)
+        .and_then(toml::Value::as_str)
+        .ok_or_else(|| invalid(format!("rule {rule}: missing string field '{key}'")))
+}
+
+// ```toml
+// [cidrs]
+// lb = ["10.0.0.0/24"]
+//
+// [[allow]]
+// from = "rpc"      # role or [cidrs] name
+// to = "val"        # role
+// kind = "p2p"      # p2p, rpc, ws or prometheus
+// scope = "chain"   # chain (default), network or all
+// ```
+pub fn parse_policy(text: &str) -> Result<Policy, PortgenError> {
+    let doc: toml::Table = text
+        .parse()
+        .map_err(|e: toml::de::Error| invalid(e.message().to_string()))?;
+
+    let mut policy = Policy::default();
+    if let Some(cidrs) = doc.get("cidrs") {
+        let cidrs = cidrs
+            .as_table()
+            .ok_or_else(|| invalid("[cidrs] must be a table".to_string()))?;
+        for (name, value) in cidrs {
+            if Role::instance_range(name).is_some() {
+                return Err(invalid(format!("cidr set '{name}' shadows a role")));
+            }
+            let list: Vec<String> = match value {
+                toml::Value::String(cidr) => vec![cidr.clone()],
+                toml::Value::Array(items) => items
+                    .iter()
+                    .map(|item| item.as_str().map(str::to_string))
+                    .collect::<Option<_>>()
+                    .ok_or_else(|| invalid(format!("cidr set '{name}' must hold strings")))?,
+                _ => return Err(invalid(format!("cidr set '{name}' must hold strings"))),
+            };
+            for cidr in &list {
+                check_cidr(cidr)?;
+            }
+            policy.cidrs.insert(name.clone(), list);
+        }
+    }
+
+    let rules = match doc.get("allow") {
+        Some(toml::Value::Array(rules)) => rules.as_slice(),
+        Some(_) => return Err(invalid("[[allow]] must be an array of tables".to_string())),
+        None => &[],
+    };
+    for (i, rule) in rules.iter().enumerate() {
+        let n = i + 1;
+        let rule = rule
+            .as_table()
+            .ok_or_else(|| invalid(format!("rule {n} must be a table")))?;
+
+        let from = field(rule, "from", n)?;
+        let from = if policy.cidrs.contains_key(from) {
+            Source::Cidrs(from.to_string())
+        } else {
+            check_role(from, n)?;
+            Source::Role(from.to_string())
+        };
+        let to = field(rule, "to", n)?;
+        check_role(to, n)?;
+        let kind = field(rule, "kind", n)?;
+        let kind = kind
+            .parse()
+            .map_err(|_| invalid(format!("rule {n}: unknown port kind '{kind}'")))?;
+        let scope = match rule.get("scope").map(|s| s.as_str()) {
+            None | Some(Some("chain")) => Scope::Chain,
+            Some(Some("network")) => Scope::Network,
+            Some(Some("all")) => Scope::All,
+            _ => {
+                return Err(invalid(format!(
+                    "rule {n}: scope must be chain, network or all"
+                )))
+            }
+        };
+
+        policy.rules.push(AllowRule {
+            from,
+            to: to.to_string(),
+            kind,
+            scope,
+        });
+    }
+    Ok(policy)
+}
+
+// One accept rule: every source may reach the destination port
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FirewallRule {
+    pub sources: Vec<String>,
+    pub dest: Ipv4Addr,
+    pub port: u16,
+}
+
+struct Placed {
+    role: String,
+    network: Network,
+    chain: ChainId,
+    address: NodeAddress,
+}
+
+fn in_scope(scope: Scope, a: &Placed, b: &Placed) -> bool {
+    match scope {
+        Scope::Chain => a.network == b.network && a.chain == b.chain,
+        Scope::Network => a.network == b.network,
+        Scope::All => true,
+    }
+}
+
+// Sources are deduplicated and grouped per destination port
+pub fn expand(policy: &Policy, nodes: &[String]) -> Result<Vec<FirewallRule>, PortgenError> {
+    let placed = nodes
+        .iter()
+        .map(|name| {
+            let node = NodeName::parse(name)?;
+            Ok(Placed {
+                role: node.role.to_string(),
+                network: node.network.parse()?,
+                chain: ChainId::from_str(node.chain.as_deref())?,
+                address: calculate_address(name)?,
+            })
+        })
+        .collect::<Result<Vec<_>, PortgenError>>()?;
+
+    let mut grouped: BTreeMap<(Ipv4Addr, u16), BTreeSet<String>> = BTreeMap::new();
+    for rule in &policy.rules {
+        for dest in placed.iter().filter(|node| node.role == rule.to) {
+            let sources: Vec<String> = match &rule.from {
+                Source::Cidrs(name) => policy.cidrs[name].clone(),
+                Source::Role(role) => placed
+                    .iter()
+                    .filter(|src| &src.role == role && src.address.ip != dest.address.ip)
+                    .filter(|src| in_scope(rule.scope, dest, src))
+                    .map(|src| src.address.ip.to_string())
+                    .collect(),
+            };
+            if sources.is_empty() {
+                continue;
+            }
+            let port = dest.address.port_of(rule.kind)?.0;
+            grouped
+                .entry((dest.address.ip, port))
+                .or_default()
+                .extend(sources);
+        }
+    }
+
+    Ok(grouped
+        .into_iter()
+        .map(|((dest, port), sources)| FirewallRule {
+            sources: sources.into_iter().collect(),
+            dest,
+            port,
+        })
+        .collect())
+}
+
+pub fn to_nftables(rules: &[FirewallRule]) -> String {
+    let mut out = String::from("# generated by portgen firewall-matrix\n");
+    for rule in rules {
+        let saddr = match rule.sources.as_slice() {
+            [single] => single.clone(),
+            many => format!("{{ {} }}", many.join(", ")),
+        };
+        let _ = writeln!(
+            out,
+            "add rule inet filter input ip saddr {saddr} ip daddr {} tcp dport {} accept",
+            rule.dest, rule.port
+        );
+    }
+    out
+}
+
+pub fn to_iptables(rules: &[FirewallRule]) -> String {
+    let mut out = String::from("#!/bin/sh\n# generated by portgen firewall-matrix\nset -e\n");
+    for rule in rules {
+        let _ = writeln!(
+            out,
+            "iptables -A INPUT -p tcp -s {} -d {} --dport {} -j ACCEPT",
+            rule.sources.join(","),
+            rule.dest,
+            rule.port
+        );
+    }
+    out
+}
@@ -0,0 +1,13 @@
+// @generated by `portgen generate-phf > src/generated.rs`, do not edit by hand
+// Covers every node name from the documented examples (DOCUMENTED_EXAMPLES).
+pub static WELL_KNOWN_ADDRESSES: phf::Map<&'static str, (u16, [u8; 4])> = phf::phf_map! {
+    "boot-polkadot-00" => (31000, [192, 168, 10, 10]),
+    "rpc-kusama-01" => (32001, [192, 168, 121, 10]),
+    "val-westend-04" => (33007, [192, 168, 234, 10]),
+    "rpc-asset-hub-polkadot-01" => (31011, [192, 168, 111, 11]),
+    "boot-bridge-hub-kusama-00" => (32020, [192, 168, 20, 12]),
+    "val-people-westend-04" => (33047, [192, 168, 234, 14]),
+    "rpc-asset-hub-kusama-01" => (32011, [192, 168, 121, 11]),
+    "val-people-westend-01" => (33044, [192, 168, 231, 14]),
+    "boot-paseo-00" => (34000, [192, 168, 40, 10]),
+};
@@ -0,0 +1,99 @@
+// The pre-commit hook script: every node name mentioned in the repository's
+// `*.yaml` and `nodes.txt` files is validated, and with a node directory the
+// staged lint of `lint --install-hook` runs after that. `git-hook` prints it,
+// `lint --install-hook` installs it, so both recognise the hook as theirs.
+use crate::{roles, Network};
+
+pub const MARKER: &str = "# generated by portgen git-hook";
+
+// Marker of hooks `lint --install-hook` wrote before it shared this script
+const LEGACY_MARKER: &str = "# installed by portgen lint --install-hook";
+
+// Whether portgen wrote the hook, so installing over it loses nothing
+pub fn is_portgen_hook(script: &str) -> bool {
+    script.contains(MARKER) || script.contains(LEGACY_MARKER)
+}
+
+// ERE (grep -E) for anything shaped like a node name: a role, any chain
+// tokens, a network and digits. Deliberately loose, so unknown chains and
+// out-of-range instances reach `portgen check` and fail there.
+pub fn name_pattern() -> String {
+    let roles: Vec<&str> = roles::table().iter().map(|role| role.abbrev).collect();
+    let networks: Vec<String> = Network::ALL.iter().map(Network::to_string).collect();
+    format!(
+        "({})(-[a-z0-9]+)*-({})-[0-9]+",
+        roles.join("|"),
+        networks.join("|")
+    )
+}
+
+// Reads the staged content of each file, so the check sees what gets
+// committed; only reads, so running it again changes nothing. `lint_dir` is
+// relative to the repository root, where hooks run.
+pub fn script(lint_dir: Option<&str>) -> String {
+    let mut script = format!(
+        r#"#!/bin/sh
+{MARKER}
+# Install: portgen git-hook > .git/hooks/pre-commit && chmod +x .git/hooks/pre-commit
+set -u
+pattern='{pattern}'
+names=$(git ls-files -- '*.yaml' 'nodes.txt' '*/nodes.txt' |
+    while IFS= read -r path; do git show ":$path"; done |
+    grep -oE "$pattern" | sort -u)
+if [ -n "$names" ] && ! result=$(printf '%s\n' "$names" | portgen check -); then
+    printf '%s\n' "$result" | grep -v ': ok$' >&2
+    echo "pre-commit: invalid node names, commit blocked" >&2
+    exit 1
+fi
+"#,
+        pattern = name_pattern()
+    );
+    if let Some(dir) = lint_dir {
+        let dir = dir.replace('\'', r"'\''");
+        script.push_str(&format!("exec portgen lint --staged '{dir}'\n"));
+    }
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::Write,
+        process::{Command, Stdio},
+    };
+
+    #[test]
+    fn one_script_for_both_installers() {
+        let script = script(Some("infra/it's"));
+        assert!(is_portgen_hook(&script));
+        assert!(script.ends_with("exec portgen lint --staged 'infra/it'\\''s'\n"));
+        assert!(!super::script(None).contains("lint"));
+        assert!(is_portgen_hook(
+            "#!/bin/sh\n# installed by portgen lint --install-hook\n"
+        ));
+        assert!(!is_portgen_hook("#!/bin/sh\nnpm test\n"));
+    }
+
+    // Needs grep: cargo test -- --ignored
+    #[test]
+    #[ignore]
+    fn pattern_extracts_node_names_with_grep() {
+        let mut grep = Command::new("grep")
+            .args(["-oE", &name_pattern()])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        grep.stdin
+            .take()
+            .unwrap()
+            .write_all(b"name: rpc-asset-hub-polkadot-01\npeers: [val-kusama-04, boot-nowhere-paseo-100]\nimage: polkadot\n")
+            .unwrap();
+        let output = grep.wait_with_output().unwrap();
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "rpc-asset-hub-polkadot-01\nval-kusama-04\nboot-nowhere-paseo-100\n"
+        );
+    }
+}
@@ -0,0 +1,181 @@
+// Grafana dashboard for the selected nodes, built from the same node list and
+// labels as the `blackbox` scrape config: custom variables holding the label
+// values the allocation produces, and panels over `probe_success`
+use crate::{blackbox, NodeInfo, PortgenError};
+use serde_json::{json, Value};
+
+pub const DEFAULT_DATASOURCE: &str = "prometheus";
+pub const DEFAULT_TITLE: &str = "portgen nodes";
+// Dashboard JSON model of Grafana 10.4
+const SCHEMA_VERSION: u32 = 39;
+
+pub struct GrafanaOptions<'a> {
+    // UID of the Prometheus datasource
+    pub datasource: &'a str,
+    pub title: &'a str,
+    pub uid: &'a str,
+}
+
+// A multi-value custom variable defaulting to All, its values in order of
+// first appearance
+fn variable(name: &str, values: &[String]) -> Value {
+    let mut options = vec![json!({ "selected": true, "text": "All", "value": "$__all" })];
+    options.extend(
+        values
+            .iter()
+            .map(|value| json!({ "selected": false, "text": value, "value": value })),
+    );
+    json!({
+        "type": "custom",
+        "name": name,
+        "label": name,
+        "query": values.join(","),
+        "multi": true,
+        "includeAll": true,
+        "current": { "selected": true, "text": ["All"], "value": ["$__all"] },
+        "options": options,
+        "hide": 0,
+    })
+}
+
+fn push_unique(values: &mut Vec<String>, value: &str) {
+    if !values.iter().any(|known| known == value) {
+        values.push(value.to_string());
+    }
+}
+
+pub fn dashboard(nodes: &[NodeInfo], options: &GrafanaOptions) -> Result<Value, PortgenError> {
+    let datasource = json!({ "type": "prometheus", "uid": options.datasource });
+    let label_names = ["network", "chain", "role", "node", "instance"];
+    let mut values: Vec<Vec<String>> = vec![Vec::new(); label_names.len()];
+    let mut listing =
+        String::from("| node | role | chain | network | address |\n|---|---|---|---|---|\n");
+    for node in nodes {
+        let name = node.name.trim_end_matches(".yaml");
+        let labels = blackbox::labels(name)?;
+        // The instance label of the default p2p probe
+        let instance = node.address.to_string();
+        for (label, values) in label_names.iter().zip(&mut values) {
+            let value = match *label {
+                "instance" => instance.as_str(),
+                label => labels[label].as_str().unwrap_or(""),
+            };
+            push_unique(values, value);
+        }
+        listing.push_str(&format!(
+            "| {name} | {} | {} | {} | {instance} |\n",
+            labels["role"].as_str().unwrap_or(""),
+            labels["chain"].as_str().unwrap_or(""),
+            labels["network"].as_str().unwrap_or(""),
+        ));
+    }
+    let variables: Vec<Value> = label_names
+        .iter()
+        .zip(&values)
+        .map(|(name, values)| variable(name, values))
+        .collect();
+
+    let matchers: Vec<String> = label_names
+        .iter()
+        .map(|label| format!("{label}=~\"${label}\""))
+        .collect();
+    let selector = format!(
+        "probe_success{{job=~\"blackbox_.*\",{}}}",
+        matchers.join(",")
+    );
+    let panels = json!([
+        {
+            "id": 1,
+            "type": "stat",
+            "title": "nodes up",
+            "datasource": datasource,
+            "gridPos": { "h": 4, "w": 6, "x": 0, "y": 0 },
+            "targets": [{
+                "refId": "A",
+                "datasource": datasource,
+                "expr": format!("sum({selector})"),
+                "instant": true,
+            }],
+            "options": { "reduceOptions": { "calcs": ["lastNotNull"] } },
+        },
+        {
+            "id": 2,
+            "type": "table",
+            "title": "probes",
+            "datasource": datasource,
+            "gridPos": { "h": 12, "w": 24, "x": 0, "y": 4 },
+            "targets": [{
+                "refId": "A",
+                "datasource": datasource,
+                "expr": selector,
+                "format": "table",
+                "instant": true,
+            }],
+            "transformations": [{
+                "id": "organize",
+                "options": { "excludeByName": { "Time": true, "__name__": true, "job": true } },
+            }],
+        },
+        {
+            "id": 3,
+            "type": "text",
+            "title": "expected nodes",
+            "gridPos": { "h": 12, "w": 24, "x": 0, "y": 16 },
+            "options": { "mode": "markdown", "content": listing },
+        },
+    ]);
+
+    Ok(json!({
+        "uid": options.uid,
+        "title": options.title,
+        "tags": ["portgen"],
+        "editable": true,
+        "schemaVersion": SCHEMA_VERSION,
+        "version": 1,
+        "time": { "from": "now-6h", "to": "now" },
+        "templating": { "list": variables },
+        "panels": panels,
+    }))
+}
+
+pub fn to_json(dashboard: &Value) -> Result<String, PortgenError> {
+    serde_json::to_string_pretty(dashboard).map_err(|_| "failed to serialize dashboard".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variables_hold_the_blackbox_label_values() {
+        let nodes: Vec<NodeInfo> = [
+            "rpc-polkadot-01",
+            "rpc-asset-hub-polkadot-01",
+            "rpc-polkadot-02",
+        ]
+        .into_iter()
+        .map(|name| NodeInfo::new(name.to_string()).unwrap())
+        .collect();
+        let options = GrafanaOptions {
+            datasource: "prom-uid",
+            title: DEFAULT_TITLE,
+            uid: "portgen-nodes",
+        };
+        let dashboard = dashboard(&nodes, &options).unwrap();
+        let variables = &dashboard["templating"]["list"];
+        let query = |i: usize| variables[i]["query"].as_str().unwrap().to_string();
+        assert_eq!(
+            [query(0), query(1), query(2)],
+            ["polkadot", "relay,asset-hub", "rpc"]
+        );
+        assert_eq!(
+            query(4),
+            "192.168.111.10:31001,192.168.111.11:31011,192.168.112.10:31002"
+        );
+        assert_eq!(dashboard["panels"][1]["datasource"]["uid"], "prom-uid");
+        assert!(dashboard["panels"][1]["targets"][0]["expr"]
+            .as_str()
+            .unwrap()
+            .contains("instance=~\"$instance\""));
+    }
+}
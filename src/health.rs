@@ -0,0 +1,235 @@
+// JSON-RPC health checks against a node's RPC port, over a minimal HTTP/1.1
+// client so connection, HTTP and RPC failures stay distinguishable
+use crate::{calculate_address, pool, NodeName, PortKind, PortgenError};
+use serde_json::{json, Value};
+use std::{
+    fmt,
+    io::{Read, Write},
+    net::{SocketAddr, SocketAddrV4, TcpStream},
+    time::Duration,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HealthStatus {
+    Ok {
+        peers: u64,
+        is_syncing: bool,
+        best_block: u64,
+    },
+    // Nothing answered: refused, unreachable or timed out
+    Connection(String),
+    // Something answered, but not with a JSON-RPC response
+    Http(String),
+    // The node answered with a JSON-RPC error
+    Rpc {
+        code: i64,
+        message: String,
+    },
+}
+
+impl HealthStatus {
+    // Distinct exit codes, 1 and 2 stay reserved for errors and usage errors
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Ok {
+                is_syncing: false, ..
+            } => 0,
+            Self::Ok { .. } => 3,
+            Self::Connection(_) => 4,
+            Self::Http(_) => 5,
+            Self::Rpc { .. } => 6,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthReport {
+    pub name: String,
+    pub endpoint: SocketAddrV4,
+    pub status: HealthStatus,
+}
+
+impl fmt::Display for HealthReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} http://{} ", self.name, self.endpoint)?;
+        match &self.status {
+            HealthStatus::Ok {
+                peers,
+                is_syncing,
+                best_block,
+            } => {
+                let state = if *is_syncing { "syncing" } else { "ok" };
+                write!(f, "{state} peers={peers} best={best_block}")
+            }
+            HealthStatus::Connection(e) => write!(f, "connection failed ({e})"),
+            HealthStatus::Http(e) => write!(f, "http error ({e})"),
+            HealthStatus::Rpc { code, message } => write!(f, "rpc error {code} ({message})"),
+        }
+    }
+}
+
+impl HealthReport {
+    pub fn to_json(&self) -> Value {
+        let mut value = json!({
+            "name": self.name,
+            "endpoint": format!("http://{}", self.endpoint),
+            "exit_code": self.status.exit_code(),
+        });
+        let fields = match &self.status {
+            HealthStatus::Ok {
+                peers,
+                is_syncing,
+                best_block,
+            } => json!({
+                "status": "ok",
+                "peers": peers,
+                "isSyncing": is_syncing,
+                "bestBlock": best_block,
+            }),
+            HealthStatus::Connection(e) => json!({ "status": "connection", "error": e }),
+            HealthStatus::Http(e) => json!({ "status": "http", "error": e }),
+            HealthStatus::Rpc { code, message } => {
+                json!({ "status": "rpc", "code": code, "error": message })
+            }
+        };
+        if let (Value::Object(value), Value::Object(fields)) = (&mut value, fields) {
+            value.extend(fields);
+        }
+        value
+    }
+}
+
+fn io_failure(e: std::io::Error) -> HealthStatus {
+    HealthStatus::Connection(e.kind().to_string())
+}
+
+fn decode_chunked(mut body: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::new();
+    loop {
+        let line_end = body.windows(2).position(|w| w == b"\r\n")?;
+        let size = std::str::from_utf8(&body[..line_end]).ok()?;
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Some(decoded);
+        }
+        decoded.extend_from_slice(body.get(..size)?);
+        body = body.get(size + 2..)?;
+    }
+}
+
+// Body of a 200 response, anything else is an HTTP failure
+fn parse_http(response: &[u8]) -> Result<Vec<u8>, HealthStatus> {
+    let malformed = || HealthStatus::Http("malformed HTTP response".to_string());
+    let head_end = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(malformed)?;
+    let head = String::from_utf8_lossy(&response[..head_end]);
+    let body = &response[head_end + 4..];
+    let mut lines = head.lines();
+    let status = lines
+        .next()
+        .and_then(|line| line.split_once(' '))
+        .map(|(_, status)| status.trim())
+        .ok_or_else(malformed)?;
+    if !status.starts_with("200") {
+        return Err(HealthStatus::Http(status.to_string()));
+    }
+
+    let header = |name: &str| {
+        lines.clone().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim()
+                .eq_ignore_ascii_case(name)
+                .then(|| value.trim().to_ascii_lowercase())
+        })
+    };
+    if header("transfer-encoding").is_some_and(|te| te.contains("chunked")) {
+        return decode_chunked(body).ok_or_else(malformed);
+    }
+    match header("content-length").and_then(|len| len.parse::<usize>().ok()) {
+        Some(len) => body.get(..len).map(<[u8]>::to_vec).ok_or_else(malformed),
+        None => Ok(body.to_vec()),
+    }
+}
+
+pub fn call(
+    endpoint: SocketAddrV4,
+    method: &str,
+    timeout: Duration,
+) -> Result<Value, HealthStatus> {
+    let mut stream =
+        TcpStream::connect_timeout(&SocketAddr::V4(endpoint), timeout).map_err(io_failure)?;
+    stream.set_read_timeout(Some(timeout)).map_err(io_failure)?;
+    stream
+        .set_write_timeout(Some(timeout))
+        .map_err(io_failure)?;
+
+    let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": [] }).to_string();
+    let request = format!(
+        "POST / HTTP/1.1\r\nHost: {endpoint}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(request.as_bytes()).map_err(io_failure)?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).map_err(io_failure)?;
+
+    let body = parse_http(&response)?;
+    let reply: Value = serde_json::from_slice(&body)
+        .map_err(|_| HealthStatus::Http("response is not JSON".to_string()))?;
+    if let Some(error) = reply.get("error") {
+        return Err(HealthStatus::Rpc {
+            code: error["code"].as_i64().unwrap_or(0),
+            message: error["message"].as_str().unwrap_or("").to_string(),
+        });
+    }
+    reply
+        .get("result")
+        .cloned()
+        .ok_or_else(|| HealthStatus::Http("JSON without result or error".to_string()))
+}
+
+fn probe(endpoint: SocketAddrV4, timeout: Duration) -> HealthStatus {
+    let checked = (|| {
+        let health = call(endpoint, "system_health", timeout)?;
+        let sync = call(endpoint, "system_syncState", timeout)?;
+        Ok(HealthStatus::Ok {
+            peers: health["peers"].as_u64().unwrap_or(0),
+            is_syncing: health["isSyncing"].as_bool().unwrap_or(false),
+            best_block: sync["currentBlock"].as_u64().unwrap_or(0),
+        })
+    })();
+    checked.unwrap_or_else(|failure| failure)
+}
+
+// Only RPC nodes serve RPC to the outside, `force` checks other roles anyway
+pub fn check_node(
+    name: &str,
+    timeout: Duration,
+    force: bool,
+) -> Result<HealthReport, PortgenError> {
+    let addr = calculate_address(name)?;
+    if !force && NodeName::parse(name)?.role != "rpc" {
+        return Err("health checks need an rpc node (or --force)".into());
+    }
+    let endpoint = SocketAddrV4::new(addr.ip, addr.port_of(PortKind::Rpc)?.0);
+    Ok(HealthReport {
+        name: name.trim_end_matches(".yaml").to_string(),
+        endpoint,
+        status: probe(endpoint, timeout),
+    })
+}
+
+pub fn check_all(
+    names: &[String],
+    timeout: Duration,
+    force: bool,
+    jobs: usize,
+) -> Result<Vec<HealthReport>, PortgenError> {
+    pool::map_ordered(names, jobs, |name| check_node(name, timeout, force))
+        .ok_or("health worker panicked")?
+        .into_iter()
+        .collect()
+}
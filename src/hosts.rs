@@ -0,0 +1,98 @@
+// /etc/hosts entries and the portgen-managed block inside a hosts file
+use crate::{atomic::write_output, calculate_address, PortgenError};
+use std::{fs, io, path::Path};
+
+pub const BEGIN_MARKER: &str = "# BEGIN portgen";
+pub const END_MARKER: &str = "# END portgen";
+
+pub fn hosts_lines(nodes: &[String]) -> Result<String, PortgenError> {
+    let mut out = String::new();
+    for name in nodes {
+        let addr = calculate_address(name)?;
+        out.push_str(&format!(
+            "{}\t{}\n",
+            addr.ip,
+            name.trim_end_matches(".yaml")
+        ));
+    }
+    Ok(out)
+}
+
+// Swaps the marker block for `entries`, appending one if the file has none
+pub fn replace_block(current: &str, entries: &str) -> Result<String, PortgenError> {
+    let block = format!("{BEGIN_MARKER}\n{entries}{END_MARKER}\n");
+    let lines: Vec<&str> = current.split_inclusive('\n').collect();
+    let begin = lines.iter().position(|l| l.trim_end() == BEGIN_MARKER);
+    let end = lines.iter().position(|l| l.trim_end() == END_MARKER);
+
+    match (begin, end) {
+        (Some(begin), Some(end)) if begin < end => {
+            let before: String = lines[..begin].concat();
+            let after: String = lines[end + 1..].concat();
+            Ok(format!("{before}{block}{after}"))
+        }
+        (None, None) => {
+            let mut out = current.to_string();
+            if !out.is_empty() && !out.ends_with('\n') {
+                out.push('\n');
+            }
+            out.push_str(&block);
+            Ok(out)
+        }
+        _ => Err("file has an unbalanced portgen marker block".into()),
+    }
+}
+
+pub enum SyncOutcome {
+    Unchanged,
+    Updated,
+    // Dry run: unified diff of what would change
+    Diff(String),
+}
+
+pub fn sync(
+    path: &Path,
+    entries: &str,
+    dry_run: bool,
+    backup: bool,
+) -> Result<SyncOutcome, PortgenError> {
+    sync_block(
+        path,
+        entries,
+        dry_run,
+        backup,
+        ("failed to read hosts file", "failed to write hosts file"),
+    )
+}
+
+// The marker block of any file portgen shares with hand-written lines, with
+// the read and write error messages of that kind of file
+pub(crate) fn sync_block(
+    path: &Path,
+    entries: &str,
+    dry_run: bool,
+    backup: bool,
+    (read_err, write_err): (&'static str, &'static str),
+) -> Result<SyncOutcome, PortgenError> {
+    let current = match fs::read_to_string(path) {
+        Ok(current) => current,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(_) => return Err(read_err.into()),
+    };
+    let updated = replace_block(&current, entries)?;
+
+    // Leave the file (and its mtime) alone when nothing changed
+    if updated == current {
+        return Ok(SyncOutcome::Unchanged);
+    }
+    if dry_run {
+        let name = path.display().to_string();
+        let diff = similar::TextDiff::from_lines(&current, &updated)
+            .unified_diff()
+            .header(&name, &name)
+            .to_string();
+        return Ok(SyncOutcome::Diff(diff));
+    }
+    write_output(path, updated.as_bytes(), backup).map_err(|_| write_err)?;
+    Ok(SyncOutcome::Updated)
+}
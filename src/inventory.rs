@@ -0,0 +1,89 @@
+// Inventory files: node names, optionally with metadata carried into JSON/YAML output
+use crate::{ChainId, NodeInfo, NodeName, PortgenError};
+use serde_json::{Map, Value};
+use std::path::Path;
+
+// Fields portgen computes itself, inventory metadata may not override them
+pub const COMPUTED_FIELDS: &[&str] = &["name", "port", "ip", "address", "chain_type"];
+
+#[derive(Debug, Clone)]
+pub struct InventoryEntry {
+    pub name: String,
+    pub metadata: Map<String, Value>,
+}
+
+impl InventoryEntry {
+    fn bare(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            metadata: Map::new(),
+        }
+    }
+}
+
+// A YAML list of `{name: ..., <metadata>...}` objects, bare strings are names
+pub fn parse_yaml(text: &str) -> Result<Vec<InventoryEntry>, PortgenError> {
+    let doc: Value = serde_yaml::from_str(text).map_err(|_| "invalid inventory YAML")?;
+    let Value::Array(items) = doc else {
+        return Err("inventory YAML must be a list of nodes".into());
+    };
+
+    items
+        .into_iter()
+        .map(|item| match item {
+            Value::String(name) => Ok(InventoryEntry::bare(&name)),
+            Value::Object(mut metadata) => {
+                let Some(Value::String(name)) = metadata.shift_remove("name") else {
+                    return Err("inventory entries need a string `name`".into());
+                };
+                if metadata
+                    .keys()
+                    .any(|key| COMPUTED_FIELDS.contains(&key.as_str()))
+                {
+                    return Err(
+                        "inventory metadata must not use port, ip, address or chain_type".into(),
+                    );
+                }
+                Ok(InventoryEntry { name, metadata })
+            }
+            _ => Err("inventory entries must be names or objects".into()),
+        })
+        .collect()
+}
+
+// One name per non-empty line, `#` comments
+pub fn parse_lines(text: &str) -> Vec<InventoryEntry> {
+    text.lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .filter(|line| !line.is_empty())
+        .map(InventoryEntry::bare)
+        .collect()
+}
+
+// `.yaml`/`.yml` files are structured inventories, anything else is a name list
+pub fn read(path: &Path) -> Result<Vec<InventoryEntry>, PortgenError> {
+    let text = std::fs::read_to_string(path).map_err(|_| "failed to read inventory file")?;
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("yaml" | "yml") => parse_yaml(&text),
+        _ => Ok(parse_lines(&text)),
+    }
+}
+
+// Computed fields first, metadata after them in inventory order
+pub fn record(
+    node: &NodeInfo,
+    metadata: Option<&Map<String, Value>>,
+) -> Result<Value, PortgenError> {
+    let chain = NodeName::parse(&node.name)?.chain;
+    let chain_type = ChainId::from_str(chain.as_deref())?.chain_type();
+    let mut object = Map::new();
+    object.insert("name".into(), node.name.clone().into());
+    object.insert("port".into(), node.address.port.0.into());
+    object.insert("ip".into(), node.address.ip.to_string().into());
+    object.insert("address".into(), node.address.to_string().into());
+    object.insert("chain_type".into(), chain_type.to_string().into());
+    if let Some(metadata) = metadata {
+        object.extend(metadata.clone());
+    }
+    Ok(Value::Object(object))
+}
@@ -0,0 +1,58 @@
+// iproute2 commands bringing a node's generated address up on a host, or
+// tearing it down again, quoted for pasting into a shell
+use crate::{ssh::shell_quote, NodeInfo};
+use std::{fmt::Write, net::Ipv4Addr};
+
+// Every node sits in the /24 of its role, network and instance
+pub const PREFIX: u8 = 24;
+
+// First host of the node's /24; chain hosts start at .10 (.100 in v2), so it
+// is never a node
+pub fn gateway(ip: Ipv4Addr) -> Ipv4Addr {
+    let [a, b, c, _] = ip.octets();
+    Ipv4Addr::new(a, b, c, 1)
+}
+
+pub struct IpOptions<'a> {
+    pub interface: &'a str,
+    // Also set the link up (down when deleting)
+    pub link: bool,
+    // Default route via this gateway, None for no route
+    pub gateway: Option<Ipv4Addr>,
+    pub delete: bool,
+}
+
+fn line(out: &mut String, words: &[String]) {
+    let words: Vec<String> = words.iter().map(|word| shell_quote(word)).collect();
+    let _ = writeln!(out, "{}", words.join(" "));
+}
+
+// Commands for one node under a `# <name>` comment; teardown runs in reverse
+pub fn commands(node: &NodeInfo, options: &IpOptions) -> String {
+    let dev = options.interface.to_string();
+    let addr = format!("{}/{PREFIX}", node.address.ip);
+    let words = |parts: &[&str]| -> Vec<String> { parts.iter().map(|p| p.to_string()).collect() };
+
+    let mut steps = Vec::new();
+    if options.link {
+        let state = if options.delete { "down" } else { "up" };
+        steps.push(words(&["ip", "link", "set", "dev", &dev, state]));
+    }
+    let verb = if options.delete { "del" } else { "add" };
+    steps.push(words(&["ip", "addr", verb, &addr, "dev", &dev]));
+    if let Some(gateway) = options.gateway {
+        let via = gateway.to_string();
+        steps.push(words(&[
+            "ip", "route", verb, "default", "via", &via, "dev", &dev,
+        ]));
+    }
+    if options.delete {
+        steps.reverse();
+    }
+
+    let mut out = format!("# {}\n", node.name);
+    for step in &steps {
+        line(&mut out, step);
+    }
+    out
+}
@@ -0,0 +1,153 @@
+// Kubernetes manifests for nodes, built as JSON values and rendered as YAML
+use crate::{
+    script_args::container_args, ChainId, Network, NodeInfo, NodeName, PortKind, PortgenError,
+};
+use serde_json::{json, Map, Value};
+
+pub fn labels(node: &NodeInfo) -> Result<Value, PortgenError> {
+    let parsed = NodeName::parse(&node.name)?;
+    let chain = ChainId::from_str(parsed.chain.as_deref())?.name()?;
+    Ok(json!({
+        "app.kubernetes.io/name": node.name,
+        "app.kubernetes.io/managed-by": "portgen",
+        "portgen/role": parsed.role,
+        "portgen/chain": chain.unwrap_or("relay"),
+        "portgen/network": parsed.network.parse::<Network>()?.to_string(),
+    }))
+}
+
+// Deployment for stateless roles: Recreate so two pods never race for the
+// same hostPort, and an emptyDir that is thrown away with the pod
+pub fn deployment(node: &NodeInfo, image: &str) -> Result<Value, PortgenError> {
+    let role = NodeName::parse(&node.name)?.role;
+    if !matches!(role.as_str(), "boot" | "rpc") {
+        return Err("deployments are only generated for stateless boot and rpc nodes".into());
+    }
+    let labels = labels(node)?;
+    let p2p = node.address.port.0;
+    let rpc = node.address.port_of(PortKind::Rpc)?.0;
+
+    Ok(json!({
+        "apiVersion": "apps/v1",
+        "kind": "Deployment",
+        "metadata": { "name": node.name, "labels": labels },
+        "spec": {
+            "replicas": 1,
+            "strategy": { "type": "Recreate" },
+            "selector": { "matchLabels": { "app.kubernetes.io/name": node.name } },
+            "template": {
+                "metadata": { "labels": labels },
+                "spec": {
+                    "containers": [{
+                        "name": "substrate",
+                        "image": image,
+                        "args": container_args(node)?,
+                        "ports": [
+                            { "name": "p2p", "containerPort": p2p, "hostPort": p2p, "protocol": "TCP" },
+                            { "name": "rpc", "containerPort": rpc, "hostPort": rpc, "protocol": "TCP" },
+                        ],
+                        "livenessProbe": {
+                            "httpGet": { "path": "/health", "port": "rpc" },
+                            "initialDelaySeconds": 30,
+                            "periodSeconds": 10,
+                        },
+                        "volumeMounts": [{ "name": "data", "mountPath": "/data" }],
+                    }],
+                    "volumes": [{ "name": "data", "emptyDir": {} }],
+                },
+            },
+        },
+    }))
+}
+
+// Probe fields to merge into a node's container spec: liveness only checks
+// that libp2p still listens, readiness waits for the RPC health endpoint.
+// Generous delays since a node opens its ports only after loading its database.
+pub fn probes(node: &NodeInfo) -> Result<Value, PortgenError> {
+    let p2p = node.address.port.0;
+    let rpc = node.address.port_of(PortKind::Rpc)?.0;
+    Ok(json!({
+        "livenessProbe": {
+            "tcpSocket": { "port": p2p },
+            "initialDelaySeconds": 60,
+            "periodSeconds": 20,
+            "failureThreshold": 6,
+        },
+        "readinessProbe": {
+            "httpGet": { "path": "/health", "port": rpc },
+            "initialDelaySeconds": 30,
+            "periodSeconds": 10,
+            "failureThreshold": 3,
+        },
+    }))
+}
+
+// ClusterIP Service in front of a node's RPC port
+pub fn service(node: &NodeInfo) -> Result<Value, PortgenError> {
+    let rpc = node.address.port_of(PortKind::Rpc)?.0;
+    Ok(json!({
+        "apiVersion": "v1",
+        "kind": "Service",
+        "metadata": { "name": node.name, "labels": labels(node)? },
+        "spec": {
+            "type": "ClusterIP",
+            "selector": { "app.kubernetes.io/name": node.name },
+            "ports": [{ "name": "rpc", "port": rpc, "targetPort": rpc, "protocol": "TCP" }],
+        },
+    }))
+}
+
+pub struct IngressOptions<'a> {
+    // Hosts are `<node-name>.<domain>`
+    pub domain: &'a str,
+    pub class: Option<&'a str>,
+    pub tls_secret: Option<&'a str>,
+}
+
+// Ingress routing HTTP (and websocket) RPC to the node's Service
+pub fn ingress(node: &NodeInfo, options: &IngressOptions) -> Result<Value, PortgenError> {
+    if NodeName::parse(&node.name)?.role != "rpc" {
+        return Err("ingresses are only generated for rpc nodes".into());
+    }
+    let host = format!("{}.{}", node.name, options.domain.trim_end_matches('.'));
+    let rpc = node.address.port_of(PortKind::Rpc)?.0;
+
+    let mut spec = Map::new();
+    if let Some(class) = options.class {
+        spec.insert("ingressClassName".to_string(), json!(class));
+    }
+    if let Some(secret) = options.tls_secret {
+        spec.insert(
+            "tls".to_string(),
+            json!([{ "hosts": [host], "secretName": secret }]),
+        );
+    }
+    spec.insert(
+        "rules".to_string(),
+        json!([{
+            "host": host,
+            "http": {
+                "paths": [{
+                    "path": "/",
+                    "pathType": "Prefix",
+                    "backend": { "service": { "name": node.name, "port": { "number": rpc } } },
+                }],
+            },
+        }]),
+    );
+    Ok(json!({
+        "apiVersion": "networking.k8s.io/v1",
+        "kind": "Ingress",
+        "metadata": { "name": node.name, "labels": labels(node)? },
+        "spec": spec,
+    }))
+}
+
+// Multi-document YAML stream, one manifest per document
+pub fn to_yaml_stream(manifests: &[Value]) -> Result<String, PortgenError> {
+    let documents = manifests
+        .iter()
+        .map(|manifest| serde_yaml::to_string(manifest).map_err(|_| "failed to serialize manifest"))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(documents.join("---\n"))
+}
@@ -0,0 +1,65 @@
+// keepalived configuration for the VIP in front of a chain's RPC pool. Real
+// servers listen on their own generated ports, so the virtual server uses NAT
+// to map the VIP port onto each of them.
+use crate::{endpoints::rpc_pool, Network, PortKind, PortgenError};
+use std::{fmt::Write, net::Ipv4Addr};
+
+#[derive(Debug, Clone)]
+pub struct VirtualServer {
+    pub network: Network,
+    // None for the relay chain
+    pub chain: Option<String>,
+    pub vip: Ipv4Addr,
+    pub vip_port: u16,
+    // Emits the vrrp_instance skeleton when set
+    pub vrid: Option<u8>,
+    pub interface: String,
+}
+
+const CONNECT_TIMEOUT_SECS: u8 = 3;
+
+pub fn config(server: &VirtualServer) -> Result<String, PortgenError> {
+    let pool = rpc_pool(server.network, server.chain.as_deref())?;
+    let chain = server.chain.as_deref().unwrap_or("relay");
+    let mut out = String::new();
+
+    if let Some(vrid) = server.vrid {
+        let _ = writeln!(
+            out,
+            "vrrp_instance VI_{}_{} {{",
+            chain.replace('-', "_"),
+            server.network
+        );
+        let _ = writeln!(out, "    state BACKUP");
+        let _ = writeln!(out, "    interface {}", server.interface);
+        let _ = writeln!(out, "    virtual_router_id {vrid}");
+        let _ = writeln!(out, "    priority 100");
+        let _ = writeln!(out, "    advert_int 1");
+        let _ = writeln!(out, "    virtual_ipaddress {{");
+        let _ = writeln!(out, "        {}", server.vip);
+        let _ = writeln!(out, "    }}");
+        let _ = writeln!(out, "}}");
+        let _ = writeln!(out);
+    }
+
+    let _ = writeln!(out, "# {chain} rpc pool on {}", server.network);
+    let _ = writeln!(out, "virtual_server {} {} {{", server.vip, server.vip_port);
+    let _ = writeln!(out, "    delay_loop 6");
+    let _ = writeln!(out, "    lb_algo rr");
+    let _ = writeln!(out, "    lb_kind NAT");
+    let _ = writeln!(out, "    protocol TCP");
+    for node in &pool {
+        let port = node.address.port_of(PortKind::Rpc)?;
+        let _ = writeln!(out);
+        let _ = writeln!(out, "    # {}", node.name);
+        let _ = writeln!(out, "    real_server {} {port} {{", node.address.ip);
+        let _ = writeln!(out, "        weight 1");
+        let _ = writeln!(out, "        TCP_CHECK {{");
+        let _ = writeln!(out, "            connect_port {port}");
+        let _ = writeln!(out, "            connect_timeout {CONNECT_TIMEOUT_SECS}");
+        let _ = writeln!(out, "        }}");
+        let _ = writeln!(out, "    }}");
+    }
+    let _ = writeln!(out, "}}");
+    Ok(out)
+}
@@ -0,0 +1,1269 @@
+//! Port and IP address scheme for substrate node names
+//!
+//! # Environment variables
+//!
+//! Every flag of the `portgen` binary can also be set through the environment,
+//! named by [`config::env_var`]: `PORTGEN_<FLAG>` for top-level flags and
+//! `PORTGEN_<SUBCOMMAND>_<FLAG>` for the flags of a subcommand, with dashes as
+//! underscores. A flag on the command line wins over its variable, and the
+//! variable over config files. Switches take `true` or `false`.
+//!
+//! | Variable | Flag |
+//! |---|---|
+//! | `PORTGEN_FORMAT` | `--format` |
+//! | `PORTGEN_CONFIG` | `--config` |
+//! | `PORTGEN_SCHEMA` | `--schema` |
+//! | `PORTGEN_IMAGE` | `--image` |
+//! | `PORTGEN_RESERVATIONS` | `--reservations` |
+//! | `PORTGEN_QUIET`, `PORTGEN_VERBOSE` | `--quiet`, `--verbose` |
+//! | `PORTGEN_BIND_CHECK_PORT_BASE` | `bind-check --port-base` |
+//! | `PORTGEN_SYSTEMD_UNIT_USER` | `systemd-unit --user` |
+//!
+//! `portgen --help` and the help of each subcommand list the variable next to
+//! every flag.
+pub mod annotate;
+pub mod apply;
+pub mod atomic;
+pub mod audit;
+pub mod aws;
+pub mod batch;
+pub mod bind;
+pub mod blackbox;
+pub mod chains;
+pub mod config;
+pub mod consistency;
+pub mod denylist;
+pub mod dns;
+pub mod docker;
+pub mod endpoints;
+pub mod env_tree;
+mod error;
+pub mod firewall;
+mod generated;
+pub mod git_hook;
+pub mod grafana;
+pub mod health;
+pub mod hosts;
+pub mod inventory;
+pub mod iproute;
+pub mod k8s;
+pub mod keepalived;
+pub mod lint;
+pub mod migration;
+pub mod nats;
+pub mod netplan;
+pub mod nomad;
+pub mod normalize;
+pub mod occupancy;
+pub mod opa;
+pub mod para_ids;
+pub mod pcap;
+pub mod pjs;
+pub mod pool;
+pub mod quadlet;
+pub mod ranges;
+pub mod redis;
+pub mod registry;
+pub mod releases;
+pub mod remote_audit;
+pub mod render;
+pub mod report;
+pub mod reservations;
+pub mod reserved;
+pub mod roles;
+pub mod rpc_proxy;
+pub mod scaffold;
+pub mod script_args;
+pub mod selection;
+pub mod selftest;
+pub mod selinux;
+pub mod services;
+pub mod ssh;
+pub mod subnet;
+pub mod suggest;
+pub mod summary;
+pub mod supervisor;
+pub mod synonyms;
+pub mod systemd;
+pub mod tailscale;
+pub mod time;
+pub mod topology;
+pub mod verify;
+
+use std::{fmt, net::Ipv4Addr, ops::RangeInclusive, str::FromStr};
+
+pub use error::PortgenError;
+pub use generated::WELL_KNOWN_ADDRESSES;
+
+pub const PORT_BASE: u16 = 30000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Port(pub u16);
+
+impl fmt::Display for Port {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// The scheme assigns one p2p port per node, the other services of a node sit
+// in their own 10000-wide band above it (31001 p2p -> 41001 rpc, ...)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PortKind {
+    P2p,
+    Rpc,
+    // Separate websocket port of nodes older than polkadot-sdk 1.0
+    Ws,
+    Prometheus,
+}
+
+impl PortKind {
+    pub const ALL: [Self; 4] = [Self::P2p, Self::Rpc, Self::Ws, Self::Prometheus];
+
+    pub fn band_offset(self) -> u16 {
+        match self {
+            Self::P2p => 0,
+            Self::Rpc => 10000,
+            Self::Ws => 20000,
+            Self::Prometheus => 30000,
+        }
+    }
+}
+
+impl fmt::Display for PortKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::P2p => "p2p",
+            Self::Rpc => "rpc",
+            Self::Ws => "ws",
+            Self::Prometheus => "prometheus",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for PortKind {
+    type Err = PortgenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.to_string() == s)
+            .ok_or("invalid port kind".into())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct NodeAddress {
+    pub port: Port,
+    pub ip: Ipv4Addr,
+}
+
+impl NodeAddress {
+    pub fn multiaddr(&self) -> String {
+        format!("/ip4/{}/tcp/{}", self.ip, self.port)
+    }
+
+    pub fn port_of(&self, kind: PortKind) -> Result<Port, PortgenError> {
+        self.port
+            .0
+            .checked_add(kind.band_offset())
+            .map(Port)
+            .ok_or("port base too high, port exceeds 65535".into())
+    }
+}
+
+impl fmt::Display for NodeAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.ip, self.port)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NodeInfo {
+    pub name: String,
+    pub address: NodeAddress,
+}
+
+impl NodeInfo {
+    pub fn new(name: String) -> Result<Self, PortgenError> {
+        Self::with_schema(name, &PORT_SCHEMA_V1)
+    }
+
+    pub fn with_schema(name: String, schema: &PortSchema) -> Result<Self, PortgenError> {
+        let address = schema.address(&name)?;
+        Ok(Self { name, address })
+    }
+}
+
+impl fmt::Display for NodeInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.name, self.address)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Network {
+    Polkadot = 1,
+    Kusama = 2,
+    Westend = 3,
+    Paseo = 4,
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Polkadot => "polkadot",
+            Self::Kusama => "kusama",
+            Self::Westend => "westend",
+            Self::Paseo => "paseo",
+        };
+        f.write_str(name)
+    }
+}
+
+// Highest chain id the port formula has room for. A network owns the 1000
+// ports PORT_BASE + N*1000 .. PORT_BASE + N*1000 + 999, and within it a chain
+// owns CC*10 .. CC*10 + 9 for its role digits 0-9. CC*10 + 9 <= 999 holds up
+// to CC = 99; chain 100 would start at N*1000 + 1000, which is network N+1's
+// relay chain bootnode.
+pub const MAX_CHAIN_ID: u16 = 99;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainId(pub u16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChainType {
+    RelayChain,
+    SystemParachain,
+    CommunityParachain,
+}
+
+impl ChainType {
+    pub fn is_parachain(self) -> bool {
+        self != Self::RelayChain
+    }
+}
+
+impl fmt::Display for ChainType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::RelayChain => "relay",
+            Self::SystemParachain => "system",
+            Self::CommunityParachain => "community",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for ChainType {
+    type Err = PortgenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "relay" => Ok(Self::RelayChain),
+            "system" => Ok(Self::SystemParachain),
+            "community" => Ok(Self::CommunityParachain),
+            _ => Err("invalid chain type".into()),
+        }
+    }
+}
+
+// What each role may be and where it lands in the scheme. Everything that
+// needs per-role facts (parsing, digits, enumeration, docs) reads them from
+// `roles::table()`, these built-ins merged with the configured roles.
+#[derive(Debug, Clone)]
+pub struct RoleConstraints {
+    // Role token used in node names
+    pub abbrev: &'static str,
+    pub name: &'static str,
+    pub instances: RangeInclusive<u8>,
+    // Instances that own a distinct slot, None when the role reuses another's
+    pub slots: Option<RangeInclusive<u8>>,
+    // Port digit: `port_offset`, plus the instance when `instance_in_port`
+    pub port_offset: u16,
+    pub instance_in_port: bool,
+    pub ip_digit: u8,
+    pub parachain_only: bool,
+}
+
+impl RoleConstraints {
+    pub fn get(abbrev: &str) -> Option<&'static Self> {
+        roles::table().iter().find(|role| role.abbrev == abbrev)
+    }
+
+    // Instance digits of the third octet the role's names land on
+    pub fn ip_instances(&self) -> RangeInclusive<u8> {
+        if self.instance_in_port {
+            self.instances.clone()
+        } else {
+            0..=0
+        }
+    }
+
+    // Role whose own slots hold the IP of the given role digit and instance
+    pub fn owning_ip(ip_digit: u8, instance: u8) -> Option<&'static Self> {
+        roles::table().iter().find(|role| {
+            role.ip_digit == ip_digit
+                && role.slots.is_some()
+                && role.ip_instances().contains(&instance)
+        })
+    }
+
+    pub fn port_digit(&self, instance: u8) -> u16 {
+        if self.instance_in_port {
+            self.port_offset + instance as u16
+        } else {
+            self.port_offset
+        }
+    }
+
+    // First valid name of the role, on a parachain when the role needs one
+    pub fn example(&self) -> String {
+        let chain = self.parachain_only.then_some("asset-hub");
+        compose_name(
+            self.abbrev,
+            chain,
+            Network::Polkadot,
+            *self.instances.start(),
+        )
+    }
+}
+
+// Bootnodes are only ever deployed as 00, so they add one slot. Collators
+// share the validators' port digits but sit in the IP slots bootnodes leave
+// free (ip digit 0, instances 1-3), so the two never share an address.
+pub const ROLE_CONSTRAINTS: &[RoleConstraints] = &[
+    RoleConstraints {
+        abbrev: "boot",
+        name: "bootnode",
+        instances: 0..=9,
+        slots: Some(0..=0),
+        port_offset: 0,
+        instance_in_port: false,
+        ip_digit: 0,
+        parachain_only: false,
+    },
+    RoleConstraints {
+        abbrev: "rpc",
+        name: "rpc node",
+        instances: 1..=3,
+        slots: Some(1..=3),
+        port_offset: 0,
+        instance_in_port: true,
+        ip_digit: 1,
+        parachain_only: false,
+    },
+    RoleConstraints {
+        abbrev: "val",
+        name: "validator",
+        instances: 1..=6,
+        slots: Some(1..=6),
+        port_offset: 3,
+        instance_in_port: true,
+        ip_digit: 2,
+        parachain_only: false,
+    },
+    RoleConstraints {
+        abbrev: "col",
+        name: "collator",
+        instances: 1..=3,
+        slots: Some(1..=3),
+        port_offset: 3,
+        instance_in_port: true,
+        ip_digit: 0,
+        parachain_only: true,
+    },
+];
+
+// A role token and instance of a name, checked against the role table
+#[derive(Debug, Clone, Copy)]
+pub struct Role {
+    constraints: &'static RoleConstraints,
+    instance: u8,
+}
+
+impl Role {
+    pub fn instance_range(role: &str) -> Option<RangeInclusive<u8>> {
+        RoleConstraints::get(role).map(|role| role.instances.clone())
+    }
+
+    pub fn from_str(role: &str, instance_str: &str) -> Result<Self, PortgenError> {
+        if instance_str.len() != 2 {
+            return Err("instance must be two digits (00-09)".into());
+        }
+
+        let num: u8 = instance_str
+            .parse()
+            .map_err(|_| "invalid instance number")?;
+
+        match RoleConstraints::get(role) {
+            Some(constraints) if constraints.instances.contains(&num) => Ok(Self {
+                constraints,
+                instance: num,
+            }),
+            _ => Err("invalid role/instance combination".into()),
+        }
+    }
+
+    pub fn constraints(self) -> &'static RoleConstraints {
+        self.constraints
+    }
+
+    pub fn to_digit(self) -> u16 {
+        self.constraints().port_digit(self.get_instance_number())
+    }
+
+    pub fn to_ip_digit(self) -> u8 {
+        self.constraints().ip_digit
+    }
+
+    // Bootnodes count as instance 0 whatever the name says
+    pub fn get_instance_number(self) -> u8 {
+        if self.constraints.instance_in_port {
+            self.instance
+        } else {
+            0
+        }
+    }
+}
+
+impl Network {
+    pub const ALL: [Self; 4] = [Self::Polkadot, Self::Kusama, Self::Westend, Self::Paseo];
+
+    pub fn from_digit(digit: u8) -> Option<Self> {
+        match digit {
+            1 => Some(Self::Polkadot),
+            2 => Some(Self::Kusama),
+            3 => Some(Self::Westend),
+            4 => Some(Self::Paseo),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = PortgenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "polkadot" => Ok(Self::Polkadot),
+            "kusama" => Ok(Self::Kusama),
+            "westend" => Ok(Self::Westend),
+            "paseo" => Ok(Self::Paseo),
+            _ => Err("invalid network name".into()),
+        }
+    }
+}
+
+// Built-in chains by id, the first name listed is the canonical one;
+// `chains::table` adds the config file's
+pub const CHAINS: &[(u16, &[&str])] = &[
+    // system
+    (1, &["asset-hub", "statemine", "statemint"]),
+    (2, &["bridge-hub", "bridgehub"]),
+    (3, &["collectives"]),
+    (4, &["people"]),
+    (5, &["coretime"]),
+    (6, &["encointer"]),
+    // custom
+    (20, &["moonbeam", "moonriver"]),
+    (21, &["hyperbridge", "nexus", "gargantua"]),
+    (22, &["interlay", "kintsugi"]),
+    (23, &["acala", "karura"]),
+    (24, &["kilt", "spiritnet"]),
+    (25, &["hydration", "hydradx"]),
+    (26, &["bifrost-polkadot", "bifrost-kusama"]),
+    (27, &["bajun", "ajuna"]),
+    (28, &["polimec"]),
+    (29, &["unique", "quartz"]),
+    (30, &["invarch"]),
+];
+
+// Registry names on their way out: renamed aliases name their replacement,
+// sunset chains have none. Deprecated names still resolve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deprecation {
+    pub name: &'static str,
+    pub replacement: Option<&'static str>,
+    pub note: &'static str,
+}
+
+pub const DEPRECATED: &[Deprecation] = &[
+    Deprecation {
+        name: "statemint",
+        replacement: Some("asset-hub"),
+        note: "renamed to Asset Hub Polkadot",
+    },
+    Deprecation {
+        name: "statemine",
+        replacement: Some("asset-hub"),
+        note: "renamed to Asset Hub Kusama",
+    },
+    Deprecation {
+        name: "hydradx",
+        replacement: Some("hydration"),
+        note: "renamed to Hydration",
+    },
+];
+
+impl Deprecation {
+    pub fn get(chain: &str) -> Option<&'static Self> {
+        DEPRECATED.iter().find(|entry| entry.name == chain)
+    }
+
+    // Deprecation of the chain name a node name is spelled with, or of the
+    // chain itself when it is sunset
+    pub fn of_node(node_str: &str) -> Option<&'static Self> {
+        let node = NodeName::parse(node_str.trim_end_matches(".yaml")).ok()?;
+        let written = node.written_chain?;
+        let canonical = normalize_chain_name(&written)?;
+        Self::get(&written).or_else(|| Self::get(canonical))
+    }
+}
+
+impl fmt::Display for Deprecation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "chain name '{}' is deprecated ({})",
+            self.name, self.note
+        )?;
+        match self.replacement {
+            Some(replacement) => write!(f, ", use '{replacement}'"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl ChainId {
+    pub fn new(id: u16) -> Result<Self, PortgenError> {
+        if id > MAX_CHAIN_ID {
+            return Err(
+                "chain id above MAX_CHAIN_ID, its ports would collide with the next network".into(),
+            );
+        }
+        Ok(ChainId(id))
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(chain: Option<&str>) -> Result<Self, PortgenError> {
+        let Some(name) = chain else {
+            return Ok(ChainId(0));
+        };
+        let name = normalize_chain_name(name).ok_or("unknown chain name")?;
+        let &(id, _) = chains::table()
+            .iter()
+            .find(|(_, names)| names[0] == name)
+            .ok_or("unknown chain name")?;
+        Self::new(id)
+    }
+
+    // Chain names end up in Kubernetes labels and resource names, so they must
+    // be valid label values: at most 63 lowercase alphanumerics and single
+    // hyphens, not starting or ending with a hyphen
+    pub fn validate_name(s: &str) -> Result<(), PortgenError> {
+        let reason = if s.is_empty() {
+            Some("must not be empty")
+        } else if s.len() > 63 {
+            Some("must be at most 63 characters")
+        } else if !s
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        {
+            Some("only lowercase letters, digits and hyphens are allowed")
+        } else if s.starts_with('-') || s.ends_with('-') {
+            Some("must not start or end with a hyphen")
+        } else if s.contains("--") {
+            Some("must not contain consecutive hyphens")
+        } else {
+            None
+        };
+
+        match reason {
+            Some(reason) => Err(PortgenError::InvalidChainName {
+                name: s.to_string(),
+                reason,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    // Checks every registered chain name, returns how many were checked
+    pub fn validate_registry() -> Result<usize, PortgenError> {
+        let mut checked = 0;
+        for name in chains::table().iter().flat_map(|(_, names)| names.iter()) {
+            Self::validate_name(name)?;
+            checked += 1;
+        }
+        Ok(checked)
+    }
+
+    // Canonical chain name, None for the relay chain
+    pub fn name(self) -> Result<Option<&'static str>, PortgenError> {
+        if self.0 == 0 {
+            return Ok(None);
+        }
+        chains::table()
+            .iter()
+            .find(|&&(id, _)| id == self.0)
+            .map(|(_, names)| Some(names[0]))
+            .ok_or("unknown chain id".into())
+    }
+
+    // Ids for chains registered without one, each derived from the chain's
+    // name (see `chains::auto_id`) so other chains never move it
+    pub fn auto_assign(names: &[&str]) -> Result<Vec<(String, ChainId)>, PortgenError> {
+        chains::assign(names, chains::table())
+    }
+
+    // Sunset chains, whose canonical name is deprecated
+    pub fn is_deprecated(self) -> bool {
+        self.name()
+            .ok()
+            .flatten()
+            .is_some_and(|name| Deprecation::get(name).is_some())
+    }
+
+    // Ids below 20 are reserved for system parachains
+    pub fn chain_type(&self) -> ChainType {
+        match self.0 {
+            0 => ChainType::RelayChain,
+            1..=19 => ChainType::SystemParachain,
+            _ => ChainType::CommunityParachain,
+        }
+    }
+
+    pub fn to_ip_host(self) -> u8 {
+        self.0 as u8 + 10 // Start from .10 for relay chain
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeName {
+    pub role: String,
+    // Canonical when the chain is registered, as written otherwise
+    pub chain: Option<String>,
+    // The chain as spelled in the name, aliases and deprecated names included
+    pub written_chain: Option<String>,
+    pub network: String,
+    pub instance: String,
+}
+
+impl NodeName {
+    pub fn parse(s: &str) -> Result<Self, PortgenError> {
+        let parts: Vec<&str> = s.trim_end_matches(".yaml").split('-').collect();
+        if parts.len() < 3 {
+            return Err("invalid node name format".into());
+        }
+
+        let role = parts.first().ok_or("missing role")?;
+        let instance = parts.last().ok_or("missing instance")?;
+        let network = parts[parts.len() - 2];
+
+        // `rpc-coretime-01` reads as a relay chain node on network `coretime`;
+        // a chain name there almost always means the network was left out
+        let middle = parts[1..parts.len() - 1].join("-");
+        if network.parse::<Network>().is_err() && ChainId::from_str(Some(&middle)).is_ok() {
+            return Err(PortgenError::AmbiguousChain {
+                chain: middle,
+                role: role.to_string(),
+                instance: instance.to_string(),
+            });
+        }
+
+        let written_chain = if parts.len() > 3 {
+            Some(parts[1..parts.len() - 2].join("-"))
+        } else {
+            None
+        };
+        let chain = written_chain
+            .as_deref()
+            .map(|chain| normalize_chain_name(chain).map_or(chain.to_string(), str::to_string));
+
+        Ok(Self {
+            role: role.to_string(),
+            chain,
+            written_chain,
+            network: network.to_string(),
+            instance: instance.to_string(),
+        })
+    }
+}
+
+// Constants of the port and IP formula. Breaking changes to the formula get a
+// new version so deployments can plan the move with `schema-diff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortSchema {
+    pub version: u8,
+    pub port_base: u16,
+    // Port distance between networks and between chains of one network
+    pub network_stride: u16,
+    pub chain_stride: u16,
+    // First two IP octets
+    pub ip_prefix: [u8; 2],
+    // Fourth IP octet of the relay chain, parachains follow by chain id
+    pub chain_host_offset: u8,
+}
+
+// The current formula: 3NCCI ports, 192.168.{RNI}.{chain_id+10}
+pub const PORT_SCHEMA_V1: PortSchema = PortSchema {
+    version: 1,
+    port_base: PORT_BASE,
+    network_stride: 1000,
+    chain_stride: 10,
+    ip_prefix: [192, 168],
+    chain_host_offset: 10,
+};
+
+// Planned: nodes move to 10.10.0.0/16 and chain hosts start at .100, leaving
+// the low host numbers of each /24 for gateways and load balancers
+pub const PORT_SCHEMA_V2: PortSchema = PortSchema {
+    version: 2,
+    ip_prefix: [10, 10],
+    chain_host_offset: 100,
+    ..PORT_SCHEMA_V1
+};
+
+impl Default for PortSchema {
+    fn default() -> Self {
+        PORT_SCHEMA_V1
+    }
+}
+
+impl PortSchema {
+    pub const ALL: [Self; 2] = [PORT_SCHEMA_V1, PORT_SCHEMA_V2];
+
+    pub fn from_version(version: u8) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|schema| schema.version == version)
+    }
+
+    pub fn port(&self, node_str: &str) -> Result<Port, PortgenError> {
+        let node = NodeName::parse(node_str)?;
+
+        let network = node.network.parse::<Network>()?;
+        let chain_id = ChainId::from_str(node.chain.as_deref())?;
+        let role = Role::from_str(&node.role, &node.instance)?;
+
+        if role.constraints().parachain_only && !chain_id.chain_type().is_parachain() {
+            return Err("collators are only valid on parachains".into());
+        }
+
+        self.port_of_parts(network, chain_id, role)
+    }
+
+    // The formula itself, for parts that are already parsed
+    pub fn port_of_parts(
+        &self,
+        network: Network,
+        chain_id: ChainId,
+        role: Role,
+    ) -> Result<Port, PortgenError> {
+        let chain_id = ChainId::new(chain_id.0)?;
+        let offset = (network as u16 * self.network_stride)
+            + (chain_id.0 * self.chain_stride)
+            + role.to_digit();
+        let port = self
+            .port_base
+            .checked_add(offset)
+            .ok_or("port base too high, port exceeds 65535")?;
+
+        Ok(Port(port))
+    }
+
+    // Raw (role, network, instance) digits of the third octet and the chain
+    // id of the fourth, unchecked
+    pub fn decompose_ip(&self, ip: Ipv4Addr) -> Result<(u8, u8, u8, u8), PortgenError> {
+        let [a, b, third_octet, fourth_octet] = ip.octets();
+        if [a, b] != self.ip_prefix {
+            return Err("address is outside the schema's /16".into());
+        }
+        let chain_id = fourth_octet
+            .checked_sub(self.chain_host_offset)
+            .ok_or("unknown chain id")?;
+        Ok((
+            third_octet / 100,
+            third_octet / 10 % 10,
+            third_octet % 10,
+            chain_id,
+        ))
+    }
+
+    pub fn reverse_lookup(&self, ip: Ipv4Addr) -> Result<String, PortgenError> {
+        let (role_digit, network_digit, instance, chain_id) = self.decompose_ip(ip)?;
+        let role = RoleConstraints::owning_ip(role_digit, instance)
+            .ok_or("address is not a node address")?
+            .abbrev;
+        let network = Network::from_digit(network_digit).ok_or("unknown network digit")?;
+
+        let name = compose_name(role, ChainId(chain_id as u16).name()?, network, instance);
+        // Rejects instance digits outside the role's range, and boot
+        // addresses whose instance digit is not 0
+        if self.address(&name)?.ip != ip {
+            return Err("address is not a node address".into());
+        }
+        Ok(name)
+    }
+
+    pub fn address(&self, node_str: &str) -> Result<NodeAddress, PortgenError> {
+        let node = NodeName::parse(node_str)?;
+
+        let network = node.network.parse::<Network>()?;
+        let chain_id = ChainId::from_str(node.chain.as_deref())?;
+        let role = Role::from_str(&node.role, &node.instance)?;
+
+        let port = self.port(node_str)?;
+
+        // Calculate third octet: {role}{network}{instance}
+        let third_octet = role.to_ip_digit() * 100 +    // First digit (0/1/2) * 100
+            (network as u8) * 10 +        // Second digit (1-4) * 10
+            role.get_instance_number(); // Third digit (instance number)
+
+        let fourth_octet = u8::try_from(chain_id.0)
+            .ok()
+            .and_then(|id| id.checked_add(self.chain_host_offset))
+            .ok_or("chain id does not fit the IP host octet")?;
+
+        // {prefix}.xyz.abc
+        let [a, b] = self.ip_prefix;
+        let ip = Ipv4Addr::new(a, b, third_octet, fourth_octet);
+
+        Ok(NodeAddress { port, ip })
+    }
+
+    // Every p2p port a network can hand out, its chains and roles included
+    pub fn port_range(&self, network: Network) -> Result<RangeInclusive<u16>, PortgenError> {
+        let first = (network as u16)
+            .checked_mul(self.network_stride)
+            .and_then(|offset| offset.checked_add(self.port_base))
+            .ok_or("port base too high, port exceeds 65535")?;
+        let last = (first as u32 + self.network_stride as u32)
+            .checked_sub(1)
+            .and_then(|last| u16::try_from(last).ok())
+            .ok_or("port base too high, port exceeds 65535")?;
+        Ok(first..=last)
+    }
+
+    // Lowest and highest address of a role on a network, over every instance
+    // and chain id. The addresses in between are not all the role's: each
+    // third octet holds one instance, and chain hosts stop at MAX_CHAIN_ID.
+    pub fn ip_range(&self, network: Network, role: &RoleConstraints) -> (Ipv4Addr, Ipv4Addr) {
+        let [a, b] = self.ip_prefix;
+        let third = |instance: u8| role.ip_digit * 100 + network as u8 * 10 + instance;
+        // Collators never sit on the relay chain
+        let first_chain = u8::from(role.parachain_only);
+        (
+            Ipv4Addr::new(
+                a,
+                b,
+                third(*role.instances.start()),
+                self.chain_host_offset + first_chain,
+            ),
+            Ipv4Addr::new(
+                a,
+                b,
+                third(*role.instances.end()),
+                self.chain_host_offset + MAX_CHAIN_ID as u8,
+            ),
+        )
+    }
+
+    // The chain's bootnode address. The chain fixes only the fourth octet;
+    // its other roles and instances differ from this in the third.
+    pub fn chain_ip(&self, network: Network, chain: ChainId) -> Ipv4Addr {
+        let [a, b] = self.ip_prefix;
+        // ChainId::new keeps ids at most MAX_CHAIN_ID, so the host fits
+        Ipv4Addr::new(
+            a,
+            b,
+            network as u8 * 10,
+            self.chain_host_offset + chain.0 as u8,
+        )
+    }
+}
+
+pub fn calculate_port(node_str: &str) -> Result<Port, PortgenError> {
+    PORT_SCHEMA_V1.port(node_str)
+}
+
+pub fn calculate_port_with_base(node_str: &str, port_base: u16) -> Result<Port, PortgenError> {
+    let schema = PortSchema {
+        port_base,
+        ..PORT_SCHEMA_V1
+    };
+    schema.port(node_str)
+}
+
+pub fn calculate_address(node_str: &str) -> Result<NodeAddress, PortgenError> {
+    PORT_SCHEMA_V1.address(node_str)
+}
+
+// Range building blocks for firewall rules, under the current schema
+pub fn port_range_for_network(network: Network) -> Result<RangeInclusive<u16>, PortgenError> {
+    PORT_SCHEMA_V1.port_range(network)
+}
+
+pub fn ip_range_for_network_role(network: Network, role: &RoleConstraints) -> (Ipv4Addr, Ipv4Addr) {
+    PORT_SCHEMA_V1.ip_range(network, role)
+}
+
+pub fn ip_range_for_chain(network: Network, chain: ChainId) -> Ipv4Addr {
+    PORT_SCHEMA_V1.chain_ip(network, chain)
+}
+
+// Nodes of a network whose port or IP differs between two schemas
+pub fn schema_diff(
+    network: Network,
+    from: &PortSchema,
+    to: &PortSchema,
+) -> Result<Vec<(String, NodeAddress, NodeAddress)>, PortgenError> {
+    let mut changed = Vec::new();
+    for name in enumerate_nodes(network) {
+        let old = from.address(&name)?;
+        let new = to.address(&name)?;
+        if old.port != new.port || old.ip != new.ip {
+            changed.push((name, old, new));
+        }
+    }
+    Ok(changed)
+}
+
+// The node name with chain aliases replaced by the canonical chain name and
+// without a `.yaml` suffix, e.g. rpc-statemint-polkadot-01 -> rpc-asset-hub-polkadot-01
+pub fn canonical_name(node_str: &str) -> Result<String, PortgenError> {
+    let node = NodeName::parse(node_str)?;
+    let network = node.network.parse::<Network>()?;
+    let chain = ChainId::from_str(node.chain.as_deref())?.name()?;
+    Role::from_str(&node.role, &node.instance)?;
+    Ok(format!(
+        "{}-{network}-{}",
+        std::iter::once(node.role.as_str())
+            .chain(chain)
+            .collect::<Vec<_>>()
+            .join("-"),
+        node.instance
+    ))
+}
+
+pub fn compose_name(role: &str, chain: Option<&str>, network: Network, instance: u8) -> String {
+    match chain {
+        None => format!("{role}-{network}-{instance:02}"),
+        Some(chain) => format!("{role}-{chain}-{network}-{instance:02}"),
+    }
+}
+
+// Every slot of one chain on a network, bootnode first
+pub fn enumerate_chain_nodes(network: Network, chain: Option<&str>) -> Vec<String> {
+    roles::table()
+        .iter()
+        // Collators have no slots on the relay chain
+        .filter(|role| chain.is_some() || !role.parachain_only)
+        .filter_map(|role| Some((role.abbrev, role.slots.clone()?)))
+        .flat_map(|(role, slots)| {
+            slots.map(move |instance| compose_name(role, chain, network, instance))
+        })
+        .collect()
+}
+
+// Canonical (first listed) name of a registered chain, given any of its names:
+// statemint -> asset-hub
+pub fn normalize_chain_name(s: &str) -> Option<&'static str> {
+    chains::table()
+        .iter()
+        .find(|(_, names)| names.contains(&s))
+        .map(|(_, names)| names[0])
+}
+
+// Relay chain first, then each registered chain by id
+pub fn enumerate_chains() -> impl Iterator<Item = Option<&'static str>> {
+    std::iter::once(None).chain(chains::table().iter().map(|(_, names)| Some(names[0])))
+}
+
+// Every slot of a network
+pub fn enumerate_nodes(network: Network) -> Vec<String> {
+    enumerate_chains()
+        .flat_map(|chain| enumerate_chain_nodes(network, chain))
+        .collect()
+}
+
+// Raw (network, chain id, instance) digits of a port: 3NCCI read back into
+// its parts, without checking that they name a network, chain or instance
+pub fn decompose_port(port: u16, port_base: u16) -> Result<(u8, u8, u8), PortgenError> {
+    let offset = port
+        .checked_sub(port_base)
+        .ok_or("port is below the port base")?;
+    if offset >= 10000 {
+        return Err("port is outside the scheme's range".into());
+    }
+    Ok((
+        (offset / 1000) as u8,
+        (offset % 1000 / 10) as u8,
+        (offset % 10) as u8,
+    ))
+}
+
+// Raw (role, network, instance) digits of the third octet of a v1 address
+// and the chain id of its fourth, unchecked like decompose_port
+pub fn decompose_ip(ip: Ipv4Addr) -> Result<(u8, u8, u8, u8), PortgenError> {
+    PORT_SCHEMA_V1.decompose_ip(ip)
+}
+
+// Node name owning the given v1 IP, the inverse of the IP half of
+// calculate_address
+pub fn reverse_lookup(ip: Ipv4Addr) -> Result<String, PortgenError> {
+    PORT_SCHEMA_V1.reverse_lookup(ip)
+}
+
+// Every schema version the IP is a node address of, with the node's name.
+// The versions use different prefixes, so at most one matches today.
+pub fn identify_schema(ip: Ipv4Addr) -> Vec<(PortSchema, String)> {
+    PortSchema::ALL
+        .into_iter()
+        .filter_map(|schema| Some((schema, schema.reverse_lookup(ip).ok()?)))
+        .collect()
+}
+
+// Every relay chain validator talks to every node of the given role on the parachain
+pub fn cross_chain_pairs(
+    relay: Network,
+    para: &str,
+    para_role: &str,
+) -> Result<Vec<(NodeInfo, NodeInfo)>, PortgenError> {
+    if !ChainId::from_str(Some(para))?.chain_type().is_parachain() {
+        return Err("parachain must not be the relay chain".into());
+    }
+    if !matches!(para_role, "col" | "val") {
+        return Err("parachain role must be col or val".into());
+    }
+
+    let relay_range = Role::instance_range("val").ok_or("invalid role")?;
+    let para_range = Role::instance_range(para_role).ok_or("invalid role")?;
+
+    let mut pairs = Vec::new();
+    for relay_instance in relay_range {
+        let relay_node = NodeInfo::new(compose_name("val", None, relay, relay_instance))?;
+        for para_instance in para_range.clone() {
+            let para_node =
+                NodeInfo::new(compose_name(para_role, Some(para), relay, para_instance))?;
+            pairs.push((relay_node.clone(), para_node));
+        }
+    }
+
+    Ok(pairs)
+}
+
+// Node names used in the README and `--help` examples
+pub const DOCUMENTED_EXAMPLES: &[&str] = &[
+    "boot-polkadot-00",
+    "rpc-kusama-01",
+    "val-westend-04",
+    "rpc-asset-hub-polkadot-01",
+    "boot-bridge-hub-kusama-00",
+    "val-people-westend-04",
+    "rpc-asset-hub-kusama-01",
+    "val-people-westend-01",
+    "boot-paseo-00",
+];
+
+// Precomputed address for a documented example node, no arithmetic involved
+pub fn lookup_well_known(name: &str) -> Option<NodeAddress> {
+    WELL_KNOWN_ADDRESSES
+        .get(name)
+        .map(|&(port, ip)| NodeAddress {
+            port: Port(port),
+            ip: Ipv4Addr::from(ip),
+        })
+}
+
+// Source of src/generated.rs, emitted by `portgen generate-phf`
+pub fn generate_phf_source() -> Result<String, PortgenError> {
+    let mut out = String::from(
+        "// @generated by `portgen generate-phf > src/generated.rs`, do not edit by hand\n\
+         // Covers every node name from the documented examples (DOCUMENTED_EXAMPLES).\n\
+         pub static WELL_KNOWN_ADDRESSES: phf::Map<&'static str, (u16, [u8; 4])> = phf::phf_map! {\n",
+    );
+    for name in DOCUMENTED_EXAMPLES {
+        let addr = calculate_address(name)?;
+        let [a, b, c, d] = addr.ip.octets();
+        out.push_str(&format!(
+            "    \"{name}\" => ({}, [{a}, {b}, {c}, {d}]),\n",
+            addr.port
+        ));
+    }
+    out.push_str("};\n");
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fmt::Write, fs, path::Path};
+
+    // Compares with testdata/<file>, or rewrites it when UPDATE_GOLDEN is set
+    fn assert_golden(file: &str, actual: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("testdata")
+            .join(file);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            fs::write(&path, actual).unwrap();
+        }
+        let expected = fs::read_to_string(&path).unwrap();
+        assert!(
+            expected == actual,
+            "{file} changed, v1 must stay bit-for-bit"
+        );
+    }
+
+    #[test]
+    fn v1_addresses_match_golden_file() {
+        let mut out = String::new();
+        for network in Network::ALL {
+            for name in enumerate_nodes(network) {
+                let address = PORT_SCHEMA_V1.address(&name).unwrap();
+                let _ = writeln!(out, "{name} {} {}", address.port, address.ip);
+            }
+        }
+        assert_golden("schema-v1-addresses.golden", &out);
+    }
+
+    #[test]
+    fn v1_migration_matches_golden_file() {
+        let to = PortSchema {
+            port_base: 40000,
+            ..PORT_SCHEMA_V1
+        };
+        let script = migration::migration_script(Network::Polkadot, &PORT_SCHEMA_V1, &to).unwrap();
+        assert_golden("schema-v1-migration.golden", &script);
+    }
+
+    // Regenerate with `portgen generate-phf > src/generated.rs` when it fails
+    #[test]
+    fn generated_table_matches_generator() {
+        assert_eq!(generate_phf_source().unwrap(), include_str!("generated.rs"));
+    }
+
+    #[test]
+    fn cross_schema_migration_forwards_to_the_new_ip() {
+        let script =
+            migration::migration_script(Network::Polkadot, &PORT_SCHEMA_V1, &PORT_SCHEMA_V2)
+                .unwrap();
+        assert!(script.contains(
+            "iptables -t nat -A PREROUTING -d 192.168.111.10 -p tcp --dport 31001 \
+             -j DNAT --to-destination 10.10.111.100:31001\n"
+        ));
+        assert!(!script.contains("REDIRECT"));
+
+        // Nothing moves, nothing to forward
+        let script =
+            migration::migration_script(Network::Polkadot, &PORT_SCHEMA_V1, &PORT_SCHEMA_V1)
+                .unwrap();
+        assert!(!script.contains("iptables"));
+    }
+
+    #[test]
+    fn chain_ids_up_to_max_stay_within_the_network() {
+        let port = |id| {
+            PORT_SCHEMA_V1.port_of_parts(
+                Network::Polkadot,
+                ChainId(id),
+                Role::from_str("rpc", "01").unwrap(),
+            )
+        };
+        assert_eq!(port(98).unwrap(), Port(31981));
+        assert_eq!(port(99).unwrap(), Port(31991));
+        // 32001 is rpc-kusama-01's port
+        assert!(port(100).is_err());
+        assert!(ChainId::new(MAX_CHAIN_ID).is_ok());
+        assert!(ChainId::new(MAX_CHAIN_ID + 1).is_err());
+        assert!(CHAINS.iter().all(|&(id, _)| id <= MAX_CHAIN_ID));
+    }
+
+    #[test]
+    fn chain_names_normalize_to_the_first_listed_name() {
+        assert_eq!(normalize_chain_name("statemint"), Some("asset-hub"));
+        assert_eq!(normalize_chain_name("asset-hub"), Some("asset-hub"));
+        assert_eq!(normalize_chain_name("unknown"), None);
+
+        let node = NodeName::parse("rpc-statemint-polkadot-01").unwrap();
+        assert_eq!(node.chain.as_deref(), Some("asset-hub"));
+        assert_eq!(node.written_chain.as_deref(), Some("statemint"));
+        assert!(Deprecation::of_node("rpc-statemint-polkadot-01").is_some());
+        // Unregistered chains stay as written, for the error further on
+        let node = NodeName::parse("rpc-nowhere-polkadot-01").unwrap();
+        assert_eq!(node.chain.as_deref(), Some("nowhere"));
+    }
+
+    #[test]
+    fn no_two_valid_names_share_an_address() {
+        let mut seen = std::collections::HashMap::new();
+        for network in Network::ALL {
+            for chain in enumerate_chains() {
+                for role in roles::table() {
+                    // Bootnodes 00-09 are one node by design
+                    let first = *role.instances.start();
+                    let instances = if role.instance_in_port {
+                        role.instances.clone()
+                    } else {
+                        first..=first
+                    };
+                    for instance in instances {
+                        let name = compose_name(role.abbrev, chain, network, instance);
+                        let Ok(address) = calculate_address(&name) else {
+                            continue;
+                        };
+                        if let Some(other) = seen.insert(address.to_string(), name.clone()) {
+                            panic!("{name} and {other} are both {address}");
+                        }
+                        assert_eq!(reverse_lookup(address.ip).unwrap(), name);
+                    }
+                }
+            }
+        }
+        assert!(seen.keys().any(|address| address == "192.168.11.11:31014"));
+    }
+
+    #[test]
+    fn every_node_falls_inside_its_ranges() {
+        assert_eq!(
+            port_range_for_network(Network::Polkadot).unwrap(),
+            31000..=31999
+        );
+        let high = PortSchema {
+            port_base: 64000,
+            ..PORT_SCHEMA_V1
+        };
+        assert!(high.port_range(Network::Polkadot).is_err());
+        let rpc = RoleConstraints::get("rpc").unwrap();
+        assert_eq!(
+            ip_range_for_network_role(Network::Kusama, rpc),
+            (
+                Ipv4Addr::new(192, 168, 121, 10),
+                Ipv4Addr::new(192, 168, 123, 109)
+            )
+        );
+        assert_eq!(
+            ip_range_for_chain(Network::Polkadot, ChainId(1)),
+            Ipv4Addr::new(192, 168, 10, 11)
+        );
+
+        for network in Network::ALL {
+            for name in enumerate_nodes(network) {
+                let node = NodeName::parse(&name).unwrap();
+                let address = calculate_address(&name).unwrap();
+                let role = RoleConstraints::get(&node.role).unwrap();
+                let (first, last) = ip_range_for_network_role(network, role);
+                assert!(port_range_for_network(network)
+                    .unwrap()
+                    .contains(&address.port.0));
+                assert!((first..=last).contains(&address.ip), "{name}");
+                let chain = ChainId::from_str(node.chain.as_deref()).unwrap();
+                assert_eq!(
+                    ip_range_for_chain(network, chain).octets()[3],
+                    address.ip.octets()[3]
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,192 @@
+// Lint rules for a directory of `<node-name>.yaml` files, over the whole tree
+// or only the files staged in git
+use crate::{
+    atomic::write_atomic,
+    calculate_address,
+    denylist::{self, DenyEntry},
+    git_hook, para_ids,
+    reservations::{self, Reservation},
+    Deprecation, NodeInfo, PortgenError,
+};
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub path: PathBuf,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}: {severity}: {}", self.path.display(), self.message)
+    }
+}
+
+pub struct LintOptions<'a> {
+    pub reservations: &'a [Reservation],
+    // Reservations held by this user are not reported
+    pub user: &'a str,
+    pub validate_para_id: bool,
+    // Reported whether or not --ignore-denylist is given
+    pub denylist: &'a [DenyEntry],
+}
+
+// Every `.yaml` file directly in `dir`, sorted
+pub fn node_files(dir: &Path) -> Result<Vec<PathBuf>, PortgenError> {
+    let entries = fs::read_dir(dir).map_err(|_| "failed to read node directory")?;
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "yaml"))
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+fn node_name(path: &Path) -> Option<&str> {
+    path.file_stem().and_then(|stem| stem.to_str())
+}
+
+// Lints `targets`; collisions are checked against all of `tree`, so a new
+// file can't take the address of one that is already there
+pub fn lint(tree: &[PathBuf], targets: &[PathBuf], options: &LintOptions) -> Vec<Finding> {
+    let mut by_address: BTreeMap<String, Vec<&PathBuf>> = BTreeMap::new();
+    for path in tree.iter().chain(targets) {
+        if let Some(addr) = node_name(path).and_then(|name| calculate_address(name).ok()) {
+            let paths = by_address.entry(addr.to_string()).or_default();
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+
+    let mut findings = Vec::new();
+    let mut report = |path: &PathBuf, severity, message: String| {
+        findings.push(Finding {
+            path: path.clone(),
+            severity,
+            message,
+        })
+    };
+    for path in targets {
+        let Some(name) = node_name(path) else {
+            report(path, Severity::Error, "file name is not UTF-8".to_string());
+            continue;
+        };
+        let addr = match calculate_address(name) {
+            Ok(addr) => addr,
+            Err(e) => {
+                report(path, Severity::Error, e.to_string());
+                continue;
+            }
+        };
+
+        let others: Vec<String> = by_address[&addr.to_string()]
+            .iter()
+            .filter(|other| **other != path)
+            .map(|other| other.display().to_string())
+            .collect();
+        if !others.is_empty() {
+            let message = format!("{addr} is also used by {}", others.join(", "));
+            report(path, Severity::Error, message);
+        }
+
+        if let Ok(node) = NodeInfo::new(name.to_string()) {
+            for hit in denylist::hits(options.denylist, &node).unwrap_or_default() {
+                report(path, Severity::Error, hit.to_string());
+            }
+        }
+
+        if let Ok(Some(reservation)) =
+            reservations::held_by_other(options.reservations, name, options.user)
+        {
+            report(path, Severity::Warning, reservation.to_string());
+        }
+        if options.validate_para_id {
+            if let Ok(Some(warning)) = para_ids::check(name) {
+                report(path, Severity::Warning, warning);
+            }
+        }
+        if let Some(deprecation) = Deprecation::of_node(name) {
+            report(path, Severity::Warning, deprecation.to_string());
+        }
+    }
+    findings
+}
+
+fn git(args: &[&str]) -> Result<String, PortgenError> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .map_err(|_| "failed to run git")?;
+    if !output.status.success() {
+        return Err("git failed (not inside a repository?)".into());
+    }
+    String::from_utf8(output.stdout).map_err(|_| "git printed non-UTF-8 paths".into())
+}
+
+// Node files staged for commit (added, copied, modified or renamed) in `dir`,
+// spelled like node_files() spells them
+pub fn staged_files(dir: &Path) -> Result<Vec<PathBuf>, PortgenError> {
+    let root = PathBuf::from(git(&["rev-parse", "--show-toplevel"])?.trim_end());
+    let canonical_dir = fs::canonicalize(dir).map_err(|_| "failed to read node directory")?;
+    let staged = git(&["diff", "--cached", "--name-only", "--diff-filter=ACMR"])?;
+    let mut paths: Vec<PathBuf> = staged
+        .lines()
+        .map(|line| root.join(line))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "yaml"))
+        .filter(|path| path.parent().is_some_and(|parent| parent == canonical_dir))
+        .filter_map(|path| Some(dir.join(path.file_name()?)))
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+// Writes `.git/hooks/pre-commit`, the `git-hook` script running the staged
+// lint on `dir` as well. An existing hook is only replaced when portgen wrote it.
+pub fn install_hook(dir: &Path) -> Result<PathBuf, PortgenError> {
+    // Hooks run from the repository root
+    let root = PathBuf::from(git(&["rev-parse", "--show-toplevel"])?.trim_end());
+    let canonical_dir = fs::canonicalize(dir).map_err(|_| "failed to read node directory")?;
+    let dir = canonical_dir
+        .strip_prefix(&root)
+        .map_err(|_| "node directory is outside the repository")?;
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let hooks = PathBuf::from(git(&["rev-parse", "--git-path", "hooks"])?.trim_end());
+    let hook = hooks.join("pre-commit");
+    if let Ok(existing) = fs::read_to_string(&hook) {
+        if !git_hook::is_portgen_hook(&existing) {
+            return Err("a pre-commit hook exists already, not replacing it".into());
+        }
+    }
+
+    let script = git_hook::script(Some(&dir.to_string_lossy()));
+    fs::create_dir_all(&hooks).map_err(|_| "failed to create hooks directory")?;
+    write_atomic(&hook, &script).map_err(|_| "failed to write pre-commit hook")?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&hook, fs::Permissions::from_mode(0o755))
+            .map_err(|_| "failed to make the hook executable")?;
+    }
+    Ok(hook)
+}
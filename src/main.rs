@@ -1,30 +1,81 @@
-use clap::Parser;
-use std::{fmt, net::Ipv4Addr, str::FromStr};
+mod table;
 
-const PORT_BASE: u16 = 30000;
+use clap::{
+    builder::{BoolishValueParser, Resettable, TypedValueParser},
+    parser::ValueSource,
+    Args as ClapArgs, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum,
+};
+use portgen::{
+    annotate,
+    apply::{self, KeyMapping},
+    atomic::write_output,
+    audit, aws, batch,
+    bind::{bind_check, BindStatus},
+    blackbox, calculate_address, calculate_port_with_base, canonical_name, chains,
+    config::{self, Setting, Source},
+    consistency, cross_chain_pairs,
+    denylist::{self, DenyEntry},
+    dns, docker, endpoints, enumerate_chains,
+    env_tree::{self, Change},
+    firewall, generate_phf_source, git_hook, grafana, health,
+    hosts::{self, SyncOutcome},
+    identify_schema, inventory, iproute, k8s, keepalived, lint,
+    migration::migration_script,
+    nats, netplan, nomad, normalize, occupancy, opa, para_ids, pcap,
+    pjs::{self, Verdict},
+    quadlet,
+    ranges::{self, Cidr},
+    redis, registry, releases, remote_audit, render, report, reservations, reserved, roles,
+    rpc_proxy, scaffold, schema_diff, script_args,
+    selection::{NameFilter, Selection},
+    selftest, selinux, services,
+    ssh::{self, SshOptions},
+    subnet,
+    suggest::suggest,
+    summary::Summary,
+    supervisor,
+    synonyms::{RoleSynonym, Synonyms},
+    systemd, tailscale, time, topology, verify, ChainId, ChainType, Deprecation, Network, NodeInfo,
+    NodeName, PortKind, PortSchema, PortgenError, Role, RoleConstraints, PORT_BASE, PORT_SCHEMA_V1,
+    PORT_SCHEMA_V2,
+};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufRead, IsTerminal, Write},
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 #[derive(Parser)]
 #[command(
     name = "portgen",
     about = "Generate port numbers and IP addresses for substrate nodes",
-    version = "0.6.2"
+    version = "0.6.2",
+    subcommand_negates_reqs = true
 )]
 #[command(after_help = "\
 Examples:
   # Relay chain nodes
-  portgen boot-polkadot-00           # Bootnode (31000, 192.168.11.10)
+  portgen boot-polkadot-00           # Bootnode (31000, 192.168.10.10)
   portgen rpc-kusama-01              # RPC node (32001, 192.168.121.10)
-  portgen val-westend-04             # Validator (33004, 192.168.234.10)
+  portgen val-westend-04             # Validator (33007, 192.168.234.10)
 
   # System parachain nodes
   portgen rpc-asset-hub-polkadot-01  # Asset Hub RPC (31011, 192.168.111.11)
   portgen boot-bridge-hub-kusama-00  # Bridge Hub boot (32020, 192.168.20.12)
-  portgen val-people-westend-04      # People chain validator (33044, 192.168.234.14)
+  portgen val-people-westend-04      # People chain validator (33047, 192.168.234.14)
 
-Supported roles:
-  - boot: bootnode (instance 00)
-  - rpc:  RPC node (instances 01-03)
-  - val:  validator node (instances 04-09)
+Roles: see `portgen list roles`
+
+Environment:
+  Every flag can also be set as PORTGEN_<FLAG>, or PORTGEN_<SUBCOMMAND>_<FLAG> for the flags
+  of a subcommand; a flag on the command line wins. Each flag's variable is listed with it.
+  PORTGEN_FORMAT=json                   --format json
+  PORTGEN_CONFIG=/etc/portgen/config.toml
+  PORTGEN_BIND_CHECK_PORT_BASE=40000    bind-check --port-base 40000
+  PORTGEN_VERBOSE=true                  --verbose (switches take true or false)
 
 Format: {role}-{chain}-{network}-{instance}
 Port:   3NCCI (N=network, CC=chain, I=instance)
@@ -33,215 +84,3236 @@ IP:     192.168.{RNI}.{chain_id+10}
         N: network (1=polkadot, 2=kusama, 3=westend, 4=paseo)
         I: instance number")]
 struct Args {
-    /// Node name (e.g., rpc-asset-hub-polkadot-01)
-    node_name: String,
+    /// Node names (e.g., rpc-asset-hub-polkadot-01), `-` reads names from stdin
+    #[arg(required_unless_present = "file")]
+    node_names: Vec<String>,
+
+    /// Inventory file: a YAML list of nodes with metadata, or one name per line
+    #[arg(long)]
+    file: Option<PathBuf>,
+
+    /// Comma-separated fields to print tab-separated instead of ip:port
+    #[arg(long, value_enum, value_delimiter = ',', conflicts_with = "output")]
+    emit: Vec<Field>,
+
+    /// Terminate output records with NUL instead of newline
+    #[arg(short = '0', long, conflicts_with = "output")]
+    null: bool,
+
+    /// Port/IP formula version to compute addresses with
+    #[arg(
+        long,
+        global = true,
+        alias = "scheme-version",
+        value_enum,
+        default_value_t = SchemaVersion::V1
+    )]
+    schema: SchemaVersion,
+
+    /// Output layout, json and yaml carry inventory metadata along
+    #[arg(long = "format", alias = "output", value_enum, default_value_t = Output::Plain)]
+    output: Output,
+
+    /// Accept names with their tokens in any order (see `normalize`)
+    #[arg(long)]
+    lenient: bool,
+
+    /// Skip invalid names instead of stopping at the first one (exits 1 if any failed)
+    #[arg(long)]
+    continue_on_error: bool,
+
+    /// Print a succeeded/failed/collisions summary to stderr, and into json/ndjson output
+    #[arg(long)]
+    summary: bool,
+
+    /// Sort multi-node results
+    #[arg(long, value_enum)]
+    sort: Option<SortKey>,
+
+    /// Worker threads for streamed stdin batches (default: available cores)
+    #[arg(long)]
+    jobs: Option<usize>,
+
+    /// Node binary for generated process definitions
+    #[arg(long, default_value = "/usr/local/bin/polkadot")]
+    binary: String,
+
+    /// Container image for generated manifests
+    #[arg(long, default_value = "parity/polkadot:latest")]
+    image: String,
+
+    #[command(flatten)]
+    nomad: NomadArgs,
+
+    #[command(flatten)]
+    ingress: IngressArgs,
+
+    #[command(flatten)]
+    netplan: NetplanArgs,
+
+    #[command(flatten)]
+    rpc_proxy: RpcProxyArgs,
+
+    /// Existing Docker bridge network to attach containers to, at their generated IP
+    #[arg(long)]
+    docker_network: Option<String>,
+
+    /// Subnet of --docker-network
+    #[arg(long, default_value = "192.168.0.0/16", requires = "docker_network")]
+    docker_subnet: Cidr,
+
+    /// Tailscale tags allowed to reach the nodes (default: tag:substrate-<role>)
+    #[arg(long, value_delimiter = ',')]
+    allow_tags: Vec<String>,
+
+    /// Draw table borders even when stdout is not a terminal
+    #[arg(long)]
+    fancy: bool,
+
+    /// Warn when a chain is used with a relay network it has no para ID on
+    #[arg(long, global = true)]
+    validate_para_id: bool,
+
+    /// Append every computed address to this JSONL audit log
+    #[arg(long, global = true)]
+    audit_log: Option<PathBuf>,
+
+    /// Reservation state file used by reserve, release and the conflict warnings
+    #[arg(long, global = true, default_value = "portgen-reservations.json")]
+    reservations: PathBuf,
+
+    /// Print nothing, report only through the exit status
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Explain how each address was derived (on stderr)
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    /// Do not warn about deprecated chain names
+    #[arg(long, global = true)]
+    no_deprecation_warnings: bool,
+
+    /// Extra role synonyms as SYNONYM=ROLE, comma separated (validator=val and others are built in)
+    #[arg(long = "role-synonym", global = true, value_delimiter = ',')]
+    role_synonyms: Vec<RoleSynonym>,
+
+    /// Accept role tokens only, no synonyms
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// Ports, port ranges, IPs or `.HOST` octets nodes must not land on, as ENTRY=REASON, comma separated
+    #[arg(long = "deny", global = true, value_delimiter = ',')]
+    denylist: Vec<DenyEntry>,
+
+    /// Hand out addresses on the denylist anyway
+    #[arg(long, global = true)]
+    ignore_denylist: bool,
+
+    /// Config file, layered over $XDG_CONFIG_HOME/portgen/config.toml
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Write the output atomically to this file instead of stdout, untouched when unchanged
+    #[arg(long = "out", global = true, value_name = "PATH")]
+    out_file: Option<PathBuf>,
+
+    /// Keep the previous contents of every replaced file as `<file>.bak`
+    #[arg(long, global = true)]
+    backup: bool,
+
+    // Effective value and source of every config key, filled in by main
+    #[arg(skip)]
+    settings: Vec<Setting>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-#[derive(Debug, Clone, Copy)]
-struct Port(u16);
+// Help headings of the flattened argument groups, set per field: a heading
+// set on the struct would carry over to every flag declared after it
+const NOMAD_HEADING: &str = "Nomad output";
 
-impl fmt::Display for Port {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
-    }
+#[derive(ClapArgs)]
+struct NomadArgs {
+    /// Datacenters the job may run in
+    #[arg(long, value_delimiter = ',', default_value = "dc1", help_heading = NOMAD_HEADING)]
+    datacenters: Vec<String>,
+    #[arg(long, default_value = "global", help_heading = NOMAD_HEADING)]
+    region: String,
+    #[arg(long, default_value = "default", help_heading = NOMAD_HEADING)]
+    namespace: String,
+    /// Nomad job type
+    #[arg(long, default_value = "service", help_heading = NOMAD_HEADING)]
+    job_type: String,
 }
 
-#[derive(Debug, Clone, Copy)]
-struct NodeAddress {
-    port: Port,
-    ip: Ipv4Addr,
+const INGRESS_HEADING: &str = "Kubernetes ingress output";
+
+#[derive(ClapArgs)]
+struct IngressArgs {
+    /// Domain the ingress hosts `<node-name>.<domain>` live under
+    #[arg(long, help_heading = INGRESS_HEADING)]
+    dns_domain: Option<String>,
+    /// ingressClassName of the Ingress
+    #[arg(long, help_heading = INGRESS_HEADING)]
+    ingress_class: Option<String>,
+    /// Secret holding the TLS certificate for the host
+    #[arg(long, help_heading = INGRESS_HEADING)]
+    tls_secret: Option<String>,
 }
 
-impl fmt::Display for NodeAddress {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}:{}", self.ip, self.port)
-    }
+const NETPLAN_HEADING: &str = "Netplan output";
+
+#[derive(ClapArgs)]
+struct NetplanArgs {
+    /// Interface the addresses are assigned to
+    #[arg(long, default_value = "eth0", help_heading = NETPLAN_HEADING)]
+    interface: String,
+    /// Prefix length of the addresses
+    #[arg(long, default_value_t = iproute::PREFIX, value_parser = clap::value_parser!(u8).range(1..=32), help_heading = NETPLAN_HEADING)]
+    prefix: u8,
 }
 
-#[derive(Debug, Clone, Copy)]
-enum Network {
-    Polkadot = 1,
-    Kusama = 2,
-    Westend = 3,
-    Paseo = 4,
+const RPC_PROXY_HEADING: &str = "RPC proxy output";
+
+#[derive(ClapArgs)]
+struct RpcProxyArgs {
+    /// Most requests the proxy accepts in one batch
+    #[arg(long, default_value_t = rpc_proxy::MAX_BATCH_SIZE, help_heading = RPC_PROXY_HEADING)]
+    max_batch_size: u32,
+    /// Milliseconds the proxy waits for the upstream node
+    #[arg(long, default_value_t = rpc_proxy::TIMEOUT_MS, help_heading = RPC_PROXY_HEADING)]
+    timeout_ms: u64,
 }
 
-#[derive(Debug, Clone, Copy)]
-struct ChainId(u16);
+#[derive(Subcommand)]
+enum Command {
+    /// List relay validator / parachain node pairs that talk to each other
+    CrossChainPorts {
+        /// Relay chain network (polkadot, kusama, westend, paseo)
+        #[arg(long)]
+        relay: Network,
+        /// Parachain name (e.g., asset-hub)
+        #[arg(long)]
+        para: String,
+        /// Parachain side role (col or val)
+        #[arg(long, default_value = "col")]
+        role: String,
+    },
+    /// List the relay chain RPC endpoints of a network for light clients
+    RelayEndpoints {
+        #[arg(long)]
+        network: Network,
+        #[arg(long, value_enum, default_value_t = EndpointFormat::Json)]
+        format: EndpointFormat,
+    },
+    /// keepalived virtual_server for the VIP in front of a chain's RPC pool
+    Keepalived {
+        /// Chain of the pool (`relay` for the relay chain)
+        #[arg(long)]
+        chain: String,
+        #[arg(long)]
+        network: Network,
+        /// Virtual IP the pool is reached on
+        #[arg(long)]
+        vip: Ipv4Addr,
+        /// Port clients connect to on the VIP
+        #[arg(long, default_value_t = 9944)]
+        vip_port: u16,
+        /// Also emit a vrrp_instance with this virtual_router_id
+        #[arg(long, value_parser = clap::value_parser!(u8).range(1..))]
+        vrid: Option<u8>,
+        /// Interface the vrrp_instance runs on
+        #[arg(long, default_value = "eth0", requires = "vrid")]
+        interface: String,
+    },
+    /// List parts of the scheme
+    List {
+        #[command(subcommand)]
+        what: ListWhat,
+    },
+    /// Show which nodes of a network change port or IP between two schemas
+    SchemaDiff {
+        #[arg(value_enum)]
+        from: SchemaVersion,
+        #[arg(value_enum)]
+        to: SchemaVersion,
+        #[arg(long)]
+        network: Network,
+    },
+    /// Check the examples of --help against the formula
+    SelfTest,
+    /// Check that each node computes to the same address twice and decodes back
+    ConsistencyCheck {
+        /// File with one node name per line, `-` reads stdin
+        node_names_file: PathBuf,
+    },
+    /// List the nodes whose address changes between two portgen releases
+    CheckVersionCompat {
+        /// Release the nodes were deployed with, X.Y.Z
+        #[arg(long)]
+        old_version: releases::Version,
+        /// Release being upgraded to, X.Y.Z (default: this one)
+        #[arg(long, default_value = releases::CURRENT)]
+        new_version: releases::Version,
+        /// File with one node name per line, `-` reads stdin
+        node_names_file: PathBuf,
+    },
+    /// Print the id a new chain registered without one would get
+    SuggestChainId { name: String },
+    /// Print the node an IP belongs to and the schema version it comes from
+    Reverse { ip: Ipv4Addr },
+    /// Print the canonical form of names whose tokens are out of order
+    Normalize {
+        /// Node names such as polkadot-rpc-asset-hub-01, `-` reads names from stdin
+        #[arg(required = true)]
+        node_names: Vec<String>,
+    },
+    /// Copy stdin to stdout with node addresses annotated with their node names
+    Annotate {
+        /// Replacement for each address, `{name}` and `{addr}` as it was written
+        #[arg(long, default_value = annotate::DEFAULT_TEMPLATE)]
+        template: String,
+        /// Replace addresses by node names instead of annotating them
+        #[arg(long, conflicts_with = "template")]
+        names_only: bool,
+    },
+    /// Print the valid next tokens for a partial node name, one per line
+    Suggest {
+        #[arg(default_value = "")]
+        partial: String,
+    },
+    /// Inspect the --audit-log file
+    AuditLog {
+        #[command(subcommand)]
+        action: AuditAction,
+    },
+    /// Inspect the layered configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Print the path of the config file in use, whether it exists or not
+    ConfigPath,
+    /// Export the chain registry, or compare it with another team's export
+    Registry {
+        #[command(subcommand)]
+        action: RegistryAction,
+    },
+    /// Create a node's YAML file with its computed address filled in
+    New {
+        node_name: String,
+        /// Directory the file is written to
+        #[arg(long, default_value = ".")]
+        out_dir: PathBuf,
+        /// minijinja template instead of the built-in skeleton, sees the same variables as `render`
+        #[arg(long)]
+        template: Option<PathBuf>,
+        /// Overwrite an existing file, and create nodes reserved by someone else
+        #[arg(long)]
+        force: bool,
+    },
+    /// Bring the address fields of existing node files in line with their names
+    Apply {
+        /// Directory of <node-name>.yaml files
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+        /// [FIELD=]KEY.PATH pairs; fields: ip, port, rpc_port, ws_port, prometheus_port, address, multiaddr
+        #[arg(long, value_delimiter = ',', default_value = "ip=ip,port=port")]
+        keys: Vec<KeyMapping>,
+        /// Print a unified diff per file instead of writing
+        #[arg(long)]
+        dry_run: bool,
+        /// Write nothing, fail if any file would change or misses a key
+        #[arg(long, conflicts_with = "dry_run")]
+        check: bool,
+        #[command(flatten)]
+        names: NameFilterArgs,
+    },
+    /// Compare the address fields of node files with their names, writing nothing
+    VerifyYaml {
+        /// Directory of <node-name>.yaml files
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+        /// [FIELD=]KEY.PATH pairs, a bare path names the field by its last key
+        #[arg(long, value_delimiter = ',', default_value = "ip,port")]
+        keys: Vec<KeyMapping>,
+        /// Only print files with problems
+        #[arg(long)]
+        problems_only: bool,
+        #[command(flatten)]
+        names: NameFilterArgs,
+    },
+    /// Reserve a node slot before its node file exists, or quarantine it
+    Reserve {
+        node_name: String,
+        /// Why the slot is held
+        #[arg(long, default_value = "")]
+        reason: String,
+        /// Holder of the reservation (default: $USER)
+        #[arg(long)]
+        by: Option<String>,
+    },
+    /// Release a reservation
+    Release {
+        node_name: String,
+        /// Release a reservation held by someone else
+        #[arg(long)]
+        force: bool,
+    },
+    /// List current reservations with their age
+    Reservations,
+    /// Print the first slot of a role that is neither deployed nor reserved
+    Next {
+        role: String,
+        #[arg(long)]
+        network: Network,
+        /// Chain of the slot (default: the relay chain)
+        #[arg(long, default_value = "relay")]
+        chain: String,
+        /// Directory of the deployed nodes' <node-name>.yaml files
+        #[arg(long)]
+        deployed: Option<PathBuf>,
+    },
+    /// List the known para IDs of a relay network
+    ParaIds {
+        #[arg(long)]
+        network: Network,
+    },
+    /// Lint a directory of node files: names, address collisions, reservations
+    Lint {
+        /// Directory of <node-name>.yaml files
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+        /// Only lint files staged in git, still checking collisions against the whole tree
+        #[arg(long)]
+        staged: bool,
+        /// Write a git pre-commit hook running `lint --staged` on the directory
+        #[arg(long, conflicts_with = "staged")]
+        install_hook: bool,
+        #[command(flatten)]
+        names: NameFilterArgs,
+    },
+    /// Print a pre-commit hook validating node names in *.yaml and nodes.txt files
+    GitHook {
+        /// Also run `lint --staged` on this node directory, relative to the repository root
+        #[arg(long, value_name = "DIR")]
+        lint: Option<String>,
+    },
+    /// Validate node names without printing addresses
+    Check {
+        /// Node names, `-` reads names from stdin
+        #[arg(required = true)]
+        node_names: Vec<String>,
+    },
+    /// Check whether a node's port can be bound on this host
+    BindCheck {
+        node_name: String,
+        /// Also try the node's generated IP, if it is assigned locally
+        #[arg(long)]
+        ip: bool,
+        /// Port base to compute the port from
+        #[arg(long, default_value_t = PORT_BASE)]
+        port_base: u16,
+        /// Check the rpc, ws and prometheus ports as well as p2p
+        #[arg(long)]
+        all_kinds: bool,
+    },
+    /// Generate an iptables script forwarding old node addresses to new ones, across port bases
+    /// and schema versions
+    MigrationScript {
+        #[arg(long)]
+        from_base: u16,
+        #[arg(long)]
+        to_base: u16,
+        #[arg(long)]
+        network: Network,
+        /// Schema the nodes are on now
+        #[arg(long, value_enum, default_value_t = SchemaVersion::V1)]
+        from_schema: SchemaVersion,
+        /// Schema the nodes move to
+        #[arg(long, value_enum, default_value_t = SchemaVersion::V1)]
+        to_schema: SchemaVersion,
+    },
+    /// Print every node of a network with its peer connections
+    FullTopology {
+        #[arg(long)]
+        network: Network,
+        #[arg(long, value_enum, default_value_t = TopologyFormat::Text)]
+        format: TopologyFormat,
+    },
+    /// Expand a role-level firewall policy into per-address allow rules
+    FirewallMatrix {
+        /// Policy file with `[cidrs]` and `[[allow]]` entries
+        #[arg(long)]
+        policy: PathBuf,
+        #[arg(long, value_enum, default_value_t = FirewallFormat::Nftables)]
+        format: FirewallFormat,
+        #[command(flatten)]
+        selection: SelectionArgs,
+    },
+    /// Query system_health and system_syncState on RPC nodes
+    Health {
+        /// RPC node names; without any, every rpc node matching the filters
+        #[arg(conflicts_with_all = ["only", "exclude"])]
+        node_names: Vec<String>,
+        /// Check nodes of other roles too
+        #[arg(long)]
+        force: bool,
+        /// Print a JSON array of reports
+        #[arg(long)]
+        json: bool,
+        /// Seconds to wait for each connection and response
+        #[arg(long, default_value_t = 5)]
+        timeout: u64,
+        /// Checks running at the same time
+        #[arg(long, default_value_t = 8)]
+        jobs: usize,
+        /// Only nodes on this network
+        #[arg(long, conflicts_with = "node_names")]
+        network: Option<Network>,
+        /// Only nodes of this chain (`relay` for the relay chain)
+        #[arg(long, conflicts_with = "node_names")]
+        chain: Option<String>,
+        #[command(flatten)]
+        names: NameFilterArgs,
+    },
+    /// Probe generated IPs for hosts already answering there before nodes are brought up
+    Occupancy {
+        /// Node names, `-` reads names from stdin; without any, every node matching the filters
+        #[arg(
+            required_unless_present_any = ["network", "chain", "role", "only", "exclude"],
+            conflicts_with_all = ["only", "exclude"]
+        )]
+        node_names: Vec<String>,
+        /// Directory of the deployed nodes' <node-name>.yaml files; responders without one are unexpected
+        #[arg(long)]
+        deployed: Option<PathBuf>,
+        /// TCP ports probed besides the node's p2p port, comma separated
+        #[arg(long, value_delimiter = ',', default_value = "22,80,443")]
+        ports: Vec<u16>,
+        /// Skip the ICMP echo, for hosts where ping can't send one
+        #[arg(long)]
+        tcp_only: bool,
+        /// Print a JSON array of reports
+        #[arg(long)]
+        json: bool,
+        /// Seconds to wait for each echo reply and connection
+        #[arg(long, default_value_t = 2)]
+        timeout: u64,
+        /// Addresses probed at the same time
+        #[arg(long, default_value_t = 16)]
+        jobs: usize,
+        /// Only nodes on this network
+        #[arg(long, conflicts_with = "node_names")]
+        network: Option<Network>,
+        /// Only nodes of this chain (`relay` for the relay chain)
+        #[arg(long, conflicts_with = "node_names")]
+        chain: Option<String>,
+        /// Only nodes with this role
+        #[arg(long, conflicts_with = "node_names")]
+        role: Option<String>,
+        #[command(flatten)]
+        names: NameFilterArgs,
+    },
+    /// Compare the listening sockets of nodes, over ssh, with their portgen ports
+    RemoteAudit {
+        /// Remote user
+        #[arg(long, short = 'l')]
+        user: Option<String>,
+        /// Private key passed to ssh -i
+        #[arg(long, short = 'i')]
+        identity_file: Option<PathBuf>,
+        /// Jump host passed to ssh -J
+        #[arg(long, short = 'J')]
+        jump: Option<String>,
+        /// Remote command printing `ss -ltn`-style output
+        #[arg(long, default_value = remote_audit::DEFAULT_COMMAND)]
+        command: String,
+        /// Print a JSON array of reports
+        #[arg(long)]
+        json: bool,
+        /// Seconds ssh waits for each connection
+        #[arg(long, default_value_t = 10)]
+        timeout: u64,
+        /// Hosts audited at the same time
+        #[arg(long, default_value_t = 8)]
+        jobs: usize,
+        #[command(flatten)]
+        selection: SelectionArgs,
+    },
+    /// Print the ip addr/link/route commands that put nodes' addresses on an interface
+    IpCmds {
+        /// Node names, `-` reads names from stdin
+        #[arg(required = true)]
+        node_names: Vec<String>,
+        /// Interface the addresses go on
+        #[arg(long)]
+        interface: String,
+        /// Also set the link up (down with --delete)
+        #[arg(long)]
+        link: bool,
+        /// Add a default route via the first host of the node's /24
+        #[arg(long)]
+        default_route: bool,
+        /// Default route via this gateway instead
+        #[arg(long)]
+        gateway: Option<Ipv4Addr>,
+        /// Print the teardown commands instead
+        #[arg(long)]
+        delete: bool,
+    },
+    /// Print a tcpdump capture filter (or Wireshark display filter) for nodes' traffic
+    PcapFilter {
+        /// Node names, `-` reads names from stdin; without any, every node matching the filters
+        #[arg(
+            required_unless_present_any = ["network", "chain", "role", "only", "exclude"],
+            conflicts_with_all = ["only", "exclude"]
+        )]
+        node_names: Vec<String>,
+        /// Port kind to match (p2p, rpc, ws, prometheus), or `all`
+        #[arg(long, default_value = "p2p")]
+        kind: pcap::KindFilter,
+        /// Print a Wireshark display filter instead
+        #[arg(long)]
+        wireshark: bool,
+        /// Only nodes on this network
+        #[arg(long, conflicts_with = "node_names")]
+        network: Option<Network>,
+        /// Only nodes of this chain (`relay` for the relay chain)
+        #[arg(long, conflicts_with = "node_names")]
+        chain: Option<String>,
+        /// Only nodes with this role
+        #[arg(long, conflicts_with = "node_names")]
+        role: Option<String>,
+        #[command(flatten)]
+        names: NameFilterArgs,
+    },
+    /// Print the semanage commands that label nodes' ports for SELinux
+    Selinux {
+        /// Node names, `-` reads names from stdin; without any, every node matching the filters
+        #[arg(
+            required_unless_present_any = ["network", "chain", "role", "only", "exclude"],
+            conflicts_with_all = ["only", "exclude"]
+        )]
+        node_names: Vec<String>,
+        /// Port kind to label (p2p, rpc, ws, prometheus), or `all`
+        #[arg(long, default_value = "all")]
+        kind: pcap::KindFilter,
+        /// Port type of roles without a --role-type
+        #[arg(long = "type", default_value = selinux::DEFAULT_TYPE)]
+        port_type: String,
+        /// Port type of one role, as ROLE=TYPE (repeatable)
+        #[arg(long = "role-type", value_delimiter = ',')]
+        role_types: Vec<selinux::RoleType>,
+        /// Modify existing records (`-m`) instead of adding them
+        #[arg(long, conflicts_with = "check")]
+        modify: bool,
+        /// Print `semanage port -l` greps verifying the records instead
+        #[arg(long)]
+        check: bool,
+        /// Only nodes on this network
+        #[arg(long, conflicts_with = "node_names")]
+        network: Option<Network>,
+        /// Only nodes of this chain (`relay` for the relay chain)
+        #[arg(long, conflicts_with = "node_names")]
+        chain: Option<String>,
+        /// Only nodes with this role
+        #[arg(long, conflicts_with = "node_names")]
+        role: Option<String>,
+        #[command(flatten)]
+        names: NameFilterArgs,
+    },
+    /// Compare published A (and PTR) records with the generated addresses
+    DnsCheck {
+        /// Domain the node names live under
+        #[arg(long)]
+        domain: String,
+        /// Also check the reverse record of each generated IP
+        #[arg(long)]
+        ptr: bool,
+        /// DNS server to query (default: first nameserver of /etc/resolv.conf)
+        #[arg(long)]
+        resolver: Option<SocketAddr>,
+        /// Lookups running at the same time
+        #[arg(long, default_value_t = 8)]
+        jobs: usize,
+        #[command(flatten)]
+        selection: SelectionArgs,
+    },
+    /// Aggregate the selected nodes into port ranges and CIDR blocks
+    Ranges {
+        /// Which port of each node to aggregate
+        #[arg(long, default_value = "p2p")]
+        kind: PortKind,
+        #[arg(long, value_enum, default_value_t = RangesFormat::Text)]
+        format: RangesFormat,
+        #[command(flatten)]
+        selection: SelectionArgs,
+    },
+    /// Print the /24 blocks each role and network occupies, aggregated, and the unused rest
+    SubnetPlan {
+        /// Supernet the plan lives in (default: the schema's /16)
+        #[arg(long)]
+        supernet: Option<Cidr>,
+        #[arg(long, value_enum, default_value_t = TopologyFormat::Text)]
+        format: TopologyFormat,
+    },
+    /// Print AWS security group ingress rules for the selected nodes' ports
+    AwsSg {
+        /// Port kinds to open (p2p, rpc, ws, prometheus, all), comma separated
+        #[arg(long, value_delimiter = ',', default_value = "p2p,rpc")]
+        kind: Vec<pcap::KindFilter>,
+        /// Terraform resources or `aws ec2 authorize-security-group-ingress` JSON, one per line
+        #[arg(long, value_enum, default_value_t = AwsSgFormat::Tf)]
+        format: AwsSgFormat,
+        /// Network the rules allow traffic from
+        #[arg(long)]
+        source_cidr: Cidr,
+        /// Security group id (default for tf: var.security_group_id)
+        #[arg(long)]
+        security_group: Option<String>,
+        /// Warn when the rules exceed this many, the group's inbound rule quota
+        #[arg(long, default_value_t = aws::RULE_LIMIT)]
+        rule_limit: usize,
+        #[command(flatten)]
+        selection: SelectionArgs,
+    },
+    /// Print a Prometheus scrape config probing the selected nodes through blackbox_exporter
+    Blackbox {
+        /// blackbox_exporter module probing the p2p ports
+        #[arg(long, default_value = blackbox::DEFAULT_MODULE)]
+        module: String,
+        /// Probe these port kinds instead (p2p, rpc, ws, prometheus, all), comma separated;
+        /// TCP connects for p2p and ws, http_2xx on /health and /metrics for rpc and prometheus
+        #[arg(long, value_delimiter = ',', conflicts_with = "module")]
+        kind: Vec<pcap::KindFilter>,
+        /// host:port of blackbox_exporter, as seen from Prometheus
+        #[arg(long, default_value = blackbox::DEFAULT_EXPORTER)]
+        exporter: String,
+        #[command(flatten)]
+        selection: SelectionArgs,
+    },
+    /// Print a Grafana dashboard of the selected nodes, matching the `blackbox` labels
+    Grafana {
+        /// UID of the Prometheus datasource
+        #[arg(long, default_value = grafana::DEFAULT_DATASOURCE)]
+        datasource: String,
+        #[arg(long, default_value = grafana::DEFAULT_TITLE)]
+        title: String,
+        /// Dashboard UID, so re-provisioning replaces the dashboard
+        #[arg(long, default_value = "portgen-nodes")]
+        uid: String,
+        #[command(flatten)]
+        selection: SelectionArgs,
+    },
+    /// Render a template file for one node or for every selected node
+    Render {
+        /// minijinja template; sees `nodes`, and the node's fields for a single node
+        #[arg(long)]
+        template: PathBuf,
+        /// Node name, or the node directory to select from
+        #[command(flatten)]
+        selection: SelectionArgs,
+    },
+    /// Print a self-contained HTML page of allocations, free slots and lint findings
+    Report {
+        #[command(flatten)]
+        selection: SelectionArgs,
+    },
+    /// Write one dotenv file per node into `<out-dir>/<network>/<name>.env`
+    EnvTree {
+        /// Root of the env file tree
+        #[arg(long)]
+        out_dir: PathBuf,
+        /// Delete env files of nodes that are no longer selected
+        #[arg(long)]
+        prune: bool,
+        /// Report what would change without touching any file
+        #[arg(long)]
+        dry_run: bool,
+        #[command(flatten)]
+        selection: SelectionArgs,
+    },
+    /// Print or sync /etc/hosts entries
+    Hosts {
+        #[command(subcommand)]
+        action: HostsAction,
+    },
+    /// /etc/services entries naming nodes' p2p ports, for ss, netstat and lsof
+    Services {
+        #[command(subcommand)]
+        action: ServicesAction,
+    },
+    /// Build the --reserved-nodes list for a validator
+    ReservedNodes {
+        /// Validator (or collator) node name
+        node_name: String,
+        /// File of `<node-name> <peer-id>` lines
+        #[arg(long)]
+        peer_ids: PathBuf,
+        /// Roles that count as reserved peers
+        #[arg(long, value_delimiter = ',', default_values = reserved::DEFAULT_PEER_ROLES)]
+        peer_roles: Vec<String>,
+        /// Print a JSON array instead of repeated CLI flags
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the substrate CLI port flags for a node, ready for `eval` or a unit file
+    ScriptArgs {
+        node_name: String,
+        /// Node binary flavour, `substrate-generic` adds a separate --ws-port
+        #[arg(long, value_enum, default_value_t = BinaryVersion::Polkadot1_0)]
+        binary_version: BinaryVersion,
+    },
+    /// Full systemd service unit per node
+    SystemdUnit {
+        /// Node names, `-` reads names from stdin
+        #[arg(required = true)]
+        node_names: Vec<String>,
+        /// Write `<name>.service` files into this directory instead of printing
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+        /// Node binary for ExecStart
+        #[arg(long, default_value = "/usr/local/bin/polkadot")]
+        binary: String,
+        #[command(flatten)]
+        unit: SystemdArgs,
+    },
+    /// ssh to a node's generated address
+    Ssh {
+        node_name: String,
+        /// Print the ssh command instead of running it
+        #[arg(long)]
+        print: bool,
+        /// Remote user
+        #[arg(long, short = 'l')]
+        user: Option<String>,
+        /// Private key passed to ssh -i
+        #[arg(long, short = 'i')]
+        identity_file: Option<PathBuf>,
+        /// Jump host passed to ssh -J
+        #[arg(long, short = 'J')]
+        jump: Option<String>,
+        /// Remote command, passed through verbatim after `--`
+        #[arg(last = true)]
+        remote_command: Vec<String>,
+    },
+    /// Check that every registered chain name is usable in Kubernetes
+    ValidateConfig,
+    /// Print the well-known address table (redirect into src/generated.rs)
+    GeneratePhf,
+    /// Check a Polkadot.js apps endpoint list against the portgen scheme
+    ImportPjsEndpoints {
+        /// Endpoint list in the apps JSON format
+        #[arg(long)]
+        from_json: PathBuf,
+    },
+}
+
+const SYSTEMD_HEADING: &str = "Unit options";
 
-#[derive(Debug, Clone, Copy)]
-enum Role {
-    Boot,
-    Rpc(u8),
-    Validator(u8),
+#[derive(ClapArgs)]
+struct SystemdArgs {
+    #[arg(long, default_value = "substrate", help_heading = SYSTEMD_HEADING)]
+    user: String,
+    #[arg(long, default_value = "substrate", help_heading = SYSTEMD_HEADING)]
+    group: String,
+    /// Restart= policy
+    #[arg(long, default_value = "always", value_parser = [
+        "no", "on-success", "on-failure", "on-abnormal", "on-watchdog", "on-abort", "always",
+    ], help_heading = SYSTEMD_HEADING)]
+    restart: String,
+    /// Seconds before a restart
+    #[arg(long, default_value_t = 10, help_heading = SYSTEMD_HEADING)]
+    restart_sec: u32,
+    /// Open file limit
+    #[arg(long, default_value_t = 65536, help_heading = SYSTEMD_HEADING)]
+    limit_nofile: u64,
+    /// MemoryMax=, e.g. 16G
+    #[arg(long, help_heading = SYSTEMD_HEADING)]
+    memory_max: Option<String>,
+    /// CPUQuota=, e.g. 400%
+    #[arg(long, help_heading = SYSTEMD_HEADING)]
+    cpu_quota: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum AuditAction {
+    /// Print the recorded history of one node, oldest first
+    Show {
+        #[arg(long)]
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print every config key with its effective value and where it came from
+    Show,
+}
+
+#[derive(Subcommand)]
+enum ListWhat {
+    /// Registered chains with their id and type
+    Chains {
+        /// Only chains of this type (relay, system, community)
+        #[arg(long)]
+        filter_type: Option<ChainType>,
+        /// Also list deprecated chains and chain names
+        #[arg(long)]
+        include_deprecated: bool,
+    },
+    /// Roles with their instance range, port and IP digits and an example name
+    Roles,
+}
+
+#[derive(Subcommand)]
+enum RegistryAction {
+    /// Print every registered chain with its id, aliases and where it is defined
+    Export {
+        #[arg(long, value_enum, default_value = "toml")]
+        format: RegistryFormat,
+    },
+    /// Print what an exported registry adds to this one, and where they conflict
+    Diff {
+        /// TOML or JSON written by `registry export`
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum HostsAction {
+    /// Print hosts file lines for the selected nodes
+    Print {
+        #[command(flatten)]
+        selection: SelectionArgs,
+    },
+    /// Replace the `# BEGIN portgen` / `# END portgen` block of a hosts file
+    Sync {
+        /// Hosts file to manage
+        #[arg(long, default_value = "/etc/hosts")]
+        file: PathBuf,
+        /// Print the unified diff instead of writing
+        #[arg(long)]
+        dry_run: bool,
+        #[command(flatten)]
+        selection: SelectionArgs,
+    },
+}
+
+#[derive(Subcommand)]
+enum ServicesAction {
+    /// Print services file lines for the selected nodes
+    Print {
+        #[command(flatten)]
+        selection: SelectionArgs,
+    },
+    /// Replace the `# BEGIN portgen` / `# END portgen` block of a services file
+    Sync {
+        /// Services file to manage
+        #[arg(long, default_value = "/etc/services")]
+        file: PathBuf,
+        /// Print the unified diff instead of writing
+        #[arg(long)]
+        dry_run: bool,
+        #[command(flatten)]
+        selection: SelectionArgs,
+    },
+}
+
+// Nodes from a directory of node files, narrowed by the filters
+#[derive(ClapArgs)]
+struct SelectionArgs {
+    /// Directory of <node-name>.yaml files (default: every slot of the scheme)
+    dir: Option<PathBuf>,
+    /// Only nodes on this network
+    #[arg(long)]
+    network: Option<Network>,
+    /// Only nodes of this chain (`relay` for the relay chain)
+    #[arg(long)]
+    chain: Option<String>,
+    /// Only nodes with this role
+    #[arg(long)]
+    role: Option<String>,
+    /// Also enumerate the slots of deprecated chains
+    #[arg(long)]
+    include_deprecated: bool,
+    #[command(flatten)]
+    names: NameFilterArgs,
 }
 
-impl Role {
-    fn from_str(role: &str, instance_str: &str) -> Result<Self, &'static str> {
-        if instance_str.len() != 2 {
-            return Err("instance must be two digits (00-09)");
+impl SelectionArgs {
+    fn selection(self) -> Selection {
+        Selection {
+            dir: self.dir,
+            network: self.network,
+            chain: self.chain,
+            role: self.role,
+            include_deprecated: self.include_deprecated,
+            names: self.names.filter(),
         }
+    }
+
+    fn resolve(self, quiet: bool) -> Result<Vec<String>, PortgenError> {
+        resolve_selection(&self.selection(), quiet)
+    }
+}
 
-        let num: u8 = instance_str
-            .parse()
-            .map_err(|_| "invalid instance number")?;
+// Glob filters over canonical node names for every subcommand that enumerates
+// slots or scans a directory, intersected with its other filters
+#[derive(ClapArgs)]
+struct NameFilterArgs {
+    /// Only nodes whose canonical name matches this glob (`*`, `?`), comma separated
+    #[arg(long, value_name = "GLOB", value_delimiter = ',')]
+    only: Vec<String>,
+    /// Leave out nodes whose canonical name matches this glob, comma separated
+    #[arg(long, value_name = "GLOB", value_delimiter = ',')]
+    exclude: Vec<String>,
+}
 
-        match (role, num) {
-            ("boot", 0..=9) => Ok(Self::Boot),
-            ("rpc", 1..=3) => Ok(Self::Rpc(num)),
-            ("val", 1..=6) => Ok(Self::Validator(num)),
-            _ => Err("invalid role/instance combination"),
+impl NameFilterArgs {
+    fn filter(self) -> NameFilter {
+        NameFilter {
+            only: self.only,
+            exclude: self.exclude,
         }
     }
+}
 
-    fn to_digit(self) -> u16 {
-        match self {
-            Self::Boot => 0,
-            Self::Rpc(n) => n as u16,
-            Self::Validator(n) => (n + 3) as u16,
+fn warn_unmatched(patterns: &[impl AsRef<str>], quiet: bool) {
+    if !quiet {
+        for pattern in patterns {
+            eprintln!("warning: pattern '{}' matches no node", pattern.as_ref());
         }
     }
+}
 
-    fn to_ip_digit(self) -> u8 {
-        match self {
-            Self::Boot => 0,
-            Self::Rpc(_) => 1,
-            Self::Validator(_) => 2,
+fn resolve_selection(selection: &Selection, quiet: bool) -> Result<Vec<String>, PortgenError> {
+    let selected = selection.resolve()?;
+    if !quiet {
+        for path in &selected.skipped {
+            eprintln!("warning: skipping {}: not a node name", path.display());
         }
     }
+    warn_unmatched(&selected.unmatched, quiet);
+    Ok(selected.nodes)
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Field {
+    Ip,
+    Port,
+    Addr,
+    Name,
+    Multiaddr,
+    Hostname,
+    Chain,
+    Network,
+    Role,
+    Instance,
+}
 
-    fn get_instance_number(self) -> u8 {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Output {
+    Plain,
+    Table,
+    Json,
+    /// One JSON record per line
+    Ndjson,
+    Yaml,
+    /// Kubernetes Deployment per node (boot and rpc only)
+    K8sDeployment,
+    /// Kubernetes Service and Ingress per rpc node
+    K8sIngress,
+    /// Kubernetes liveness and readiness probes per node, to merge into a container spec
+    K8sProbes,
+    /// Netplan config with the nodes' addresses on --interface
+    Netplan,
+    /// supervisord [program:x] section per node
+    Supervisor,
+    /// Nomad job spec (HCL) per node
+    Nomad,
+    /// Podman quadlet .container unit per node
+    PodmanQuadlet,
+    /// Tailscale ACL rules, one per role
+    TailscaleAcl,
+    /// Docker Compose file with a service per node
+    DockerCompose,
+    /// `docker run` command per node
+    DockerRun,
+    /// Rego policy with the port formula and the nodes' ports, for OPA admission checks
+    Opa,
+    /// Redis SET commands per node field
+    Redis,
+    /// Redis HSET command per node
+    RedisHash,
+    /// RPC proxy config per node, upstream on the ws port and listening 1000 above it
+    RpcProxy,
+    /// NATS subject per node
+    Nats,
+    /// NATS subject and the node's record as payload, one JSON object per line
+    NatsJson,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum TopologyFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum RegistryFormat {
+    Toml,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum FirewallFormat {
+    Nftables,
+    Iptables,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum BinaryVersion {
+    /// polkadot-sdk >= 1.0, HTTP and websocket RPC share --rpc-port
+    #[value(name = "polkadot-1.0")]
+    Polkadot1_0,
+    /// Older substrate binaries with a separate --ws-port
+    SubstrateGeneric,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum EndpointFormat {
+    /// JSON array of websocket URLs
+    Json,
+    /// Comma-separated, for --rpc-endpoints style flags
+    UrlList,
+    /// smoldot relay chain `endpoints` config
+    Smoldot,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum RangesFormat {
+    Text,
+    Nftables,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum AwsSgFormat {
+    Tf,
+    Cli,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum SchemaVersion {
+    /// Today's encoding, kept bit-for-bit
+    #[value(alias = "1")]
+    V1,
+    /// 10.10.0.0/16 addresses, chain hosts from .100
+    #[value(alias = "2")]
+    V2,
+}
+
+impl SchemaVersion {
+    fn schema(self) -> PortSchema {
         match self {
-            Self::Boot => 0,
-            Self::Rpc(n) => n,
-            Self::Validator(n) => n,
-        }
-    }
-}
-
-impl FromStr for Network {
-    type Err = &'static str;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_lowercase().as_str() {
-            "polkadot" => Ok(Self::Polkadot),
-            "kusama" => Ok(Self::Kusama),
-            "westend" => Ok(Self::Westend),
-            "paseo" => Ok(Self::Paseo),
-            _ => Err("invalid network name"),
-        }
-    }
-}
-
-impl ChainId {
-    fn from_str(chain: Option<&str>) -> Result<Self, &'static str> {
-        let id = match chain {
-            None => 0,
-            Some(name) => match name {
-                // system
-                "asset-hub" | "statemine" | "statemint" => 1,
-                "bridge-hub" | "bridgehub" => 2,
-                "collectives" => 3,
-                "people" => 4,
-                "coretime" => 5,
-                "encointer" => 6,
-                // custom
-                "moonbeam" | "moonriver" => 20,
-                "hyperbridge" | "nexus" | "gargantua" => 21,
-                "interlay" | "kintsugi" => 22,
-                "acala" | "karura" => 23,
-                "kilt" | "spiritnet" => 24,
-                "hydration" | "hydradx" => 25,
-                "bifrost-polkadot" | "bifrost-kusama" => 26,
-                "bajun" | "ajuna" => 27,
-                "polimec" => 28,
-                "unique" | "quartz" => 29,
-                "invarch" => 30,
-                _ => return Err("unknown chain name"),
-            },
-        };
-        Ok(ChainId(id))
+            Self::V1 => PORT_SCHEMA_V1,
+            Self::V2 => PORT_SCHEMA_V2,
+        }
     }
+}
 
-    fn to_ip_host(&self) -> u8 {
-        self.0 as u8 + 10 // Start from .10 for relay chain
-    }
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum SortKey {
+    Name,
+    Port,
+    Ip,
 }
 
-#[derive(Debug)]
-struct NodeName<'a> {
-    role: &'a str,
-    chain: Option<String>,
-    network: &'a str,
-    instance: &'a str,
+const WRITE_ERR: &str = "failed to write output";
+
+// Longest chain name shown in a table before it gets cut
+const TABLE_CHAIN_WIDTH: usize = 20;
+
+fn emit_field(field: Field, info: &NodeInfo) -> Result<String, PortgenError> {
+    let node_str = info.name.as_str();
+    let node = NodeName::parse(node_str)?;
+    let addr = info.address;
+
+    let value = match field {
+        Field::Ip => addr.ip.to_string(),
+        Field::Port => addr.port.to_string(),
+        Field::Addr => addr.to_string(),
+        Field::Name => node_str.to_string(),
+        Field::Multiaddr => addr.multiaddr(),
+        Field::Hostname => node_str.trim_end_matches(".yaml").to_string(),
+        Field::Chain => node.chain.unwrap_or_else(|| "relay".to_string()),
+        Field::Network => node.network.parse::<Network>()?.to_string(),
+        Field::Role => node.role.to_string(),
+        Field::Instance => node.instance.to_string(),
+    };
+    Ok(value)
 }
 
-impl<'a> NodeName<'a> {
-    fn parse(s: &'a str) -> Result<Self, &'static str> {
-        let parts: Vec<&str> = s.trim_end_matches(".yaml").split('-').collect();
-        if parts.len() < 3 {
-            return Err("invalid node name format");
-        }
+fn format_node(node: &NodeInfo, fields: &[Field]) -> Result<String, PortgenError> {
+    if fields.is_empty() {
+        return Ok(node.address.to_string());
+    }
 
-        let role = parts.first().ok_or("missing role")?;
-        let instance = parts.last().ok_or("missing instance")?;
-        let network = parts[parts.len() - 2];
+    let values = fields
+        .iter()
+        .map(|&field| emit_field(field, node))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(values.join("\t"))
+}
 
-        let chain = if parts.len() > 3 {
-            Some(parts[1..parts.len() - 2].join("-"))
-        } else {
-            None
-        };
+fn sort_nodes(nodes: &mut [NodeInfo], key: SortKey) {
+    match key {
+        SortKey::Name => nodes.sort_by(|a, b| a.name.cmp(&b.name)),
+        SortKey::Port => nodes.sort_by_key(|node| node.address.port),
+        SortKey::Ip => nodes.sort_by_key(|node| node.address.ip),
+    }
+}
 
-        Ok(Self {
-            role,
-            chain,
-            network,
-            instance,
+fn render_table(nodes: &[NodeInfo], fancy: bool) -> Result<String, PortgenError> {
+    let headers = ["NAME", "ROLE", "CHAIN", "NETWORK", "PORT", "IP"];
+    let rows = nodes
+        .iter()
+        .map(|node| {
+            let cell = |field| emit_field(field, node);
+            Ok(vec![
+                cell(Field::Hostname)?,
+                cell(Field::Role)?,
+                table::truncate(&cell(Field::Chain)?, TABLE_CHAIN_WIDTH),
+                cell(Field::Network)?,
+                node.address.port.to_string(),
+                node.address.ip.to_string(),
+            ])
         })
+        .collect::<Result<Vec<_>, PortgenError>>()?;
+    Ok(table::render(&headers, &rows, fancy))
+}
+
+// One node name per line, blank lines and `#` comments left out; `-` is stdin
+fn read_names_file(path: &Path) -> Result<Vec<String>, PortgenError> {
+    let text = if path.as_os_str() == "-" {
+        io::read_to_string(io::stdin()).map_err(|_| "failed to read stdin")?
+    } else {
+        fs::read_to_string(path).map_err(|_| "failed to read node names file")?
+    };
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+// The node name a `<node-name>.yaml` file is named for, as written
+fn node_file_name(path: &Path) -> Option<String> {
+    Some(path.file_stem()?.to_str()?.to_string())
+}
+
+// Names from the arguments, `-` expanding to one per non-empty stdin line,
+// with role synonyms replaced and deprecated chain names warned about when
+// `warn_deprecated`
+fn expand_node_names(
+    names: Vec<String>,
+    synonyms: &Synonyms,
+    warn_deprecated: bool,
+) -> Result<Vec<String>, PortgenError> {
+    let mut expanded = Vec::new();
+    for name in names {
+        if name != "-" {
+            expanded.push(synonyms.canonical(&name));
+            continue;
+        }
+        for line in io::stdin().lock().lines() {
+            let line = line.map_err(|_| "failed to read stdin")?;
+            let line = line.trim();
+            if !line.is_empty() {
+                expanded.push(synonyms.canonical(line));
+            }
+        }
+    }
+    if warn_deprecated {
+        expanded.iter().for_each(|name| warn_if_deprecated(name));
     }
+    Ok(expanded)
 }
 
-fn calculate_port(node_str: &str) -> Result<Port, &'static str> {
-    let node = NodeName::parse(node_str)?;
+fn warn_if_deprecated(name: &str) {
+    if let Some(deprecation) = Deprecation::of_node(name) {
+        eprintln!("warning: {name}: {deprecation}");
+    }
+}
 
+fn explain(node_str: &str) -> Result<String, PortgenError> {
+    let node = NodeName::parse(node_str)?;
     let network = node.network.parse::<Network>()?;
     let chain_id = ChainId::from_str(node.chain.as_deref())?;
-    let role = Role::from_str(node.role, node.instance)?;
+    let role = Role::from_str(&node.role, &node.instance)?;
+    Ok(format!(
+        "{node_str}: network {network} ({}), chain id {}, role {} (port digit {}, ip digit {}), instance {}",
+        network as u8,
+        chain_id.0,
+        node.role,
+        role.to_digit(),
+        role.to_ip_digit(),
+        node.instance,
+    ))
+}
 
-    let port = PORT_BASE + (network as u16 * 1000) + (chain_id.0 * 10) + role.to_digit();
+// Replaces portgen with ssh, so the remote exit status is ssh's and ours
+#[cfg(unix)]
+fn run_ssh(ssh_args: &[String]) -> Result<i32, PortgenError> {
+    use std::os::unix::process::CommandExt;
+    let _ = std::process::Command::new("ssh").args(ssh_args).exec();
+    Err("failed to run ssh".into())
+}
 
-    Ok(Port(port))
+#[cfg(not(unix))]
+fn run_ssh(ssh_args: &[String]) -> Result<i32, PortgenError> {
+    let status = std::process::Command::new("ssh")
+        .args(ssh_args)
+        .status()
+        .map_err(|_| "failed to run ssh")?;
+    Ok(status.code().unwrap_or(1))
 }
 
-fn calculate_address(node_str: &str) -> Result<NodeAddress, &'static str> {
-    let node = NodeName::parse(node_str)?;
+// Exit status of the command, errors map to 1 in main
+fn run(args: Args, out: &mut dyn Write) -> Result<i32, PortgenError> {
+    let warn_deprecated = !args.quiet && !args.no_deprecation_warnings;
+    let synonyms = if args.strict {
+        Synonyms::none()
+    } else {
+        Synonyms::new(&args.role_synonyms)?
+    };
+    let denylist = if args.ignore_denylist {
+        Vec::new()
+    } else {
+        args.denylist.clone()
+    };
+    // Subcommands handing out addresses record them in the --audit-log file
+    let audit_log = args.audit_log.clone();
+    let settings_hash = audit::settings_hash(&args.settings);
+    let record = |subcommand: &str, schema: &PortSchema, nodes: &[NodeInfo]| match &audit_log {
+        Some(path) => audit::append(path, subcommand, schema.version, &settings_hash, nodes),
+        None => Ok(()),
+    };
+    // For subcommands computing the addresses themselves, with the v1 formulas
+    let record_names = |subcommand: &str, names: &[String]| {
+        if audit_log.is_none() {
+            return Ok(());
+        }
+        let nodes = names
+            .iter()
+            .map(|name| NodeInfo::new(name.clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+        record(subcommand, &PORT_SCHEMA_V1, &nodes)
+    };
+    match args.command {
+        Some(Command::CrossChainPorts { relay, para, role }) => {
+            for (relay_node, para_node) in cross_chain_pairs(relay, &para, &role)? {
+                writeln!(out, "{relay_node} {para_node}").map_err(|_| WRITE_ERR)?;
+            }
+        }
+        Some(Command::RelayEndpoints { network, format }) => {
+            let urls = endpoints::relay_endpoints(network)?;
+            let rendered = match format {
+                EndpointFormat::Json => {
+                    serde_json::to_string_pretty(&urls).map_err(|_| "failed to serialize")?
+                }
+                EndpointFormat::UrlList => urls.join(","),
+                EndpointFormat::Smoldot => endpoints::smoldot_config(network, &urls)?,
+            };
+            writeln!(out, "{rendered}").map_err(|_| WRITE_ERR)?;
+        }
+        Some(Command::Keepalived {
+            chain,
+            network,
+            vip,
+            vip_port,
+            vrid,
+            interface,
+        }) => {
+            let chain = match chain.as_str() {
+                "relay" => None,
+                name => Some(
+                    ChainId::from_str(Some(name))?
+                        .name()?
+                        .ok_or("unknown chain name")?,
+                ),
+            };
+            let server = keepalived::VirtualServer {
+                network,
+                chain: chain.map(str::to_string),
+                vip,
+                vip_port,
+                vrid,
+                interface,
+            };
+            write!(out, "{}", keepalived::config(&server)?).map_err(|_| WRITE_ERR)?;
+        }
+        Some(Command::List { what }) => match what {
+            ListWhat::Chains {
+                filter_type,
+                include_deprecated,
+            } => {
+                for chain in enumerate_chains() {
+                    let id = ChainId::from_str(chain)?;
+                    let chain_type = id.chain_type();
+                    if filter_type.is_some_and(|wanted| wanted != chain_type) {
+                        continue;
+                    }
+                    // Sunset chains first under their own name, then renamed aliases
+                    let mut names: Vec<&str> = vec![chain.unwrap_or("relay")];
+                    if include_deprecated {
+                        let aliases = chains::table()
+                            .iter()
+                            .find(|(chain_id, _)| *chain_id == id.0);
+                        names.extend(
+                            aliases
+                                .map_or(&[][..], |(_, names)| &names[1..])
+                                .iter()
+                                .filter(|alias| Deprecation::get(alias).is_some()),
+                        );
+                    }
+                    for name in names {
+                        let deprecation = Deprecation::get(name);
+                        if deprecation.is_some() && !include_deprecated {
+                            continue;
+                        }
+                        let marker = deprecation.map_or(String::new(), |deprecation| {
+                            format!("  DEPRECATED: {}", deprecation.note)
+                                + &deprecation
+                                    .replacement
+                                    .map_or(String::new(), |r| format!(", use {r}"))
+                        });
+                        // Ids derived from the name, spelled out as the config line
+                        // that would pin them
+                        let assigned = if chains::is_auto_assigned(id.0) {
+                            format!("  {name} = {} (auto-assigned)", id.0)
+                        } else {
+                            String::new()
+                        };
+                        writeln!(out, "{:>2} {name:<16} {chain_type}{assigned}{marker}", id.0)
+                            .map_err(|_| WRITE_ERR)?;
+                    }
+                }
+            }
+            ListWhat::Roles => {
+                // Configured roles bring their own token, name and source widths
+                let table = roles::table();
+                let source =
+                    |role: &RoleConstraints| roles::source(role.abbrev).unwrap_or("builtin");
+                let width = |header: &str, cell: &dyn Fn(&RoleConstraints) -> usize| {
+                    table
+                        .iter()
+                        .map(cell)
+                        .chain([header.len()])
+                        .max()
+                        .unwrap_or(0)
+                };
+                // Built-in and configured synonyms, none under --strict
+                let aliases = |role: &RoleConstraints| {
+                    let mut aliases: Vec<&str> = synonyms
+                        .entries()
+                        .iter()
+                        .filter(|entry| entry.role == role.abbrev)
+                        .map(|entry| entry.synonym.as_str())
+                        .collect();
+                    aliases.sort_unstable();
+                    if aliases.is_empty() {
+                        "-".to_string()
+                    } else {
+                        aliases.join(",")
+                    }
+                };
+                let role_w = width("ROLE", &|role| role.abbrev.len());
+                let name_w = width("NAME", &|role| role.name.len());
+                let aliases_w = width("ALIASES", &|role| aliases(role).len());
+                let source_w = width("SOURCE", &|role| source(role).len());
+                writeln!(
+                    out,
+                    "{:<role_w$} {:<name_w$} {:<9} {:<12} {:<8} {:<aliases_w$} {:<source_w$} \
+                     EXAMPLE",
+                    "ROLE", "NAME", "INSTANCES", "PORT DIGIT", "IP DIGIT", "ALIASES", "SOURCE"
+                )
+                .map_err(|_| WRITE_ERR)?;
+                for role in table {
+                    let instances =
+                        format!("{:02}-{:02}", role.instances.start(), role.instances.end());
+                    let port_digit = match (role.instance_in_port, role.port_offset) {
+                        (false, offset) => offset.to_string(),
+                        (true, 0) => "instance".to_string(),
+                        (true, offset) => format!("instance+{offset}"),
+                    };
+                    let mut example = role.example();
+                    if role.parachain_only {
+                        example.push_str(" (parachains only)");
+                    }
+                    writeln!(
+                        out,
+                        "{:<role_w$} {:<name_w$} {instances:<9} {port_digit:<12} {:<8} \
+                         {:<aliases_w$} {:<source_w$} {example}",
+                        role.abbrev,
+                        role.name,
+                        role.ip_digit,
+                        aliases(role),
+                        source(role)
+                    )
+                    .map_err(|_| WRITE_ERR)?;
+                }
+            }
+        },
+        Some(Command::ConsistencyCheck { node_names_file }) => {
+            let schema = args.schema.schema();
+            let mut failed = 0;
+            for name in &read_names_file(&node_names_file)? {
+                match consistency::check(&schema, name) {
+                    Ok(()) => writeln!(out, "{name}: ok"),
+                    Err(e @ PortgenError::ConsistencyViolation { .. }) => {
+                        failed += 1;
+                        writeln!(out, "{e}")
+                    }
+                    Err(e) => {
+                        failed += 1;
+                        writeln!(out, "{name}: {e}")
+                    }
+                }
+                .map_err(|_| WRITE_ERR)?;
+            }
+            if failed > 0 {
+                return Err("consistency check failed".into());
+            }
+        }
+        Some(Command::CheckVersionCompat {
+            old_version,
+            new_version,
+            node_names_file,
+        }) => {
+            let old = releases::schema_of(old_version)?;
+            let new = releases::schema_of(new_version)?;
+            let names = read_names_file(&node_names_file)?;
+            let changed = if releases::same_formula(&old, &new) {
+                Vec::new()
+            } else {
+                releases::changes(&names, &old, &new)?
+            };
+            if changed.is_empty() {
+                writeln!(out, "no changes").map_err(|_| WRITE_ERR)?;
+            } else {
+                for (name, before, after) in &changed {
+                    writeln!(out, "{name} {before} -> {after}").map_err(|_| WRITE_ERR)?;
+                }
+                writeln!(
+                    out,
+                    "{} of {} nodes change between {old_version} (schema v{}) and \
+                     {new_version} (schema v{})",
+                    changed.len(),
+                    names.len(),
+                    old.version,
+                    new.version
+                )
+                .map_err(|_| WRITE_ERR)?;
+            }
+        }
+        Some(Command::SelfTest) => {
+            let help = Args::command()
+                .get_after_help()
+                .map(|help| help.to_string())
+                .unwrap_or_default();
+            let examples = selftest::examples(&help);
+            let mut failed = 0;
+            for example in &examples {
+                let expected = format!("{}:{}", example.ip, example.port);
+                match selftest::check(example) {
+                    Ok(None) => writeln!(out, "{}: ok", example.name),
+                    Ok(Some((port, ip))) => {
+                        failed += 1;
+                        writeln!(
+                            out,
+                            "{}: expected {expected}, got {ip}:{port}",
+                            example.name
+                        )
+                    }
+                    Err(e) => {
+                        failed += 1;
+                        writeln!(out, "{}: expected {expected}, got error: {e}", example.name)
+                    }
+                }
+                .map_err(|_| WRITE_ERR)?;
+            }
+            if examples.is_empty() {
+                return Err("no examples found in the help text".into());
+            }
+            if failed > 0 {
+                return Err("some documented examples are wrong".into());
+            }
+        }
+        Some(Command::SuggestChainId { name }) => {
+            writeln!(out, "{}", chains::suggest(&name)?.0).map_err(|_| WRITE_ERR)?;
+        }
+        Some(Command::Reverse { ip }) => {
+            let found = identify_schema(ip);
+            if found.is_empty() {
+                return Err("address is not a node address in any schema version".into());
+            }
+            for (schema, name) in found {
+                writeln!(out, "{name} (schema v{})", schema.version).map_err(|_| WRITE_ERR)?;
+            }
+        }
+        Some(Command::Normalize { node_names }) => {
+            // Names go through normalize whole, synonyms wherever they stand
+            for name in expand_node_names(node_names, &Synonyms::none(), false)? {
+                writeln!(out, "{}", normalize::normalize(&name, &synonyms)?)
+                    .map_err(|_| WRITE_ERR)?;
+            }
+        }
+        Some(Command::SchemaDiff { from, to, network }) => {
+            let changed = schema_diff(network, &from.schema(), &to.schema())?;
+            for (name, old, new) in &changed {
+                writeln!(out, "{name} {old} -> {new}").map_err(|_| WRITE_ERR)?;
+            }
+            writeln!(out, "{} nodes change", changed.len()).map_err(|_| WRITE_ERR)?;
+        }
+        Some(Command::Annotate {
+            template,
+            names_only,
+        }) => {
+            let schema = args.schema.schema();
+            let options = annotate::AnnotateOptions {
+                schema: &schema,
+                template: if names_only { "{name}" } else { &template },
+            };
+            let mut input = io::stdin().lock();
+            let mut line = Vec::new();
+            loop {
+                line.clear();
+                let read = input
+                    .read_until(b'\n', &mut line)
+                    .map_err(|_| "failed to read stdin")?;
+                if read == 0 {
+                    break;
+                }
+                // Lines that are not UTF-8 hold no addresses we could read back
+                match std::str::from_utf8(&line) {
+                    Ok(text) => out.write_all(annotate::annotate_line(text, &options).as_bytes()),
+                    Err(_) => out.write_all(&line),
+                }
+                .map_err(|_| WRITE_ERR)?;
+                // Each line as it arrives, for tail -f
+                out.flush().map_err(|_| WRITE_ERR)?;
+            }
+        }
+        Some(Command::Suggest { partial }) => {
+            for suggestion in suggest(&partial) {
+                writeln!(out, "{suggestion}").map_err(|_| WRITE_ERR)?;
+            }
+        }
+        Some(Command::AuditLog {
+            action: AuditAction::Show { name },
+        }) => {
+            let path = args
+                .audit_log
+                .ok_or("audit-log show needs --audit-log <path>")?;
+            for entry in audit::history(&path, &name)? {
+                writeln!(out, "{entry}").map_err(|_| WRITE_ERR)?;
+            }
+        }
+        Some(Command::New {
+            node_name,
+            out_dir,
+            template,
+            force,
+        }) => {
+            let node = scaffold::node(&synonyms.canonical(&node_name))?;
+            denylist::check(&denylist, &node)?;
+            let held = reservations::load(&args.reservations)?;
+            let user = reservations::current_user();
+            if let Some(reservation) = reservations::held_by_other(&held, &node.name, &user)? {
+                if !force {
+                    if !args.quiet {
+                        eprintln!("{}: {reservation}", node.name);
+                    }
+                    return Err("node is reserved by someone else (use --force)".into());
+                }
+            }
+            let source = match &template {
+                Some(path) => {
+                    Some(fs::read_to_string(path).map_err(|_| "failed to read template file")?)
+                }
+                None => None,
+            };
+            let label = template.as_ref().map(|path| path.display().to_string());
+            let contents = scaffold::contents(&node, label.as_deref().zip(source.as_deref()))?;
+            let path = scaffold::write(&out_dir, &node, &contents, force, args.backup)?;
+            writeln!(out, "wrote {}", path.display()).map_err(|_| WRITE_ERR)?;
+            writeln!(out, "{} {}", node.name, node.address).map_err(|_| WRITE_ERR)?;
+            record("new", &PORT_SCHEMA_V1, &[node])?;
+        }
+        Some(Command::Apply {
+            dir,
+            keys,
+            dry_run,
+            check,
+            names,
+        }) => {
+            let filter = names.filter();
+            let file_names: Vec<String> = lint::node_files(&dir)?
+                .iter()
+                .filter_map(|path| node_file_name(path))
+                .collect();
+            warn_unmatched(&filter.unmatched(&file_names), args.quiet);
+            let (reports, skipped) =
+                apply::apply_dir(&dir, &keys, &filter, !dry_run && !check, args.backup)?;
+            if !args.quiet {
+                for path in &skipped {
+                    eprintln!("{}: not a node name, skipped", path.display());
+                }
+            }
+            for report in &reports {
+                if !report.changes.is_empty() || !report.missing.is_empty() || report.unparsable {
+                    writeln!(out, "{report}").map_err(|_| WRITE_ERR)?;
+                }
+                if dry_run {
+                    write!(out, "{}", report.diff).map_err(|_| WRITE_ERR)?;
+                }
+            }
+            let changed = reports.iter().filter(|r| !r.changes.is_empty()).count();
+            if !dry_run && !check {
+                let written: Vec<String> = reports
+                    .iter()
+                    .filter(|r| !r.changes.is_empty())
+                    .filter_map(|r| node_file_name(&r.path))
+                    .collect();
+                record_names("apply", &written)?;
+            }
+            let incomplete = reports
+                .iter()
+                .filter(|r| !r.missing.is_empty() || r.unparsable)
+                .count();
+            let verb = if dry_run || check {
+                "would change"
+            } else {
+                "updated"
+            };
+            writeln!(
+                out,
+                "{changed} {verb}, {} unchanged, {incomplete} with missing keys",
+                reports.len() - changed
+            )
+            .map_err(|_| WRITE_ERR)?;
+            if check && changed > 0 {
+                return Err("node files are out of date (run portgen apply)".into());
+            }
+            if incomplete > 0 {
+                return Err("some node files miss keys".into());
+            }
+        }
+        Some(Command::VerifyYaml {
+            dir,
+            keys,
+            problems_only,
+            names,
+        }) => {
+            let filter = names.filter();
+            let file_names: Vec<String> = lint::node_files(&dir)?
+                .iter()
+                .filter_map(|path| node_file_name(path))
+                .collect();
+            warn_unmatched(&filter.unmatched(&file_names), args.quiet);
+            let (reports, skipped) = verify::verify_dir(&dir, &keys, &filter)?;
+            if !args.quiet {
+                for path in &skipped {
+                    eprintln!("{}: not a node name, skipped", path.display());
+                }
+            }
+            for report in &reports {
+                if !problems_only || report.status.exit_code() != 0 {
+                    writeln!(out, "{report}").map_err(|_| WRITE_ERR)?;
+                }
+            }
+            let count = |code| {
+                reports
+                    .iter()
+                    .filter(|report| report.status.exit_code() == code)
+                    .count()
+            };
+            writeln!(
+                out,
+                "{} ok, {} mismatched, {} with missing keys, {} invalid",
+                count(0),
+                count(5),
+                count(3),
+                count(4)
+            )
+            .map_err(|_| WRITE_ERR)?;
+            // The worst file decides the exit code
+            return Ok(reports
+                .iter()
+                .map(|report| report.status.exit_code())
+                .max()
+                .unwrap_or(0));
+        }
+        Some(Command::Reserve {
+            node_name,
+            reason,
+            by,
+        }) => {
+            let by = by.unwrap_or_else(reservations::current_user);
+            let reservation = reservations::reserve(&args.reservations, &node_name, &by, &reason)?;
+            writeln!(out, "{}: {reservation}", reservation.name).map_err(|_| WRITE_ERR)?;
+            record_names("reserve", &[reservation.name])?;
+        }
+        Some(Command::Release { node_name, force }) => {
+            let user = reservations::current_user();
+            let released = reservations::release(&args.reservations, &node_name, &user, force)?;
+            writeln!(out, "{}: released", released.name).map_err(|_| WRITE_ERR)?;
+        }
+        Some(Command::Config {
+            action: ConfigAction::Show,
+        }) => {
+            let rows: Vec<Vec<String>> = args
+                .settings
+                .iter()
+                .map(|setting| {
+                    vec![
+                        setting.key.to_string(),
+                        setting.value.clone(),
+                        setting.location(),
+                    ]
+                })
+                .collect();
+            let table = table::render(&["KEY", "VALUE", "SOURCE"], &rows, args.fancy);
+            write!(out, "{table}").map_err(|_| WRITE_ERR)?;
+        }
+        Some(Command::ConfigPath) => {
+            let path = match args.config.clone() {
+                Some(path) => path,
+                None => config::xdg_path().ok_or("neither XDG_CONFIG_HOME nor HOME is set")?,
+            };
+            writeln!(out, "{}", path.display()).map_err(|_| WRITE_ERR)?;
+        }
+        Some(Command::Registry { action }) => match action {
+            RegistryAction::Export { format } => {
+                let entries = registry::effective();
+                let dump = match format {
+                    RegistryFormat::Toml => registry::to_toml(&entries)?,
+                    RegistryFormat::Json => registry::to_json(&entries)?,
+                };
+                write!(out, "{dump}").map_err(|_| WRITE_ERR)?;
+            }
+            RegistryAction::Diff { file } => {
+                let text = fs::read_to_string(&file).map_err(|_| "failed to read registry file")?;
+                let differences = registry::diff(&registry::effective(), &registry::parse(&text)?);
+                for difference in &differences {
+                    writeln!(out, "{difference}").map_err(|_| WRITE_ERR)?;
+                }
+                if differences.iter().any(registry::Difference::is_conflict) {
+                    return Err("registries conflict".into());
+                }
+            }
+        },
+        Some(Command::Reservations) => {
+            let now = time::now_unix();
+            for reservation in reservations::load(&args.reservations)? {
+                let age = reservation.age_secs(now).map_or("?".to_string(), time::age);
+                writeln!(
+                    out,
+                    "{:<32} {:<12} {age:>7}  {}",
+                    reservation.name, reservation.by, reservation.reason
+                )
+                .map_err(|_| WRITE_ERR)?;
+            }
+        }
+        Some(Command::Next {
+            role,
+            network,
+            chain,
+            deployed,
+        }) => {
+            let chain = match chain.as_str() {
+                "relay" => None,
+                name => Some(
+                    ChainId::from_str(Some(name))?
+                        .name()?
+                        .ok_or("unknown chain name")?,
+                ),
+            };
+            let taken: Vec<String> = match deployed {
+                Some(dir) => lint::node_files(&dir)?
+                    .iter()
+                    .filter_map(|path| node_file_name(path))
+                    .filter_map(|name| canonical_name(&name).ok())
+                    .collect(),
+                None => Vec::new(),
+            };
+            let held = reservations::load(&args.reservations)?;
+            let name = reservations::next_free(&role, chain, network, &taken, &held)?
+                .ok_or("every slot of the role is deployed or reserved")?;
+            let node = NodeInfo::new(name)?;
+            writeln!(out, "{} {}", node.name, node.address).map_err(|_| WRITE_ERR)?;
+            record("next", &PORT_SCHEMA_V1, &[node])?;
+        }
+        Some(Command::ParaIds { network }) => {
+            for (id, names) in para_ids::para_ids(network) {
+                writeln!(out, "{id:>5} {}", names.join(", ")).map_err(|_| WRITE_ERR)?;
+            }
+        }
+        Some(Command::Lint {
+            dir,
+            staged,
+            install_hook,
+            names,
+        }) => {
+            if install_hook {
+                let hook = lint::install_hook(&dir)?;
+                writeln!(out, "installed {}", hook.display()).map_err(|_| WRITE_ERR)?;
+                return Ok(0);
+            }
+            let held = reservations::load(&args.reservations)?;
+            let user = reservations::current_user();
+            let options = lint::LintOptions {
+                reservations: &held,
+                user: &user,
+                validate_para_id: args.validate_para_id,
+                denylist: &args.denylist,
+            };
+            let mut tree = lint::node_files(&dir)?;
+            let mut targets = if staged {
+                let staged = lint::staged_files(&dir)?;
+                tree.extend(staged.iter().cloned());
+                staged
+            } else {
+                tree.clone()
+            };
+            // Collisions are still checked against the whole tree
+            let filter = names.filter();
+            let target_names: Vec<String> = targets
+                .iter()
+                .filter_map(|path| node_file_name(path))
+                .collect();
+            warn_unmatched(&filter.unmatched(&target_names), args.quiet);
+            targets.retain(|path| node_file_name(path).is_some_and(|name| filter.keeps(&name)));
+            let mut findings = lint::lint(&tree, &targets, &options);
+            // The whole scheme, not just the files, so new slots can be planned around
+            if !staged {
+                let collisions = denylist::collisions(&args.denylist)?;
+                for (name, hit) in collisions
+                    .into_iter()
+                    .filter(|(name, _)| filter.keeps(name))
+                {
+                    findings.push(lint::Finding {
+                        path: dir.clone(),
+                        severity: lint::Severity::Warning,
+                        message: format!("slot {name}: {hit}"),
+                    });
+                }
+            }
+            for finding in &findings {
+                writeln!(out, "{finding}").map_err(|_| WRITE_ERR)?;
+            }
+            if findings.iter().any(|f| f.severity == lint::Severity::Error) {
+                return Err("lint found errors".into());
+            }
+        }
+        Some(Command::GitHook { lint }) => {
+            write!(out, "{}", git_hook::script(lint.as_deref())).map_err(|_| WRITE_ERR)?;
+        }
+        Some(Command::Check { node_names }) => {
+            // Node names reserved by someone else still validate, with a warning
+            let held = reservations::load(&args.reservations)?;
+            let user = reservations::current_user();
+            let mut invalid = 0;
+            for node_name in expand_node_names(node_names, &synonyms, warn_deprecated)? {
+                let hits = NodeInfo::new(node_name.clone())
+                    .and_then(|node| denylist::hits(&denylist, &node));
+                match hits {
+                    Ok(hits) if !hits.is_empty() => {
+                        invalid += 1;
+                        let hits: Vec<String> = hits.iter().map(ToString::to_string).collect();
+                        writeln!(out, "{node_name}: {}", hits.join("; "))
+                    }
+                    Ok(_) => {
+                        let mut warnings = Vec::new();
+                        if let Some(reservation) =
+                            reservations::held_by_other(&held, &node_name, &user)?
+                        {
+                            warnings.push(reservation.to_string());
+                        }
+                        if args.validate_para_id {
+                            warnings.extend(para_ids::check(&node_name)?);
+                        }
+                        if warnings.is_empty() {
+                            writeln!(out, "{node_name}: ok")
+                        } else {
+                            writeln!(out, "{node_name}: warning: {}", warnings.join("; "))
+                        }
+                    }
+                    Err(e) => {
+                        invalid += 1;
+                        writeln!(out, "{node_name}: {e}")
+                    }
+                }
+                .map_err(|_| WRITE_ERR)?;
+            }
+            if invalid > 0 {
+                return Err("some node names are invalid".into());
+            }
+        }
+        Some(Command::BindCheck {
+            node_name,
+            ip,
+            port_base,
+            all_kinds,
+        }) => {
+            let mut address = calculate_address(&node_name)?;
+            address.port = calculate_port_with_base(&node_name, port_base)?;
+            let kinds: &[PortKind] = if all_kinds {
+                &PortKind::ALL
+            } else {
+                &[PortKind::P2p]
+            };
 
-    let network = node.network.parse::<Network>()?;
-    let chain_id = ChainId::from_str(node.chain.as_deref())?;
-    let role = Role::from_str(node.role, node.instance)?;
+            // The worst status decides the exit code; NotLocal only means --ip
+            // had nothing to check
+            let mut code = 0;
+            for &kind in kinds {
+                let port = address.port_of(kind)?.0;
+                let mut targets = vec![SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port)];
+                if ip {
+                    targets.push(SocketAddrV4::new(address.ip, port));
+                }
+                for target in targets {
+                    let status = bind_check(target);
+                    if all_kinds {
+                        writeln!(out, "{target} {kind} {status}")
+                    } else {
+                        writeln!(out, "{target} {status}")
+                    }
+                    .map_err(|_| WRITE_ERR)?;
+                    if status != BindStatus::NotLocal {
+                        code = code.max(status.exit_code());
+                    }
+                }
+            }
+            return Ok(code);
+        }
+        Some(Command::MigrationScript {
+            from_base,
+            to_base,
+            network,
+            from_schema,
+            to_schema,
+        }) => {
+            let from = PortSchema {
+                port_base: from_base,
+                ..from_schema.schema()
+            };
+            let to = PortSchema {
+                port_base: to_base,
+                ..to_schema.schema()
+            };
+            write!(out, "{}", migration_script(network, &from, &to)?).map_err(|_| WRITE_ERR)?;
+        }
+        Some(Command::FullTopology { network, format }) => {
+            let topology = topology::build(network)?;
+            let rendered = match format {
+                TopologyFormat::Text => topology::to_text(&topology),
+                TopologyFormat::Json => topology::to_json(&topology)? + "\n",
+            };
+            write!(out, "{rendered}").map_err(|_| WRITE_ERR)?;
+        }
+        Some(Command::FirewallMatrix {
+            policy,
+            format,
+            selection,
+        }) => {
+            let text = fs::read_to_string(policy).map_err(|_| "failed to read policy file")?;
+            let policy = firewall::parse_policy(&text)?;
+            let nodes = selection.resolve(args.quiet)?;
+            let rules = firewall::expand(&policy, &nodes)?;
+            let rendered = match format {
+                FirewallFormat::Nftables => firewall::to_nftables(&rules),
+                FirewallFormat::Iptables => firewall::to_iptables(&rules),
+            };
+            write!(out, "{rendered}").map_err(|_| WRITE_ERR)?;
+            record_names("firewall-matrix", &nodes)?;
+        }
+        Some(Command::DnsCheck {
+            domain,
+            ptr,
+            resolver,
+            jobs,
+            selection,
+        }) => {
+            let resolver = match resolver {
+                Some(resolver) => resolver,
+                None => dns::system_resolver()?,
+            };
+            let nodes = selection.resolve(args.quiet)?;
+            let reports = dns::check_all(&nodes, &domain, resolver, ptr, jobs)?;
+            let failed = reports.iter().filter(|report| !report.is_ok()).count();
+            for report in &reports {
+                writeln!(out, "{report}").map_err(|_| WRITE_ERR)?;
+            }
+            writeln!(out, "{} ok, {failed} failed", reports.len() - failed)
+                .map_err(|_| WRITE_ERR)?;
+            if failed > 0 {
+                return Err("some DNS records do not match".into());
+            }
+        }
+        Some(Command::Health {
+            node_names,
+            force,
+            json,
+            timeout,
+            jobs,
+            network,
+            chain,
+            names,
+        }) => {
+            let nodes = if node_names.is_empty() {
+                let selection = Selection {
+                    network,
+                    chain,
+                    role: Some("rpc".to_string()),
+                    names: names.filter(),
+                    ..Selection::default()
+                };
+                resolve_selection(&selection, args.quiet)?
+            } else {
+                expand_node_names(node_names, &synonyms, warn_deprecated)?
+            };
+            let reports = health::check_all(&nodes, Duration::from_secs(timeout), force, jobs)?;
+            if json {
+                let reports: Vec<_> = reports.iter().map(|report| report.to_json()).collect();
+                let rendered =
+                    serde_json::to_string_pretty(&reports).map_err(|_| "failed to serialize")?;
+                writeln!(out, "{rendered}").map_err(|_| WRITE_ERR)?;
+            } else {
+                for report in &reports {
+                    writeln!(out, "{report}").map_err(|_| WRITE_ERR)?;
+                }
+            }
+            // The worst node decides the exit code
+            return Ok(reports
+                .iter()
+                .map(|report| report.status.exit_code())
+                .max()
+                .unwrap_or(0));
+        }
+        Some(Command::Occupancy {
+            node_names,
+            deployed,
+            ports,
+            tcp_only,
+            json,
+            timeout,
+            jobs,
+            network,
+            chain,
+            role,
+            names,
+        }) => {
+            let nodes = if node_names.is_empty() {
+                let selection = Selection {
+                    network,
+                    chain,
+                    role,
+                    names: names.filter(),
+                    ..Selection::default()
+                };
+                resolve_selection(&selection, args.quiet)?
+            } else {
+                expand_node_names(node_names, &synonyms, warn_deprecated)?
+            };
+            let deployed = match deployed {
+                Some(dir) => lint::node_files(&dir)?
+                    .iter()
+                    .filter_map(|path| node_file_name(path))
+                    .filter_map(|name| canonical_name(&name).ok())
+                    .collect(),
+                None => Vec::new(),
+            };
+            let options = occupancy::ProbeOptions {
+                ports: &ports,
+                timeout: Duration::from_secs(timeout),
+                icmp: !tcp_only,
+            };
+            let reports = occupancy::probe_all(&nodes, &deployed, &options, jobs)?;
+            if json {
+                let reports: Vec<_> = reports.iter().map(|report| report.to_json()).collect();
+                let rendered =
+                    serde_json::to_string_pretty(&reports).map_err(|_| "failed to serialize")?;
+                writeln!(out, "{rendered}").map_err(|_| WRITE_ERR)?;
+            } else {
+                for report in &reports {
+                    writeln!(out, "{report}").map_err(|_| WRITE_ERR)?;
+                }
+            }
+            // Any unexpected responder decides the exit code
+            return Ok(reports
+                .iter()
+                .map(|report| report.occupancy.exit_code())
+                .max()
+                .unwrap_or(0));
+        }
+        Some(Command::RemoteAudit {
+            user,
+            identity_file,
+            jump,
+            command,
+            json,
+            timeout,
+            jobs,
+            selection,
+        }) => {
+            let nodes = selection.resolve(args.quiet)?;
+            let ssh = SshOptions {
+                user,
+                identity_file,
+                jump,
+            };
+            let options = remote_audit::AuditOptions {
+                ssh: &ssh,
+                command: &command,
+                timeout: Duration::from_secs(timeout),
+            };
+            let reports = remote_audit::audit_all(&nodes, &options, jobs)?;
+            if json {
+                let reports: Vec<_> = reports.iter().map(|report| report.to_json()).collect();
+                let rendered =
+                    serde_json::to_string_pretty(&reports).map_err(|_| "failed to serialize")?;
+                writeln!(out, "{rendered}").map_err(|_| WRITE_ERR)?;
+            } else {
+                for report in &reports {
+                    writeln!(out, "{report}").map_err(|_| WRITE_ERR)?;
+                }
+            }
+            // The worst host decides the exit code
+            return Ok(reports
+                .iter()
+                .map(|report| report.status.exit_code())
+                .max()
+                .unwrap_or(0));
+        }
+        Some(Command::IpCmds {
+            node_names,
+            interface,
+            link,
+            default_route,
+            gateway,
+            delete,
+        }) => {
+            let schema = args.schema.schema();
+            let nodes = expand_node_names(node_names, &synonyms, warn_deprecated)?
+                .into_iter()
+                .map(|name| NodeInfo::with_schema(name, &schema))
+                .collect::<Result<Vec<_>, _>>()?;
+            let blocks: Vec<String> = nodes
+                .iter()
+                .map(|node| {
+                    let options = iproute::IpOptions {
+                        interface: &interface,
+                        link,
+                        gateway: gateway
+                            .or(default_route.then(|| iproute::gateway(node.address.ip))),
+                        delete,
+                    };
+                    iproute::commands(node, &options)
+                })
+                .collect();
+            write!(out, "{}", blocks.join("\n")).map_err(|_| WRITE_ERR)?;
+            record("ip-cmds", &schema, &nodes)?;
+        }
+        Some(Command::PcapFilter {
+            node_names,
+            kind,
+            wireshark,
+            network,
+            chain,
+            role,
+            names,
+        }) => {
+            let names = if node_names.is_empty() {
+                let selection = Selection {
+                    network,
+                    chain,
+                    role,
+                    names: names.filter(),
+                    ..Selection::default()
+                };
+                resolve_selection(&selection, args.quiet)?
+            } else {
+                expand_node_names(node_names, &synonyms, warn_deprecated)?
+            };
+            let nodes = names
+                .into_iter()
+                .map(NodeInfo::new)
+                .collect::<Result<Vec<_>, _>>()?;
+            let filter = if wireshark {
+                pcap::wireshark(&nodes, kind)?
+            } else {
+                pcap::bpf(&nodes, kind)?
+            };
+            writeln!(out, "{filter}").map_err(|_| WRITE_ERR)?;
+        }
+        Some(Command::Selinux {
+            node_names,
+            kind,
+            port_type,
+            role_types,
+            modify,
+            check,
+            network,
+            chain,
+            role,
+            names,
+        }) => {
+            let names = if node_names.is_empty() {
+                let selection = Selection {
+                    network,
+                    chain,
+                    role,
+                    names: names.filter(),
+                    ..Selection::default()
+                };
+                resolve_selection(&selection, args.quiet)?
+            } else {
+                expand_node_names(node_names, &synonyms, warn_deprecated)?
+            };
+            let nodes = names
+                .into_iter()
+                .map(NodeInfo::new)
+                .collect::<Result<Vec<_>, _>>()?;
+            let options = selinux::SelinuxOptions {
+                default_type: &port_type,
+                role_types: &role_types,
+                kinds: kind,
+                modify,
+            };
+            let rendered = if check {
+                selinux::checks(&nodes, &options)?
+            } else {
+                selinux::commands(&nodes, &options)?
+            };
+            write!(out, "{rendered}").map_err(|_| WRITE_ERR)?;
+            record("selinux", &PORT_SCHEMA_V1, &nodes)?;
+        }
+        Some(Command::Ranges {
+            kind,
+            format,
+            selection,
+        }) => {
+            let nodes = selection.resolve(args.quiet)?;
+            let aggregated = ranges::aggregate(&nodes, kind)?;
+            let rendered = match format {
+                RangesFormat::Text => ranges::to_text(&aggregated),
+                RangesFormat::Nftables => ranges::to_nftables(&aggregated),
+                RangesFormat::Json => ranges::to_json(&aggregated)? + "\n",
+            };
+            write!(out, "{rendered}").map_err(|_| WRITE_ERR)?;
+        }
+        Some(Command::SubnetPlan { supernet, format }) => {
+            let schema = args.schema.schema();
+            let supernet = supernet.unwrap_or_else(|| subnet::default_supernet(&schema));
+            let plan = subnet::plan(&schema, supernet)?;
+            let rendered = match format {
+                TopologyFormat::Text => {
+                    let mut rows: Vec<Vec<String>> = plan
+                        .used
+                        .iter()
+                        .map(|block| vec![block.cidr.to_string(), block.meaning()])
+                        .collect();
+                    rows.extend(
+                        plan.unused
+                            .iter()
+                            .map(|cidr| vec![cidr.to_string(), "unused".to_string()]),
+                    );
+                    table::render(&["PREFIX", "MEANING"], &rows, args.fancy)
+                }
+                TopologyFormat::Json => subnet::to_json(&plan)? + "\n",
+            };
+            write!(out, "{rendered}").map_err(|_| WRITE_ERR)?;
+        }
+        Some(Command::AwsSg {
+            kind,
+            format,
+            source_cidr,
+            security_group,
+            rule_limit,
+            selection,
+        }) => {
+            let nodes = selection.resolve(args.quiet)?;
+            let mut kinds: Vec<PortKind> = kind.iter().flat_map(|kind| kind.kinds()).collect();
+            kinds.sort();
+            kinds.dedup();
+            let rules = aws::rules(&nodes, &kinds)?;
+            if rules.len() > rule_limit && !args.quiet {
+                eprintln!(
+                    "warning: {} rules exceed the limit of {rule_limit} per security group",
+                    rules.len()
+                );
+            }
+            let options = aws::SgOptions {
+                security_group: security_group.as_deref(),
+                source_cidr,
+            };
+            let rendered = match format {
+                AwsSgFormat::Tf => aws::to_terraform(&rules, &options),
+                AwsSgFormat::Cli => aws::to_cli(&rules, &options)?,
+            };
+            write!(out, "{rendered}").map_err(|_| WRITE_ERR)?;
+            record_names("aws-sg", &nodes)?;
+        }
+        Some(Command::Blackbox {
+            module,
+            kind,
+            exporter,
+            selection,
+        }) => {
+            let nodes = selection
+                .resolve(args.quiet)?
+                .into_iter()
+                .map(NodeInfo::new)
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut kinds: Vec<PortKind> = kind.iter().flat_map(|kind| kind.kinds()).collect();
+            kinds.sort();
+            kinds.dedup();
+            let options = blackbox::BlackboxOptions {
+                exporter: &exporter,
+                module: &module,
+                kinds: &kinds,
+            };
+            write!(out, "{}", blackbox::scrape_config(&nodes, &options)?).map_err(|_| WRITE_ERR)?;
+            record("blackbox", &PORT_SCHEMA_V1, &nodes)?;
+        }
+        Some(Command::Grafana {
+            datasource,
+            title,
+            uid,
+            selection,
+        }) => {
+            let nodes = selection
+                .resolve(args.quiet)?
+                .into_iter()
+                .map(NodeInfo::new)
+                .collect::<Result<Vec<_>, _>>()?;
+            let options = grafana::GrafanaOptions {
+                datasource: &datasource,
+                title: &title,
+                uid: &uid,
+            };
+            let dashboard = grafana::dashboard(&nodes, &options)?;
+            writeln!(out, "{}", grafana::to_json(&dashboard)?).map_err(|_| WRITE_ERR)?;
+            record("grafana", &PORT_SCHEMA_V1, &nodes)?;
+        }
+        Some(Command::Render {
+            template,
+            selection,
+        }) => {
+            let source =
+                fs::read_to_string(&template).map_err(|_| "failed to read template file")?;
+            // A lone node name renders that node, anything else is a selection
+            let names = match &selection.dir {
+                Some(target)
+                    if !target.is_dir() && NodeName::parse(&target.to_string_lossy()).is_ok() =>
+                {
+                    vec![target.to_string_lossy().into_owned()]
+                }
+                _ => selection.resolve(args.quiet)?,
+            };
+            let nodes = names
+                .into_iter()
+                .map(NodeInfo::new)
+                .collect::<Result<Vec<_>, _>>()?;
+            let rendered = render::render(&template.to_string_lossy(), &source, &nodes)?;
+            write!(out, "{rendered}").map_err(|_| WRITE_ERR)?;
+            record("render", &PORT_SCHEMA_V1, &nodes)?;
+        }
+        Some(Command::Report { selection }) => {
+            let selection = selection.selection();
+            // Findings only make sense for a directory of deployed node files
+            let findings = match &selection.dir {
+                Some(dir) => {
+                    let held = reservations::load(&args.reservations)?;
+                    let user = reservations::current_user();
+                    let options = lint::LintOptions {
+                        reservations: &held,
+                        user: &user,
+                        validate_para_id: args.validate_para_id,
+                        denylist: &args.denylist,
+                    };
+                    let files = lint::node_files(dir)?;
+                    lint::lint(&files, &files, &options)
+                }
+                None => Vec::new(),
+            };
+            let page = report::render(&report::data(&selection, &findings)?)?;
+            write!(out, "{page}").map_err(|_| WRITE_ERR)?;
+        }
+        Some(Command::EnvTree {
+            out_dir,
+            prune,
+            dry_run,
+            selection,
+        }) => {
+            let nodes = selection.resolve(args.quiet)?;
+            let changes = env_tree::sync(&out_dir, &nodes, prune, dry_run, args.backup)?;
+            let count = |kind| changes.iter().filter(|(_, c)| *c == kind).count();
+            for (path, change) in &changes {
+                if *change != Change::Unchanged {
+                    writeln!(out, "{change:<9} {}", path.display()).map_err(|_| WRITE_ERR)?;
+                }
+            }
+            writeln!(
+                out,
+                "{} created, {} updated, {} unchanged, {} pruned{}",
+                count(Change::Created),
+                count(Change::Updated),
+                count(Change::Unchanged),
+                count(Change::Pruned),
+                if dry_run { " (dry run)" } else { "" }
+            )
+            .map_err(|_| WRITE_ERR)?;
+            if !dry_run {
+                record_names("env-tree", &nodes)?;
+            }
+        }
+        Some(Command::Hosts { action }) => match action {
+            HostsAction::Print { selection } => {
+                let nodes = selection.resolve(args.quiet)?;
+                write!(out, "{}", hosts::hosts_lines(&nodes)?).map_err(|_| WRITE_ERR)?;
+                record_names("hosts", &nodes)?;
+            }
+            HostsAction::Sync {
+                file,
+                dry_run,
+                selection,
+            } => {
+                let nodes = selection.resolve(args.quiet)?;
+                let entries = hosts::hosts_lines(&nodes)?;
+                let report = match hosts::sync(&file, &entries, dry_run, args.backup)? {
+                    SyncOutcome::Unchanged => format!("{}: unchanged\n", file.display()),
+                    SyncOutcome::Updated => format!("{}: updated\n", file.display()),
+                    SyncOutcome::Diff(diff) => diff,
+                };
+                write!(out, "{report}").map_err(|_| WRITE_ERR)?;
+                if !dry_run {
+                    record_names("hosts", &nodes)?;
+                }
+            }
+        },
+        Some(Command::Services { action }) => match action {
+            ServicesAction::Print { selection } => {
+                let nodes = selection.resolve(args.quiet)?;
+                write!(out, "{}", services::services_lines(&nodes)?).map_err(|_| WRITE_ERR)?;
+                record_names("services", &nodes)?;
+            }
+            ServicesAction::Sync {
+                file,
+                dry_run,
+                selection,
+            } => {
+                let nodes = selection.resolve(args.quiet)?;
+                let entries = services::services_lines(&nodes)?;
+                let report = match services::sync(&file, &entries, dry_run, args.backup)? {
+                    SyncOutcome::Unchanged => format!("{}: unchanged\n", file.display()),
+                    SyncOutcome::Updated => format!("{}: updated\n", file.display()),
+                    SyncOutcome::Diff(diff) => diff,
+                };
+                write!(out, "{report}").map_err(|_| WRITE_ERR)?;
+                if !dry_run {
+                    record_names("services", &nodes)?;
+                }
+            }
+        },
+        Some(Command::ReservedNodes {
+            node_name,
+            peer_ids,
+            peer_roles,
+            json,
+        }) => {
+            let text = fs::read_to_string(peer_ids).map_err(|_| "failed to read peer id file")?;
+            let peer_ids = reserved::parse_peer_ids(&text)?;
 
-    let port = calculate_port(node_str)?;
+            let mut addrs = Vec::new();
+            let mut missing = Vec::new();
+            for peer in reserved::reserved_peers(&node_name, &peer_roles)? {
+                match peer_ids.get(&peer.name) {
+                    Some(peer_id) => addrs.push(reserved::multiaddr(&peer, peer_id)),
+                    None => missing.push(peer.name),
+                }
+            }
 
-    // Calculate third octet: {role}{network}{instance}
-    let third_octet = role.to_ip_digit() * 100 +    // First digit (0/1/2) * 100
-        (network as u8) * 10 +        // Second digit (1-4) * 10
-        role.get_instance_number(); // Third digit (instance number)
+            let rendered = if json {
+                serde_json::to_string_pretty(&addrs).map_err(|_| "failed to serialize")? + "\n"
+            } else {
+                addrs
+                    .iter()
+                    .map(|addr| format!("--reserved-nodes {addr}\n"))
+                    .collect()
+            };
+            write!(out, "{rendered}").map_err(|_| WRITE_ERR)?;
 
-    let fourth_octet = chain_id.to_ip_host();
+            if !missing.is_empty() {
+                if !args.quiet {
+                    for name in &missing {
+                        eprintln!("missing peer id: {name}");
+                    }
+                }
+                return Err("peer ids missing for some reserved nodes".into());
+            }
+        }
+        Some(Command::SystemdUnit {
+            node_names,
+            out_dir,
+            binary,
+            unit,
+        }) => {
+            let options = systemd::UnitOptions {
+                binary: &binary,
+                user: &unit.user,
+                group: &unit.group,
+                restart: &unit.restart,
+                restart_sec: unit.restart_sec,
+                limit_nofile: unit.limit_nofile,
+                memory_max: unit.memory_max.as_deref(),
+                cpu_quota: unit.cpu_quota.as_deref(),
+            };
+            let names = expand_node_names(node_names, &synonyms, warn_deprecated)?;
+            let units = names
+                .iter()
+                .map(|name| systemd::unit(name, &options))
+                .collect::<Result<Vec<_>, _>>()?;
+            match out_dir {
+                Some(dir) => {
+                    fs::create_dir_all(&dir).map_err(|_| "failed to create unit directory")?;
+                    for (name, unit) in names.iter().zip(&units) {
+                        let path = dir.join(systemd::unit_name(name));
+                        write_output(&path, unit.as_bytes(), args.backup)
+                            .map_err(|_| "failed to write unit file")?;
+                        if args.verbose {
+                            eprintln!("wrote {}", path.display());
+                        }
+                    }
+                }
+                None => {
+                    let units = units.join("\n");
+                    write!(out, "{units}").map_err(|_| WRITE_ERR)?;
+                }
+            }
+            record_names("systemd-unit", &names)?;
+        }
+        Some(Command::ScriptArgs {
+            node_name,
+            binary_version,
+        }) => {
+            let separate_ws = matches!(binary_version, BinaryVersion::SubstrateGeneric);
+            let schema = args.schema.schema();
+            let node = NodeInfo::with_schema(node_name, &schema)?;
+            let flags = script_args::address_flags(&node.address, separate_ws)?;
+            writeln!(out, "{}", flags.join(" ")).map_err(|_| WRITE_ERR)?;
+            record("script-args", &schema, &[node])?;
+        }
+        Some(Command::Ssh {
+            node_name,
+            print,
+            user,
+            identity_file,
+            jump,
+            remote_command,
+        }) => {
+            let options = SshOptions {
+                user,
+                identity_file,
+                jump,
+            };
+            let ssh_args = ssh::ssh_args(&node_name, &options, &remote_command)?;
+            if print {
+                let words: Vec<String> = ssh_args.iter().map(|arg| ssh::shell_quote(arg)).collect();
+                writeln!(out, "ssh {}", words.join(" ")).map_err(|_| WRITE_ERR)?;
+                return Ok(0);
+            }
+            out.flush().map_err(|_| WRITE_ERR)?;
+            return run_ssh(&ssh_args);
+        }
+        Some(Command::ValidateConfig) => {
+            let checked = ChainId::validate_registry()?;
+            writeln!(out, "ok: {checked} chain names valid").map_err(|_| WRITE_ERR)?;
+        }
+        Some(Command::GeneratePhf) => {
+            write!(out, "{}", generate_phf_source()?).map_err(|_| WRITE_ERR)?
+        }
+        Some(Command::ImportPjsEndpoints { from_json }) => {
+            let json =
+                fs::read_to_string(from_json).map_err(|_| "failed to read endpoints file")?;
+            let (mut matches, mut near, mut unknown) = (0, 0, 0);
+            for endpoint in pjs::parse_endpoints(&json)? {
+                let report = pjs::check_endpoint(endpoint);
+                match report.verdict {
+                    Verdict::Match { .. } => matches += 1,
+                    Verdict::NearMatch { .. } => near += 1,
+                    Verdict::Unknown { .. } => unknown += 1,
+                }
+                writeln!(out, "{report}").map_err(|_| WRITE_ERR)?;
+            }
+            writeln!(out, "{matches} match, {near} near-match, {unknown} unknown")
+                .map_err(|_| WRITE_ERR)?;
+        }
+        None => {
+            let schema = args.schema.schema();
+            let terminator = if args.null { '\0' } else { '\n' };
 
-    // 192.168.xyz.abc
-    let ip = Ipv4Addr::new(192, 168, third_octet, fourth_octet);
+            // A lone `-` in plain output streams: nothing needs the whole input
+            // at once, so lines are formatted in parallel as they arrive
+            let streamable = args.node_names == ["-"]
+                && args.file.is_none()
+                && args.sort.is_none()
+                && args.output == Output::Plain
+                && !args.verbose
+                && !args.validate_para_id
+                && !args.continue_on_error
+                && !args.summary
+                && args.audit_log.is_none();
+            if streamable {
+                let jobs = args
+                    .jobs
+                    .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+                let input = io::BufReader::new(io::stdin());
+                batch::process(input, out, jobs, terminator, |line| {
+                    // Warned about like the buffered names, as each line comes in
+                    if warn_deprecated {
+                        warn_if_deprecated(&synonyms.canonical(line));
+                    }
+                    let name = if args.lenient {
+                        normalize::normalize(line, &synonyms)?
+                    } else {
+                        synonyms.canonical(line)
+                    };
+                    let node = NodeInfo::with_schema(name, &schema)?;
+                    denylist::check(&denylist, &node)?;
+                    format_node(&node, &args.emit)
+                })?;
+                return Ok(0);
+            }
 
-    Ok(NodeAddress { port, ip })
+            let mut names = expand_node_names(args.node_names, &synonyms, warn_deprecated)?;
+            let mut metadata = HashMap::new();
+            if let Some(file) = &args.file {
+                for entry in inventory::read(file)? {
+                    let name = synonyms.canonical(&entry.name);
+                    names.push(name.clone());
+                    metadata.insert(name, entry.metadata);
+                }
+            }
+            let mut nodes = Vec::new();
+            let mut failed = Vec::new();
+            let mut first_error = None;
+            for name in names {
+                let node = if args.lenient {
+                    normalize::normalize(&name, &synonyms)
+                        .and_then(|canonical| NodeInfo::with_schema(canonical, &schema))
+                } else {
+                    NodeInfo::with_schema(name.clone(), &schema)
+                }
+                .and_then(|node| denylist::check(&denylist, &node).map(|()| node));
+                match node {
+                    Ok(node) => nodes.push(node),
+                    Err(e) => {
+                        failed.push((name, e.to_string()));
+                        first_error.get_or_insert(e);
+                        // Without --summary nothing needs the rest of the input
+                        if !args.continue_on_error && !args.summary {
+                            break;
+                        }
+                    }
+                }
+            }
+            let summary = args.summary.then(|| Summary::new(&nodes, failed.clone()));
+            if let Some(summary) = &summary {
+                if !args.quiet {
+                    eprint!("{}", summary.to_text());
+                }
+            }
+            if let Some(e) = first_error.as_ref().filter(|_| !args.continue_on_error) {
+                return Err(e.clone());
+            }
+            if args.validate_para_id && !args.quiet {
+                for node in &nodes {
+                    if let Some(warning) = para_ids::check(&node.name)? {
+                        eprintln!("warning: {}: {warning}", node.name);
+                    }
+                }
+            }
+            record("lookup", &schema, &nodes)?;
+            if let Some(key) = args.sort {
+                sort_nodes(&mut nodes, key);
+            }
+            if args.verbose {
+                for node in &nodes {
+                    eprintln!("{}", explain(&node.name)?);
+                }
+            }
+
+            let rendered = match args.output {
+                Output::Json | Output::Ndjson | Output::Yaml => {
+                    let records = nodes
+                        .iter()
+                        .map(|node| {
+                            let mut record = inventory::record(node, metadata.get(&node.name))?;
+                            record["schema_version"] = schema.version.into();
+                            Ok(record)
+                        })
+                        .collect::<Result<Vec<_>, PortgenError>>()?;
+                    match (args.output, &summary) {
+                        (Output::Json, None) => {
+                            serde_json::to_string_pretty(&records)
+                                .map_err(|_| "failed to serialize")?
+                                + "\n"
+                        }
+                        // Array mode carries the summary next to the records
+                        (Output::Json, Some(summary)) => {
+                            let document = serde_json::json!({ "nodes": records, "summary": summary.to_json() });
+                            serde_json::to_string_pretty(&document)
+                                .map_err(|_| "failed to serialize")?
+                                + "\n"
+                        }
+                        // NDJSON ends with the summary as its last line
+                        (Output::Ndjson, summary) => {
+                            let mut lines = String::new();
+                            let trailer = summary
+                                .as_ref()
+                                .map(|summary| serde_json::json!({ "summary": summary.to_json() }));
+                            for record in records.iter().chain(&trailer) {
+                                lines.push_str(&record.to_string());
+                                lines.push('\n');
+                            }
+                            lines
+                        }
+                        _ => serde_yaml::to_string(&records).map_err(|_| "failed to serialize")?,
+                    }
+                }
+                Output::Table => {
+                    let fancy = args.fancy || io::stdout().is_terminal();
+                    render_table(&nodes, fancy)?
+                }
+                Output::K8sDeployment => {
+                    let manifests = nodes
+                        .iter()
+                        .map(|node| k8s::deployment(node, &args.image))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    k8s::to_yaml_stream(&manifests)?
+                }
+                Output::Netplan => {
+                    let options = netplan::NetplanOptions {
+                        interface: &args.netplan.interface,
+                        prefix: args.netplan.prefix,
+                    };
+                    netplan::config(&nodes, &options)?
+                }
+                Output::K8sProbes => {
+                    let snippets = nodes
+                        .iter()
+                        .map(|node| {
+                            let yaml = k8s::to_yaml_stream(&[k8s::probes(node)?])?;
+                            Ok(format!("# {}\n{yaml}", node.name))
+                        })
+                        .collect::<Result<Vec<_>, PortgenError>>()?;
+                    snippets.join("---\n")
+                }
+                Output::K8sIngress => {
+                    let options = k8s::IngressOptions {
+                        domain: args
+                            .ingress
+                            .dns_domain
+                            .as_deref()
+                            .ok_or("--format k8s-ingress needs --dns-domain")?,
+                        class: args.ingress.ingress_class.as_deref(),
+                        tls_secret: args.ingress.tls_secret.as_deref(),
+                    };
+                    let mut manifests = Vec::new();
+                    for node in &nodes {
+                        let ingress = k8s::ingress(node, &options)?;
+                        manifests.push(k8s::service(node)?);
+                        manifests.push(ingress);
+                    }
+                    k8s::to_yaml_stream(&manifests)?
+                }
+                Output::Supervisor => nodes
+                    .iter()
+                    .map(|node| supervisor::program(&node.name, &args.binary))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .join("\n"),
+                Output::Nomad => {
+                    let options = nomad::JobOptions {
+                        datacenters: args.nomad.datacenters.clone(),
+                        region: args.nomad.region.clone(),
+                        namespace: args.nomad.namespace.clone(),
+                        job_type: args.nomad.job_type.clone(),
+                        image: args.image.clone(),
+                    };
+                    nodes
+                        .iter()
+                        .map(|node| nomad::job(&node.name, &options))
+                        .collect::<Result<Vec<_>, _>>()?
+                        .join("\n")
+                }
+                Output::PodmanQuadlet => nodes
+                    .iter()
+                    .map(|node| quadlet::container_unit(node, &args.image))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .join("\n"),
+                Output::TailscaleAcl => tailscale::acls(&nodes, &args.allow_tags)?,
+                Output::Opa => opa::policy(&nodes, &schema)?,
+                Output::RpcProxy => {
+                    let options = rpc_proxy::ProxyOptions {
+                        max_batch_size: args.rpc_proxy.max_batch_size,
+                        timeout_ms: args.rpc_proxy.timeout_ms,
+                    };
+                    rpc_proxy::configs(&nodes, &options)? + "\n"
+                }
+                Output::Redis => nodes
+                    .iter()
+                    .map(redis::set_commands)
+                    .collect::<Result<String, _>>()?,
+                Output::RedisHash => nodes
+                    .iter()
+                    .map(redis::hset_command)
+                    .collect::<Result<String, _>>()?,
+                Output::Nats => nodes
+                    .iter()
+                    .map(|node| Ok(nats::subject(node)? + "\n"))
+                    .collect::<Result<String, PortgenError>>()?,
+                Output::NatsJson => {
+                    let mut lines = String::new();
+                    for node in &nodes {
+                        let mut record = inventory::record(node, metadata.get(&node.name))?;
+                        record["schema_version"] = schema.version.into();
+                        lines.push_str(&nats::message(node, record)?.to_string());
+                        lines.push('\n');
+                    }
+                    lines
+                }
+                Output::DockerCompose | Output::DockerRun => {
+                    let network = args.docker_network.map(|name| docker::DockerNetwork {
+                        name,
+                        subnet: args.docker_subnet,
+                    });
+                    if let Some(network) = &network {
+                        for node in docker::outside_subnet(&nodes, network) {
+                            if !args.quiet {
+                                eprintln!(
+                                    "warning: {}: {} is outside docker network {} ({})",
+                                    node.name, node.address.ip, network.name, network.subnet
+                                );
+                            }
+                        }
+                    }
+                    if args.output == Output::DockerCompose {
+                        docker::compose(&nodes, &args.image, network.as_ref())?
+                    } else {
+                        let commands = nodes
+                            .iter()
+                            .map(|node| docker::run_command(node, &args.image, network.as_ref()))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        commands.join("\n") + "\n"
+                    }
+                }
+                Output::Plain => String::new(),
+            };
+            if args.output != Output::Plain {
+                write!(out, "{rendered}").map_err(|_| WRITE_ERR)?;
+            } else {
+                for node in &nodes {
+                    let record = format_node(node, &args.emit)?;
+                    write!(out, "{record}{terminator}").map_err(|_| WRITE_ERR)?;
+                }
+            }
+            if !failed.is_empty() {
+                return Err("some node names are invalid".into());
+            }
+        }
+    }
+    Ok(0)
+}
+
+// Installs the config layers as argument defaults. Each value goes through
+// its argument's parser first, so a bad one is reported where it was set.
+// Top-level keys also set the same argument of subcommands that have one.
+fn with_defaults(
+    mut command: clap::Command,
+    settings: &[Setting],
+) -> Result<clap::Command, PortgenError> {
+    for setting in settings {
+        let owner = match setting.command {
+            Some(name) => command
+                .find_subcommand(name)
+                .ok_or("config key without an argument")?,
+            None => &command,
+        };
+        let arg = owner
+            .get_arguments()
+            .find(|arg| arg.get_id() == setting.arg)
+            .ok_or("config key without an argument")?;
+        let long = arg.get_long().ok_or("config key without an argument")?;
+        let check = clap::Command::new("portgen").no_binary_name(true).arg(
+            arg.clone()
+                .required(false)
+                .requires(Resettable::Reset)
+                .conflicts_with(Resettable::Reset),
+        );
+        if check
+            .try_get_matches_from([format!("--{long}={}", setting.value)])
+            .is_err()
+        {
+            let mut message = format!("invalid value '{}'", setting.value);
+            let possible: Vec<String> = arg
+                .get_possible_values()
+                .iter()
+                .map(|value| value.get_name().to_string())
+                .collect();
+            if !possible.is_empty() {
+                message += &format!(" (possible values: {})", possible.join(", "));
+            }
+            return Err(PortgenError::Config {
+                location: setting.location(),
+                key: Some(setting.key.to_string()),
+                message,
+            });
+        }
+        let values: Vec<String> = match arg.get_value_delimiter() {
+            Some(delimiter) => setting.value.split(delimiter).map(str::to_string).collect(),
+            None => vec![setting.value.clone()],
+        };
+
+        let install = |arg: clap::Arg| arg.default_values(values.clone());
+        command = match setting.command {
+            Some(name) => command.mut_subcommand(name, |sub| sub.mut_arg(setting.arg, install)),
+            None => {
+                let shared: Vec<String> = command
+                    .get_subcommands()
+                    .filter(|sub| sub.get_arguments().any(|arg| arg.get_id() == setting.arg))
+                    .map(|sub| sub.get_name().to_string())
+                    .collect();
+                let mut command = command.mut_arg(setting.arg, install);
+                for name in shared {
+                    command = command.mut_subcommand(name, |sub| sub.mut_arg(setting.arg, install));
+                }
+                command
+            }
+        };
+    }
+    Ok(command)
+}
+
+// Sets every flag of `command` and its subcommands from its PORTGEN_*
+// variable as well, see config::env_var
+fn with_env(mut command: clap::Command, subcommands: &[&str]) -> clap::Command {
+    let flags: Vec<(String, String)> = command
+        .get_arguments()
+        .filter_map(|arg| Some((arg.get_id().to_string(), arg.get_long()?.to_string())))
+        .collect();
+    for (id, long) in flags {
+        let var = config::env_var(subcommands, &long);
+        // Switches take 1, yes and on from the environment as well as true
+        command = command.mut_arg(id, |arg| match arg.get_action() {
+            clap::ArgAction::SetTrue => arg.env(var).value_parser(BoolishValueParser::new()),
+            _ => arg.env(var),
+        });
+    }
+    let names: Vec<String> = command
+        .get_subcommands()
+        .map(|sub| sub.get_name().to_string())
+        .collect();
+    for name in names {
+        let path: Vec<&str> = subcommands.iter().copied().chain([name.as_str()]).collect();
+        command = command.mut_subcommand(&name, |sub| with_env(sub, &path));
+    }
+    command
+}
+
+// Value and source of every config key once clap has parsed the flags
+fn effective_settings(
+    matches: &clap::ArgMatches,
+    layered: Vec<Setting>,
+    command: &clap::Command,
+) -> Vec<Setting> {
+    // Global flags given after a subcommand are recorded on the subcommand
+    let mut levels = vec![matches];
+    while let Some((_, sub)) = levels[levels.len() - 1].subcommand() {
+        levels.push(sub);
+    }
+    let builtin = Args::command();
+    let find_arg = |command: &'_ clap::Command, owner: Option<&str>, id: &str| {
+        let owner = match owner {
+            Some(name) => command.find_subcommand(name)?,
+            None => command,
+        };
+        owner
+            .get_arguments()
+            .find(|arg| arg.get_id() == id)
+            .cloned()
+    };
+    config::KEYS
+        .iter()
+        .map(|&(key, owner, arg)| {
+            let setting = |value, source| Setting {
+                key,
+                command: owner,
+                arg,
+                value,
+                source,
+                line: None,
+            };
+            let flagged = levels
+                .iter()
+                .rev()
+                .find(|m| {
+                    m.try_contains_id(arg).is_ok()
+                        && m.value_source(arg) == Some(ValueSource::CommandLine)
+                })
+                .and_then(|m| m.get_raw(arg));
+            if let Some(values) = flagged {
+                let values: Vec<String> = values
+                    .map(|value| value.to_string_lossy().into_owned())
+                    .collect();
+                return setting(values.join(","), Source::Flag);
+            }
+            // Read directly, clap only reads them for the subcommand that runs
+            let var = find_arg(command, owner, arg)
+                .and_then(|arg| arg.get_env().map(|var| var.to_string_lossy().into_owned()));
+            if let Some((var, value)) =
+                var.and_then(|var| Some((var.clone(), std::env::var(var).ok()?)))
+            {
+                return setting(value, Source::Env(var));
+            }
+            if let Some(layer) = layered.iter().find(|setting| setting.key == key) {
+                return layer.clone();
+            }
+            let defaults: Vec<String> = find_arg(&builtin, owner, arg)
+                .map(|arg| {
+                    arg.get_default_values()
+                        .iter()
+                        .map(|value| value.to_string_lossy().into_owned())
+                        .collect()
+                })
+                .unwrap_or_default();
+            setting(defaults.join(","), Source::Builtin)
+        })
+        .collect()
+}
+
+// Short flags of `command` and its subcommands that take a value, which ends a
+// cluster like `-vJhost`
+fn value_shorts(command: &clap::Command, shorts: &mut Vec<char>) {
+    shorts.extend(
+        command
+            .get_arguments()
+            .filter(|arg| arg.get_action().takes_values())
+            .filter_map(|arg| arg.get_short()),
+    );
+    for sub in command.get_subcommands() {
+        value_shorts(sub, shorts);
+    }
+}
+
+// --quiet given as a flag, alone or in a cluster like `-qv`, or through
+// PORTGEN_QUIET with any value clap reads as true
+fn quiet_requested(args: &[String], command: &clap::Command) -> bool {
+    let mut takes_value = Vec::new();
+    value_shorts(command, &mut takes_value);
+    let flag = args.iter().take_while(|arg| *arg != "--").any(|arg| {
+        if arg == "--quiet" {
+            return true;
+        }
+        let Some(cluster) = arg.strip_prefix('-').filter(|rest| !rest.starts_with('-')) else {
+            return false;
+        };
+        cluster
+            .chars()
+            .take_while(|c| !takes_value.contains(c))
+            .any(|c| c == 'q')
+    });
+    let env = std::env::var_os("PORTGEN_QUIET").is_some_and(|value| {
+        BoolishValueParser::new()
+            .parse_ref(command, None, &value)
+            .unwrap_or(false)
+    });
+    flag || env
 }
 
 fn main() {
-    let args = Args::parse();
-    match calculate_address(&args.node_name) {
-        Ok(addr) => println!("{addr}"),
+    // Decided before parsing so even usage errors stay silent under --quiet
+    let raw_args: Vec<String> = std::env::args().skip(1).collect();
+    let quiet = quiet_requested(&raw_args, &Args::command());
+
+    let explicit = config::explicit_path(std::env::args().skip(1));
+    // Before clap, which parses role names in --role-synonym
+    let command = config::load_roles(explicit.as_deref())
+        .and_then(|defs| roles::install(&defs))
+        .and_then(|()| config::load_chains(explicit.as_deref()))
+        .and_then(|defs| chains::install(&defs))
+        .and_then(|()| config::load(explicit.as_deref()))
+        .and_then(|layered| with_defaults(Args::command(), &layered).map(|cmd| (cmd, layered)));
+    // After the defaults, whose values are checked without the variables
+    let command = command.map(|(command, layered)| (with_env(command, &[]), layered));
+    let (command, layered) = match command {
+        Ok(command) => command,
+        Err(e) => {
+            if !quiet {
+                eprintln!("Error: {}", e);
+            }
+            std::process::exit(1);
+        }
+    };
+
+    let parsed = command
+        .clone()
+        .try_get_matches()
+        .and_then(|matches| Ok((Args::from_arg_matches(&matches)?, matches)));
+    let args = match parsed {
+        Ok((mut args, matches)) => {
+            args.settings = effective_settings(&matches, layered, &command);
+            args
+        }
+        Err(e) if quiet => std::process::exit(e.exit_code()),
+        Err(e) => e.exit(),
+    };
+
+    let quiet = quiet || args.quiet;
+    let (out_file, backup) = (args.out_file.clone(), args.backup);
+    // --out collects everything and replaces the file only once run succeeded
+    let mut collected = Vec::new();
+    let mut out: Box<dyn Write> = if out_file.is_some() {
+        Box::new(&mut collected)
+    } else if quiet {
+        Box::new(io::sink())
+    } else {
+        Box::new(io::stdout().lock())
+    };
+    let result = run(args, &mut out);
+    let _ = out.flush();
+    drop(out);
+    let result = result.and_then(|code| {
+        if let Some(path) = &out_file {
+            write_output(path, &collected, backup).map_err(|_| "failed to write --out file")?;
+        }
+        Ok(code)
+    });
+    match result {
+        Ok(0) => {}
+        Ok(code) => std::process::exit(code),
         Err(e) => {
-            eprintln!("Error: {}", e);
+            if !quiet {
+                eprintln!("Error: {}", e);
+            }
             std::process::exit(1);
         }
     }
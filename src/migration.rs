@@ -0,0 +1,75 @@
+// iptables scripts that keep old node addresses reachable while moving to a
+// new port base or schema version
+use crate::{enumerate_nodes, Network, PortSchema, PortgenError};
+use std::fmt::Write;
+
+// Either side may be another schema version, each with its own port base.
+// Nodes keeping their address need no rule; one moving to another IP is
+// DNATed there, one only changing port is redirected on its host.
+pub fn migration_script(
+    network: Network,
+    from: &PortSchema,
+    to: &PortSchema,
+) -> Result<String, PortgenError> {
+    let (from_base, to_base) = (from.port_base, to.port_base);
+    // Each rule once, with every node it serves: collators share the
+    // validators' ports, so their redirects are the same
+    let mut rules: Vec<(String, Vec<String>)> = Vec::new();
+    let mut dnat = false;
+    for name in enumerate_nodes(network) {
+        let old = from.address(&name)?;
+        let new = to.address(&name)?;
+        let rule = if old.ip != new.ip {
+            dnat = true;
+            format!(
+                "-d {} -p tcp --dport {} -j DNAT --to-destination {new}",
+                old.ip, old.port
+            )
+        } else if old.port != new.port {
+            format!(
+                "-p tcp --dport {} -j REDIRECT --to-port {}",
+                old.port, new.port
+            )
+        } else {
+            continue;
+        };
+        match rules.iter_mut().find(|(known, _)| *known == rule) {
+            Some((_, names)) => names.push(name),
+            None => rules.push((rule, vec![name])),
+        }
+    }
+
+    let mut script = String::new();
+    let _ = writeln!(script, "#!/bin/sh");
+    let _ = writeln!(
+        script,
+        "# portgen port migration for {network}: base {from_base} -> {to_base}"
+    );
+    if from.version != to.version {
+        let _ = writeln!(script, "# schema v{} -> v{}", from.version, to.version);
+    }
+    if dnat {
+        let _ = writeln!(
+            script,
+            "# DNAT to other hosts needs net.ipv4.ip_forward=1 on this one"
+        );
+    }
+    let _ = writeln!(script, "set -e");
+    for (rule, names) in &rules {
+        let _ = writeln!(script, "\n# {}", names.join(" "));
+        let _ = writeln!(script, "iptables -t nat -A PREROUTING {rule}");
+    }
+
+    let _ = writeln!(
+        script,
+        "\n# Rollback, once every node listens on its new address:"
+    );
+    for (rule, names) in &rules {
+        let _ = writeln!(
+            script,
+            "# iptables -t nat -D PREROUTING {rule}  # {}",
+            names.join(" ")
+        );
+    }
+    Ok(script)
+}
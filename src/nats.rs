@@ -0,0 +1,21 @@
+// NATS subjects for node events, following the name hierarchy so that
+// wildcards select a network or chain: substrate.<network>.<chain-or-relay>.<role>.<instance>
+use crate::{ChainId, Network, NodeInfo, NodeName, PortgenError};
+use serde_json::{json, Value};
+
+pub fn subject(node: &NodeInfo) -> Result<String, PortgenError> {
+    let parsed = NodeName::parse(&node.name)?;
+    let network = parsed.network.parse::<Network>()?;
+    let chain = ChainId::from_str(parsed.chain.as_deref())?
+        .name()?
+        .unwrap_or("relay");
+    Ok(format!(
+        "substrate.{network}.{chain}.{}.{}",
+        parsed.role, parsed.instance
+    ))
+}
+
+// The subject with the node's full record as the payload to publish
+pub fn message(node: &NodeInfo, record: Value) -> Result<Value, PortgenError> {
+    Ok(json!({ "subject": subject(node)?, "payload": record }))
+}
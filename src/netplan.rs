@@ -0,0 +1,31 @@
+// Netplan configuration giving an interface the nodes' generated addresses
+// as static addresses, for bare-metal hosts
+use crate::{NodeInfo, PortgenError};
+use serde_json::json;
+
+pub struct NetplanOptions<'a> {
+    pub interface: &'a str,
+    pub prefix: u8,
+}
+
+// Every node on one interface, so a host running several nodes gets one file
+pub fn config(nodes: &[NodeInfo], options: &NetplanOptions) -> Result<String, PortgenError> {
+    let addresses: Vec<String> = nodes
+        .iter()
+        .map(|node| format!("{}/{}", node.address.ip, options.prefix))
+        .collect();
+    let names: Vec<&str> = nodes.iter().map(|node| node.name.as_str()).collect();
+    let config = json!({
+        "network": {
+            "version": 2,
+            "ethernets": {
+                options.interface: { "addresses": addresses },
+            },
+        },
+    });
+    let yaml = serde_yaml::to_string(&config).map_err(|_| "failed to serialize netplan config")?;
+    Ok(format!(
+        "# Generated by portgen: {}\n{yaml}",
+        names.join(", ")
+    ))
+}
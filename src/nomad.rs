@@ -0,0 +1,57 @@
+// Nomad job specs (HCL) with static port allocation
+use crate::{calculate_address, script_args::port_flags, PortKind, PortgenError};
+use std::fmt::Write;
+
+#[derive(Debug, Clone)]
+pub struct JobOptions {
+    pub datacenters: Vec<String>,
+    pub region: String,
+    pub namespace: String,
+    pub job_type: String,
+    pub image: String,
+}
+
+fn quoted_list(items: &[String]) -> String {
+    let items: Vec<String> = items.iter().map(|item| format!("{item:?}")).collect();
+    format!("[{}]", items.join(", "))
+}
+
+pub fn job(node_str: &str, options: &JobOptions) -> Result<String, PortgenError> {
+    let name = node_str.trim_end_matches(".yaml");
+    let addr = calculate_address(name)?;
+    let rpc_port = addr.port_of(PortKind::Rpc)?;
+
+    let mut out = String::new();
+    let _ = writeln!(out, "job {name:?} {{");
+    let _ = writeln!(out, "  datacenters = {}", quoted_list(&options.datacenters));
+    let _ = writeln!(out, "  region      = {:?}", options.region);
+    let _ = writeln!(out, "  namespace   = {:?}", options.namespace);
+    let _ = writeln!(out, "  type        = {:?}", options.job_type);
+    let _ = writeln!(out);
+    let _ = writeln!(out, "  group {name:?} {{");
+    let _ = writeln!(out, "    network {{");
+    let _ = writeln!(out, "      port \"substrate\" {{");
+    let _ = writeln!(out, "        static = {}", addr.port);
+    let _ = writeln!(out, "      }}");
+    let _ = writeln!(out, "      port \"rpc\" {{");
+    let _ = writeln!(out, "        static = {rpc_port}");
+    let _ = writeln!(out, "      }}");
+    let _ = writeln!(out, "    }}");
+    let _ = writeln!(out);
+    let _ = writeln!(out, "    task \"substrate\" {{");
+    let _ = writeln!(out, "      driver = \"docker\"");
+    let _ = writeln!(out);
+    let _ = writeln!(out, "      config {{");
+    let _ = writeln!(out, "        image = {:?}", options.image);
+    let _ = writeln!(out, "        ports = [\"substrate\", \"rpc\"]");
+    let _ = writeln!(
+        out,
+        "        args  = {}",
+        quoted_list(&port_flags(name, false)?)
+    );
+    let _ = writeln!(out, "      }}");
+    let _ = writeln!(out, "    }}");
+    let _ = writeln!(out, "  }}");
+    let _ = writeln!(out, "}}");
+    Ok(out)
+}
@@ -0,0 +1,193 @@
+// Lenient node names: the tokens of a name assembled in another order, like
+// `polkadot-rpc-asset-hub-01`, told apart by what they are rather than where
+// they stand, and put back in the canonical `{role}-{chain}-{network}-{instance}`
+use crate::{
+    compose_name, synonyms::Synonyms, ChainId, Network, PortgenError, RoleConstraints,
+    PORT_SCHEMA_V1,
+};
+
+fn unnormalizable(name: &str, reason: impl Into<String>) -> PortgenError {
+    PortgenError::Normalize {
+        name: name.to_string(),
+        reason: reason.into(),
+    }
+}
+
+// The single token `matches` accepts, with its position
+fn single<'a>(
+    name: &str,
+    tokens: &[&'a str],
+    what: &str,
+    matches: impl Fn(&str) -> bool,
+) -> Result<(usize, &'a str), PortgenError> {
+    let found: Vec<(usize, &str)> = tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, token)| matches(token))
+        .map(|(i, &token)| (i, token))
+        .collect();
+    match found[..] {
+        [] => Err(unnormalizable(name, format!("no {what}"))),
+        [one] => Ok(one),
+        _ => {
+            let tokens: Vec<&str> = found.iter().map(|(_, token)| *token).collect();
+            Err(unnormalizable(
+                name,
+                format!("{what} given twice ({})", tokens.join(", ")),
+            ))
+        }
+    }
+}
+
+// Role synonyms count as their role, wherever they stand
+pub fn normalize(name: &str, synonyms: &Synonyms) -> Result<String, PortgenError> {
+    let lowered = name.trim().trim_end_matches(".yaml").to_ascii_lowercase();
+    let tokens = synonyms.replace_tokens(lowered.split(['-', '_']).map(str::to_string).collect());
+    let tokens: Vec<&str> = tokens.iter().map(String::as_str).collect();
+    if tokens.iter().any(|token| token.is_empty()) {
+        return Err(unnormalizable(name, "empty token"));
+    }
+
+    let (instance_at, instance) = single(name, &tokens, "instance", |token| {
+        token.chars().all(|c| c.is_ascii_digit())
+    })?;
+    let instance: u8 = match instance.parse() {
+        Ok(instance) if instance < 100 => instance,
+        _ => return Err(unnormalizable(name, "instance must be at most two digits")),
+    };
+    let (role_at, role) = single(name, &tokens, "role", |token| {
+        RoleConstraints::get(token).is_some()
+    })?;
+
+    // Every network token could be the network, the rest must then spell a
+    // chain (or nothing, for the relay chain) without gaps between its tokens
+    let rest: Vec<(usize, &str)> = tokens
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| i != instance_at && i != role_at)
+        .map(|(i, &token)| (i, token))
+        .collect();
+    let mut readings = Vec::new();
+    for &(network_at, network) in &rest {
+        let Ok(network) = network.parse::<Network>() else {
+            continue;
+        };
+        let chain: Vec<(usize, &str)> = rest
+            .iter()
+            .filter(|&&(i, _)| i != network_at)
+            .copied()
+            .collect();
+        if chain.windows(2).any(|pair| pair[1].0 != pair[0].0 + 1) {
+            continue;
+        }
+        let chain = chain
+            .iter()
+            .map(|(_, token)| *token)
+            .collect::<Vec<_>>()
+            .join("-");
+        if !chain.is_empty() && ChainId::from_str(Some(&chain)).is_err() {
+            continue;
+        }
+        let reading = compose_name(
+            role,
+            (!chain.is_empty()).then_some(chain.as_str()),
+            network,
+            instance,
+        );
+        if !readings.contains(&reading) {
+            readings.push(reading);
+        }
+    }
+
+    match &readings[..] {
+        [] if !rest
+            .iter()
+            .any(|(_, token)| token.parse::<Network>().is_ok()) =>
+        {
+            Err(unnormalizable(name, "no network"))
+        }
+        [] => {
+            let unknown: Vec<&str> = rest
+                .iter()
+                .filter(|(_, token)| token.parse::<Network>().is_err())
+                .map(|(_, token)| *token)
+                .collect();
+            if unknown.is_empty() {
+                return Err(unnormalizable(name, "network given twice"));
+            }
+            Err(unnormalizable(
+                name,
+                format!("'{}' is not a known chain", unknown.join("-")),
+            ))
+        }
+        [canonical] => {
+            // Role and instance ranges, collators on the relay chain
+            PORT_SCHEMA_V1.port(canonical)?;
+            Ok(canonical.clone())
+        }
+        _ => Err(unnormalizable(
+            name,
+            format!("ambiguous, could be {}", readings.join(" or ")),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reorders_tokens_into_the_canonical_name() {
+        for (input, canonical) in [
+            ("polkadot-rpc-asset-hub-01", "rpc-asset-hub-polkadot-01"),
+            ("asset-hub-rpc-polkadot-01", "rpc-asset-hub-polkadot-01"),
+            ("rpc-asset-hub-polkadot-01", "rpc-asset-hub-polkadot-01"),
+            ("Kusama_val_04", "val-kusama-04"),
+            ("00-polkadot-boot.yaml", "boot-polkadot-00"),
+            // The chain's own network token is not the network
+            (
+                "kusama-rpc-bifrost-polkadot-01",
+                "rpc-bifrost-polkadot-kusama-01",
+            ),
+        ] {
+            assert_eq!(
+                normalize(input, &Synonyms::none()).unwrap(),
+                canonical,
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn role_synonyms_count_as_their_role() {
+        let synonyms = Synonyms::new(&[]).unwrap();
+        for (input, canonical) in [
+            ("polkadot-validator-04", "val-polkadot-04"),
+            (
+                "asset-hub-polkadot-rpc-node-01",
+                "rpc-asset-hub-polkadot-01",
+            ),
+            ("bootnode-kusama-00", "boot-kusama-00"),
+        ] {
+            assert_eq!(normalize(input, &synonyms).unwrap(), canonical, "{input}");
+        }
+        assert!(normalize("polkadot-validator-04", &Synonyms::none()).is_err());
+    }
+
+    #[test]
+    fn rejects_missing_duplicated_and_unknown_tokens() {
+        for input in [
+            "rpc-asset-hub-01",
+            "polkadot-asset-hub-01",
+            "rpc-polkadot",
+            "rpc-val-polkadot-01",
+            "rpc-polkadot-01-02",
+            "rpc-asset-polkadot-hub-01",
+            "rpc-nowhere-polkadot-01",
+            "col-polkadot-01",
+            "rpc-polkadot-kusama-01",
+        ] {
+            assert!(normalize(input, &Synonyms::none()).is_err(), "{input}");
+        }
+    }
+}
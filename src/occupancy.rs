@@ -0,0 +1,224 @@
+// Live probes of generated addresses before nodes are brought up on them: ICMP
+// echo through the system's ping and TCP connects, telling deployed nodes from
+// whatever else already answers on an address
+use crate::{calculate_address, canonical_name, pool, NodeAddress, PortgenError};
+use serde_json::{json, Value};
+use std::{
+    fmt,
+    io::ErrorKind,
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4, TcpStream},
+    process::Command,
+    time::Duration,
+};
+
+// ssh and web ports, where appliances and stray hosts tend to answer
+pub const DEFAULT_PORTS: &[u16] = &[22, 80, 443];
+
+pub struct ProbeOptions<'a> {
+    // Probed besides the node's own p2p port
+    pub ports: &'a [u16],
+    pub timeout: Duration,
+    // Off for --tcp-only, when ping can't send echo requests
+    pub icmp: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Occupancy {
+    // Something answers and the node is deployed
+    Expected,
+    // Something answers, but the node is not deployed
+    Unexpected,
+    Silent,
+}
+
+impl Occupancy {
+    pub fn classify(alive: bool, deployed: bool) -> Self {
+        match (alive, deployed) {
+            (false, _) => Self::Silent,
+            (true, true) => Self::Expected,
+            (true, false) => Self::Unexpected,
+        }
+    }
+
+    // Distinct exit codes, 1 and 2 stay reserved for errors and usage errors
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Self::Expected | Self::Silent => 0,
+            Self::Unexpected => 3,
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            Self::Expected => "expected",
+            Self::Unexpected => "unexpected",
+            Self::Silent => "silent",
+        }
+    }
+}
+
+impl fmt::Display for Occupancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Expected => "expected node responding",
+            Self::Unexpected => "unexpected responder",
+            Self::Silent => "silent",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortState {
+    Open,
+    // A reset still means a host holds the address
+    Refused,
+    // Timed out or unreachable
+    Filtered,
+}
+
+pub fn probe_tcp(ip: Ipv4Addr, port: u16, timeout: Duration) -> PortState {
+    let target = SocketAddr::V4(SocketAddrV4::new(ip, port));
+    match TcpStream::connect_timeout(&target, timeout) {
+        Ok(_) => PortState::Open,
+        Err(e) if e.kind() == ErrorKind::ConnectionRefused => PortState::Refused,
+        Err(_) => PortState::Filtered,
+    }
+}
+
+// One echo request; no reply is Ok(false), ping failing to run is an error
+pub fn probe_icmp(ip: Ipv4Addr, timeout: Duration) -> Result<bool, PortgenError> {
+    let output = Command::new("ping")
+        .args(["-n", "-q", "-c", "1", "-W"])
+        .arg(timeout.as_secs().max(1).to_string())
+        .arg(ip.to_string())
+        .output()
+        .map_err(|_| "failed to run ping, use --tcp-only")?;
+    match output.status.code() {
+        Some(0) => Ok(true),
+        Some(1) => Ok(false),
+        _ => Err(PortgenError::PingFailed(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        )),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OccupancyReport {
+    pub name: String,
+    pub address: NodeAddress,
+    pub deployed: bool,
+    // None when ICMP was not probed
+    pub echo_reply: Option<bool>,
+    pub open: Vec<u16>,
+    pub refused: Vec<u16>,
+    pub occupancy: Occupancy,
+}
+
+impl OccupancyReport {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "ip": self.address.ip.to_string(),
+            "status": self.occupancy.key(),
+            "deployed": self.deployed,
+            "echo_reply": self.echo_reply,
+            "open_ports": self.open,
+            "refused_ports": self.refused,
+            "exit_code": self.occupancy.exit_code(),
+        })
+    }
+}
+
+impl fmt::Display for OccupancyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.name, self.address.ip, self.occupancy)?;
+        let mut evidence = Vec::new();
+        if self.echo_reply == Some(true) {
+            evidence.push("echo reply".to_string());
+        }
+        evidence.extend(self.open.iter().map(|port| format!("{port} open")));
+        evidence.extend(self.refused.iter().map(|port| format!("{port} refused")));
+        if !evidence.is_empty() {
+            write!(f, " ({})", evidence.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+pub fn probe_node(
+    name: &str,
+    deployed: bool,
+    options: &ProbeOptions,
+) -> Result<OccupancyReport, PortgenError> {
+    let address = calculate_address(name)?;
+    let echo_reply = if options.icmp {
+        Some(probe_icmp(address.ip, options.timeout)?)
+    } else {
+        None
+    };
+    let mut ports = vec![address.port.0];
+    ports.extend(options.ports.iter().filter(|&&port| port != address.port.0));
+    let (mut open, mut refused) = (Vec::new(), Vec::new());
+    for port in ports {
+        match probe_tcp(address.ip, port, options.timeout) {
+            PortState::Open => open.push(port),
+            PortState::Refused => refused.push(port),
+            PortState::Filtered => {}
+        }
+    }
+    let alive = echo_reply == Some(true) || !open.is_empty() || !refused.is_empty();
+    Ok(OccupancyReport {
+        name: name.trim_end_matches(".yaml").to_string(),
+        address,
+        deployed,
+        echo_reply,
+        open,
+        refused,
+        occupancy: Occupancy::classify(alive, deployed),
+    })
+}
+
+// `deployed` holds canonical names; nothing deployed makes every responder
+// unexpected, the check before a first rollout
+pub fn probe_all(
+    names: &[String],
+    deployed: &[String],
+    options: &ProbeOptions,
+    jobs: usize,
+) -> Result<Vec<OccupancyReport>, PortgenError> {
+    pool::map_ordered(names, jobs, |name| {
+        let canonical = canonical_name(name)?;
+        probe_node(name, deployed.contains(&canonical), options)
+    })
+    .ok_or("occupancy worker panicked")?
+    .into_iter()
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn responders_are_told_apart_by_deployment() {
+        assert_eq!(Occupancy::classify(true, true), Occupancy::Expected);
+        assert_eq!(Occupancy::classify(true, false), Occupancy::Unexpected);
+        assert_eq!(Occupancy::classify(false, true), Occupancy::Silent);
+        assert_eq!(Occupancy::Unexpected.exit_code(), 3);
+
+        // A listener is open; once it is gone the port refuses, still a host
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let timeout = Duration::from_secs(1);
+        assert_eq!(
+            probe_tcp(Ipv4Addr::LOCALHOST, port, timeout),
+            PortState::Open
+        );
+        drop(listener);
+        assert_eq!(
+            probe_tcp(Ipv4Addr::LOCALHOST, port, timeout),
+            PortState::Refused
+        );
+    }
+}
@@ -0,0 +1,139 @@
+// Rego policy for an OPA admission sidecar: the port formula as rules, the
+// ports of the given nodes as data, and deny rules for pods whose hostPorts
+// are not the ones portgen assigned
+use crate::{chains, roles, Network, NodeInfo, PortKind, PortSchema, PortgenError};
+use serde_json::{json, Map, Value};
+use std::fmt::Write;
+
+fn literal(value: &Value) -> Result<String, PortgenError> {
+    serde_json::to_string_pretty(value).map_err(|_| "failed to serialize policy".into())
+}
+
+// Everything after the data literals; the formula mirrors PortSchema::port
+const RULES: &str = r#"
+# Node names the admission request is about, from the portgen pod labels
+requested_nodes contains name if {
+	name := input.request.object.metadata.labels["app.kubernetes.io/name"]
+}
+
+# Port of a requested node, computed from the formula
+port_for_node[name] := port if {
+	some name in requested_nodes
+	port := formula_port(name)
+}
+
+chain_id("") := 0
+
+chain_id(name) := chains[name] if name != ""
+
+role_allowed(role, _) if not role.parachain_only
+
+role_allowed(role, chain) if chain > 0
+
+formula_port(name) := port if {
+	parts := split(name, "-")
+	count(parts) >= 3
+	role := roles[parts[0]]
+	network := networks[parts[count(parts) - 2]]
+	instance_str := parts[count(parts) - 1]
+	count(instance_str) == 2
+	instance := to_number(instance_str)
+	instance >= role.min_instance
+	instance <= role.max_instance
+	chain := chain_id(concat("-", array.slice(parts, 1, count(parts) - 2)))
+	role_allowed(role, chain)
+	digit := role.port_offset + (instance * role.instance_factor)
+	port := ((port_base + (network * network_stride)) + (chain * chain_stride)) + digit
+}
+
+# Every port a node may bind: its p2p port and the other bands above it
+node_ports(name) := {port + offset |
+	port := port_for_node[name]
+	some offset in bands
+}
+
+host_ports contains port if {
+	some container in input.request.object.spec.containers
+	some mapping in container.ports
+	port := mapping.hostPort
+}
+
+deny contains msg if {
+	some name in requested_nodes
+	not port_for_node[name]
+	msg := sprintf("%s is not a valid portgen node name", [name])
+}
+
+deny contains msg if {
+	some name in requested_nodes
+	port_for_node[name]
+	not valid_port[name]
+	msg := sprintf("%s is not a known node", [name])
+}
+
+deny contains msg if {
+	some name in requested_nodes
+	port_for_node[name]
+	some port in host_ports
+	not port in node_ports(name)
+	msg := sprintf("hostPort %d of %s is not one of its portgen ports %v", [port, name, node_ports(name)])
+}
+
+deny contains msg if {
+	count(requested_nodes) > 0
+	some port in host_ports
+	port < port_base
+	msg := sprintf("hostPort %d is below the portgen port base %d", [port, port_base])
+}
+"#;
+
+pub fn policy(nodes: &[NodeInfo], schema: &PortSchema) -> Result<String, PortgenError> {
+    let networks: Map<String, Value> = Network::ALL
+        .iter()
+        .map(|&network| (network.to_string(), json!(network as u8)))
+        .collect();
+    let chains: Map<String, Value> = chains::table()
+        .iter()
+        .flat_map(|&(id, names)| names.iter().map(move |name| (name.to_string(), json!(id))))
+        .collect();
+    let roles: Map<String, Value> = roles::table()
+        .iter()
+        .map(|role| {
+            let rule = json!({
+                "port_offset": role.port_offset,
+                "instance_factor": u8::from(role.instance_in_port),
+                "min_instance": role.instances.start(),
+                "max_instance": role.instances.end(),
+                "parachain_only": role.parachain_only,
+            });
+            (role.abbrev.to_string(), rule)
+        })
+        .collect();
+    let bands: Vec<u16> = PortKind::ALL
+        .iter()
+        .map(|kind| kind.band_offset())
+        .collect();
+    let valid: Map<String, Value> = nodes
+        .iter()
+        .map(|node| (node.name.clone(), json!(node.address.port.0)))
+        .collect();
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "# Generated by portgen (port schema v{})",
+        schema.version
+    );
+    let _ = writeln!(out, "package portgen\n\nimport rego.v1\n");
+    let _ = writeln!(out, "port_base := {}\n", schema.port_base);
+    let _ = writeln!(out, "network_stride := {}\n", schema.network_stride);
+    let _ = writeln!(out, "chain_stride := {}\n", schema.chain_stride);
+    let _ = writeln!(out, "networks := {}\n", literal(&json!(networks))?);
+    let _ = writeln!(out, "chains := {}\n", literal(&json!(chains))?);
+    let _ = writeln!(out, "roles := {}\n", literal(&json!(roles))?);
+    let _ = writeln!(out, "bands := {}\n", literal(&json!(bands))?);
+    let _ = writeln!(out, "# Ports of the known nodes");
+    let _ = writeln!(out, "valid_port := {}", literal(&json!(valid))?);
+    out.push_str(RULES);
+    Ok(out)
+}
@@ -0,0 +1,94 @@
+// Para IDs of registered chains on each relay network. A chain can go by a
+// different name per network (statemint/statemine), so entries list the
+// names valid on that network
+use crate::{ChainId, Network, NodeName, PortgenError};
+
+pub const PARA_ID_MAP: &[(Network, u32, &[&str])] = &[
+    (Network::Polkadot, 1000, &["asset-hub", "statemint"]),
+    (Network::Polkadot, 1001, &["collectives"]),
+    (Network::Polkadot, 1002, &["bridge-hub", "bridgehub"]),
+    (Network::Polkadot, 1004, &["people"]),
+    (Network::Polkadot, 1005, &["coretime"]),
+    (Network::Polkadot, 2000, &["acala"]),
+    (Network::Polkadot, 2004, &["moonbeam"]),
+    (Network::Polkadot, 2030, &["bifrost-polkadot"]),
+    (Network::Polkadot, 2032, &["interlay"]),
+    (Network::Polkadot, 2034, &["hydration", "hydradx"]),
+    (Network::Polkadot, 2037, &["unique"]),
+    (Network::Polkadot, 2051, &["ajuna"]),
+    (Network::Polkadot, 2086, &["kilt", "spiritnet"]),
+    (Network::Polkadot, 3340, &["invarch"]),
+    (Network::Polkadot, 3344, &["polimec"]),
+    (Network::Polkadot, 3367, &["hyperbridge", "nexus"]),
+    (Network::Kusama, 1000, &["asset-hub", "statemine"]),
+    (Network::Kusama, 1001, &["encointer"]),
+    (Network::Kusama, 1002, &["bridge-hub", "bridgehub"]),
+    (Network::Kusama, 1004, &["people"]),
+    (Network::Kusama, 1005, &["coretime"]),
+    (Network::Kusama, 2000, &["karura"]),
+    (Network::Kusama, 2001, &["bifrost-kusama"]),
+    (Network::Kusama, 2023, &["moonriver"]),
+    (Network::Kusama, 2092, &["kintsugi"]),
+    (Network::Kusama, 2095, &["quartz"]),
+    (Network::Kusama, 2119, &["bajun"]),
+    (Network::Westend, 1000, &["asset-hub"]),
+    (Network::Westend, 1001, &["collectives"]),
+    (Network::Westend, 1002, &["bridge-hub", "bridgehub"]),
+    (Network::Westend, 1004, &["people"]),
+    (Network::Westend, 1005, &["coretime"]),
+    (Network::Paseo, 1000, &["asset-hub"]),
+    (Network::Paseo, 1002, &["bridge-hub", "bridgehub"]),
+    (Network::Paseo, 1004, &["people"]),
+    (Network::Paseo, 1005, &["coretime"]),
+    (Network::Paseo, 4009, &["hyperbridge", "gargantua"]),
+];
+
+pub fn para_ids(network: Network) -> impl Iterator<Item = (u32, &'static [&'static str])> {
+    PARA_ID_MAP
+        .iter()
+        .filter(move |(n, _, _)| *n == network)
+        .map(|&(_, id, names)| (id, names))
+}
+
+// Entries for the same registered chain, whatever name they use
+fn entries_of(
+    chain: ChainId,
+) -> impl Iterator<Item = &'static (Network, u32, &'static [&'static str])> {
+    PARA_ID_MAP
+        .iter()
+        .filter(move |(_, _, names)| ChainId::from_str(Some(names[0])).is_ok_and(|id| id == chain))
+}
+
+// Why a node's chain does not fit its relay network, None when it does or the
+// chain has no known para ID anywhere
+pub fn check(node_str: &str) -> Result<Option<String>, PortgenError> {
+    let node = NodeName::parse(node_str)?;
+    // Para names differ per network, so the spelling is what gets checked
+    let Some(chain) = node.written_chain.as_deref() else {
+        return Ok(None);
+    };
+    let network = node.network.parse::<Network>()?;
+    let chain_id = ChainId::from_str(Some(chain))?;
+    let known: Vec<_> = entries_of(chain_id).collect();
+    if known.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(match known.iter().find(|(n, _, _)| *n == network) {
+        Some((_, _, names)) if names.contains(&chain) => None,
+        Some((_, id, names)) => Some(format!(
+            "'{chain}' is not the {network} name of para {id}, use '{}'",
+            names[0]
+        )),
+        None => {
+            let elsewhere: Vec<String> = known
+                .iter()
+                .map(|(n, id, _)| format!("{id} on {n}"))
+                .collect();
+            Some(format!(
+                "'{chain}' has no known para ID on {network} ({})",
+                elsewhere.join(", ")
+            ))
+        }
+    })
+}
@@ -0,0 +1,126 @@
+// Capture filters for a node's traffic: BPF expressions for tcpdump and the
+// equivalent Wireshark display filters
+use crate::{NodeInfo, PortKind, PortgenError};
+use std::str::FromStr;
+
+// Which ports of a node a filter matches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KindFilter {
+    Kind(PortKind),
+    All,
+}
+
+impl FromStr for KindFilter {
+    type Err = PortgenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(Self::All),
+            kind => kind.parse().map(Self::Kind),
+        }
+    }
+}
+
+impl KindFilter {
+    pub fn kinds(self) -> Vec<PortKind> {
+        match self {
+            Self::Kind(kind) => vec![kind],
+            Self::All => PortKind::ALL.to_vec(),
+        }
+    }
+}
+
+fn ports(node: &NodeInfo, kinds: KindFilter) -> Result<Vec<u16>, PortgenError> {
+    kinds
+        .kinds()
+        .into_iter()
+        .map(|kind| Ok(node.address.port_of(kind)?.0))
+        .collect()
+}
+
+// Several nodes are OR-ed, each in parentheses
+fn combine(terms: Vec<String>, or: &str) -> Result<String, PortgenError> {
+    match terms.len() {
+        0 => Err("no nodes selected".into()),
+        1 => Ok(terms.concat()),
+        _ => {
+            let terms: Vec<String> = terms.iter().map(|term| format!("({term})")).collect();
+            Ok(terms.join(or))
+        }
+    }
+}
+
+// `host 192.168.111.11 and tcp port 31011`, valid for `tcpdump -d`
+pub fn bpf(nodes: &[NodeInfo], kinds: KindFilter) -> Result<String, PortgenError> {
+    let terms = nodes
+        .iter()
+        .map(|node| {
+            let ports: Vec<String> = ports(node, kinds)?
+                .iter()
+                .map(|port| format!("tcp port {port}"))
+                .collect();
+            let ports = match ports.as_slice() {
+                [port] => port.clone(),
+                ports => format!("({})", ports.join(" or ")),
+            };
+            Ok(format!("host {} and {ports}", node.address.ip))
+        })
+        .collect::<Result<Vec<_>, PortgenError>>()?;
+    combine(terms, " or ")
+}
+
+// `ip.addr == 192.168.111.11 && tcp.port == 31011`
+pub fn wireshark(nodes: &[NodeInfo], kinds: KindFilter) -> Result<String, PortgenError> {
+    let terms = nodes
+        .iter()
+        .map(|node| {
+            let ports: Vec<String> = ports(node, kinds)?
+                .iter()
+                .map(|port| port.to_string())
+                .collect();
+            let ports = match ports.as_slice() {
+                [port] => format!("tcp.port == {port}"),
+                ports => format!("tcp.port in {{{}}}", ports.join(" ")),
+            };
+            Ok(format!("ip.addr == {} && {ports}", node.address.ip))
+        })
+        .collect::<Result<Vec<_>, PortgenError>>()?;
+    combine(terms, " || ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io, process::Command};
+
+    // Compiles the expressions with tcpdump where it is installed
+    #[test]
+    fn filters_compile_with_tcpdump() {
+        let nodes: Vec<NodeInfo> = ["rpc-asset-hub-polkadot-01", "val-polkadot-04"]
+            .iter()
+            .map(|name| NodeInfo::new(name.to_string()).unwrap())
+            .collect();
+        let filters = [
+            bpf(&nodes[..1], KindFilter::Kind(PortKind::P2p)).unwrap(),
+            bpf(&nodes[..1], KindFilter::All).unwrap(),
+            bpf(&nodes, KindFilter::All).unwrap(),
+        ];
+        assert_eq!(filters[0], "host 192.168.111.11 and tcp port 31011");
+
+        for filter in filters {
+            let output = match Command::new("tcpdump")
+                .args(["-d", "-y", "EN10MB", "-i", "lo"])
+                .arg(&filter)
+                .output()
+            {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return,
+                output => output.unwrap(),
+            };
+            assert!(
+                output.status.success(),
+                "{filter}: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+    }
+}
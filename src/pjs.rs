@@ -0,0 +1,146 @@
+// Checks Polkadot.js apps endpoint lists against the portgen scheme
+use crate::{calculate_address, reverse_lookup, PortgenError};
+use serde_json::Value;
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, ToSocketAddrs},
+};
+
+#[derive(Debug, Clone)]
+pub struct Endpoint {
+    pub chain: String,
+    pub provider: String,
+    pub url: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum Verdict {
+    // IP and port both decode to the same node
+    Match { node: String },
+    // IP belongs to a node but the port is not the one portgen assigns
+    NearMatch { node: String, expected_port: u16 },
+    Unknown { reason: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct EndpointReport {
+    pub endpoint: Endpoint,
+    pub verdict: Verdict,
+}
+
+impl fmt::Display for EndpointReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Endpoint {
+            chain,
+            provider,
+            url,
+        } = &self.endpoint;
+        match &self.verdict {
+            Verdict::Match { node } => write!(f, "match       {chain} {provider} {url} -> {node}"),
+            Verdict::NearMatch {
+                node,
+                expected_port,
+            } => write!(
+                f,
+                "near-match  {chain} {provider} {url} -> {node} (expected port {expected_port})"
+            ),
+            Verdict::Unknown { reason } => {
+                write!(f, "unknown     {chain} {provider} {url} ({reason})")
+            }
+        }
+    }
+}
+
+// Apps endpoint options carry `text`/`info` and a `providers` name -> url map,
+// with parachains nested under `linked`. Walk everything so both the raw
+// array export and wrapping objects work.
+fn collect_endpoints(value: &Value, chain: &str, out: &mut Vec<Endpoint>) {
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                collect_endpoints(item, chain, out);
+            }
+        }
+        Value::Object(map) => {
+            let chain = map
+                .get("text")
+                .or_else(|| map.get("info"))
+                .and_then(Value::as_str)
+                .unwrap_or(chain);
+            if let Some(Value::Object(providers)) = map.get("providers") {
+                for (provider, url) in providers {
+                    if let Some(url) = url.as_str() {
+                        out.push(Endpoint {
+                            chain: chain.to_string(),
+                            provider: provider.clone(),
+                            url: url.to_string(),
+                        });
+                    }
+                }
+            }
+            for (key, nested) in map {
+                if key != "providers" {
+                    collect_endpoints(nested, chain, out);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+pub fn parse_endpoints(json: &str) -> Result<Vec<Endpoint>, PortgenError> {
+    let value: Value = serde_json::from_str(json).map_err(|_| "invalid endpoints JSON")?;
+    let mut endpoints = Vec::new();
+    collect_endpoints(&value, "", &mut endpoints);
+    Ok(endpoints)
+}
+
+// Host and port of a ws(s)/http(s) URL, defaulting the port from the scheme
+fn split_url(url: &str) -> Option<(String, u16)> {
+    let (scheme, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse().ok()?),
+        None => match scheme {
+            "wss" | "https" => (authority, 443),
+            "ws" | "http" => (authority, 80),
+            _ => return None,
+        },
+    };
+    Some((host.to_string(), port))
+}
+
+fn resolve(host: &str) -> Option<Ipv4Addr> {
+    if let Ok(ip) = host.parse() {
+        return Some(ip);
+    }
+    (host, 0)
+        .to_socket_addrs()
+        .ok()?
+        .find_map(|addr| match addr.ip() {
+            IpAddr::V4(ip) => Some(ip),
+            IpAddr::V6(_) => None,
+        })
+}
+
+pub fn check_endpoint(endpoint: Endpoint) -> EndpointReport {
+    let verdict = (|| -> Result<Verdict, PortgenError> {
+        let (host, port) = split_url(&endpoint.url).ok_or("unparseable url")?;
+        let ip = resolve(&host).ok_or("host does not resolve to IPv4")?;
+        let node = reverse_lookup(ip)?;
+        let expected_port = calculate_address(&node)?.port.0;
+        Ok(if expected_port == port {
+            Verdict::Match { node }
+        } else {
+            Verdict::NearMatch {
+                node,
+                expected_port,
+            }
+        })
+    })()
+    .unwrap_or_else(|e| Verdict::Unknown {
+        reason: e.to_string(),
+    });
+
+    EndpointReport { endpoint, verdict }
+}
@@ -0,0 +1,29 @@
+// Podman quadlet `.container` units, for ~/.config/containers/systemd/
+use crate::{script_args::container_args, NodeInfo, PortKind, PortgenError};
+use std::fmt::Write;
+
+pub fn container_unit(node: &NodeInfo, image: &str) -> Result<String, PortgenError> {
+    let name = node.name.trim_end_matches(".yaml");
+    let p2p = node.address.port;
+    let rpc = node.address.port_of(PortKind::Rpc)?;
+
+    let mut out = String::new();
+    let _ = writeln!(out, "# {name}.container");
+    let _ = writeln!(out, "[Unit]");
+    let _ = writeln!(out, "Description=Substrate node {name}");
+    let _ = writeln!(out);
+    let _ = writeln!(out, "[Container]");
+    let _ = writeln!(out, "ContainerName={name}");
+    let _ = writeln!(out, "Image={image}");
+    let _ = writeln!(out, "PublishPort={p2p}:{p2p}");
+    let _ = writeln!(out, "PublishPort={rpc}:{rpc}");
+    let _ = writeln!(out, "Volume={name}-data:/data");
+    let _ = writeln!(out, "Exec={}", container_args(node)?.join(" "));
+    let _ = writeln!(out);
+    let _ = writeln!(out, "[Service]");
+    let _ = writeln!(out, "Restart=always");
+    let _ = writeln!(out);
+    let _ = writeln!(out, "[Install]");
+    let _ = writeln!(out, "WantedBy=default.target");
+    Ok(out)
+}
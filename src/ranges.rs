@@ -0,0 +1,236 @@
+// Exact aggregation of node ports and IPs into port ranges and CIDR blocks
+use crate::{calculate_address, PortKind, PortgenError};
+use serde_json::json;
+use std::{fmt::Write, net::Ipv4Addr, ops::RangeInclusive};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    pub ip: Ipv4Addr,
+    pub prefix: u8,
+}
+
+impl std::fmt::Display for Cidr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.ip, self.prefix)
+    }
+}
+
+impl std::str::FromStr for Cidr {
+    type Err = PortgenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ip, prefix) = s.split_once('/').ok_or("CIDR needs a /prefix")?;
+        let ip: Ipv4Addr = ip.parse().map_err(|_| "invalid CIDR address")?;
+        let prefix: u8 = prefix.parse().map_err(|_| "invalid CIDR prefix")?;
+        if prefix > 32 {
+            return Err("invalid CIDR prefix".into());
+        }
+        let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+        Ok(Self {
+            ip: Ipv4Addr::from(u32::from(ip) & mask),
+            prefix,
+        })
+    }
+}
+
+impl Cidr {
+    pub fn contains(self, ip: Ipv4Addr) -> bool {
+        self.addresses().contains(&u32::from(ip))
+    }
+
+    pub fn addresses(self) -> RangeInclusive<u32> {
+        let start = u32::from(self.ip);
+        let size = 1u64 << (32 - self.prefix);
+        start..=(start as u64 + size - 1) as u32
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Ranges {
+    pub ports: Vec<RangeInclusive<u16>>,
+    pub cidrs: Vec<Cidr>,
+}
+
+// Runs of consecutive values, input needs to be sorted and deduplicated
+pub fn runs<T: Copy + Into<u64>>(values: &[T]) -> Vec<RangeInclusive<T>> {
+    let mut runs: Vec<RangeInclusive<T>> = Vec::new();
+    for &value in values {
+        match runs.last_mut() {
+            Some(run) if (*run.end()).into() + 1 == value.into() => {
+                *run = *run.start()..=value;
+            }
+            _ => runs.push(value..=value),
+        }
+    }
+    runs
+}
+
+// Largest aligned blocks covering exactly the run, never more
+pub fn run_to_cidrs(run: RangeInclusive<u32>) -> Vec<Cidr> {
+    let mut cidrs = Vec::new();
+    let (mut start, end) = (*run.start() as u64, *run.end() as u64);
+    while start <= end {
+        let mut size = if start == 0 {
+            1u64 << 32
+        } else {
+            1u64 << start.trailing_zeros()
+        };
+        while start + size - 1 > end {
+            size /= 2;
+        }
+        cidrs.push(Cidr {
+            ip: Ipv4Addr::from(start as u32),
+            prefix: 32 - size.trailing_zeros() as u8,
+        });
+        start += size;
+    }
+    cidrs
+}
+
+pub fn aggregate(nodes: &[String], kind: PortKind) -> Result<Ranges, PortgenError> {
+    let mut ports = Vec::new();
+    let mut ips = Vec::new();
+    for name in nodes {
+        let addr = calculate_address(name)?;
+        ports.push(addr.port_of(kind)?.0);
+        ips.push(u32::from(addr.ip));
+    }
+    ports.sort_unstable();
+    ports.dedup();
+    ips.sort_unstable();
+    ips.dedup();
+
+    Ok(Ranges {
+        ports: runs(&ports),
+        cidrs: runs(&ips).into_iter().flat_map(run_to_cidrs).collect(),
+    })
+}
+
+pub fn port_range(range: &RangeInclusive<u16>) -> String {
+    if range.start() == range.end() {
+        range.start().to_string()
+    } else {
+        format!("{}-{}", range.start(), range.end())
+    }
+}
+
+pub fn to_text(ranges: &Ranges) -> String {
+    let mut out = String::from("# ports\n");
+    for range in &ranges.ports {
+        let _ = writeln!(out, "{}", port_range(range));
+    }
+    let _ = writeln!(out, "\n# addresses");
+    for cidr in &ranges.cidrs {
+        let _ = writeln!(out, "{cidr}");
+    }
+    out
+}
+
+pub fn to_nftables(ranges: &Ranges) -> String {
+    let ports: Vec<String> = ranges.ports.iter().map(port_range).collect();
+    let cidrs: Vec<String> = ranges.cidrs.iter().map(Cidr::to_string).collect();
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "set portgen_ports {{\n\ttype inet_service\n\tflags interval\n\telements = {{ {} }}\n}}",
+        ports.join(", ")
+    );
+    let _ = writeln!(
+        out,
+        "set portgen_addrs {{\n\ttype ipv4_addr\n\tflags interval\n\telements = {{ {} }}\n}}",
+        cidrs.join(", ")
+    );
+    out
+}
+
+pub fn to_json(ranges: &Ranges) -> Result<String, PortgenError> {
+    let ports: Vec<_> = ranges
+        .ports
+        .iter()
+        .map(|range| json!({ "start": range.start(), "end": range.end() }))
+        .collect();
+    let cidrs: Vec<String> = ranges.cidrs.iter().map(Cidr::to_string).collect();
+    serde_json::to_string_pretty(&json!({ "ports": ports, "cidrs": cidrs }))
+        .map_err(|_| "failed to serialize ranges".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{selection::Selection, Network};
+    use std::collections::BTreeSet;
+
+    fn expand(ranges: &Ranges) -> (BTreeSet<u16>, BTreeSet<u32>) {
+        let ports = ranges.ports.iter().cloned().flatten().collect();
+        let ips = ranges
+            .cidrs
+            .iter()
+            .flat_map(|cidr| cidr.addresses())
+            .collect();
+        (ports, ips)
+    }
+
+    fn assert_exact(selection: Selection) {
+        let nodes = selection.resolve().unwrap().nodes;
+        for kind in PortKind::ALL {
+            let ranges = aggregate(&nodes, kind).unwrap();
+            let (ports, ips) = expand(&ranges);
+
+            let mut want_ports = BTreeSet::new();
+            let mut want_ips = BTreeSet::new();
+            for name in &nodes {
+                let addr = calculate_address(name).unwrap();
+                want_ports.insert(addr.port_of(kind).unwrap().0);
+                want_ips.insert(u32::from(addr.ip));
+            }
+            assert_eq!(ports, want_ports);
+            assert_eq!(ips, want_ips);
+        }
+    }
+
+    #[test]
+    fn every_network_and_role_expands_back_exactly() {
+        for network in Network::ALL {
+            for role in [None, Some("boot"), Some("rpc"), Some("val")] {
+                assert_exact(Selection {
+                    network: Some(network),
+                    role: role.map(str::to_string),
+                    ..Selection::default()
+                });
+            }
+        }
+        assert_exact(Selection::default());
+    }
+
+    #[test]
+    fn single_chain_expands_back_exactly() {
+        assert_exact(Selection {
+            network: Some(Network::Kusama),
+            chain: Some("asset-hub".to_string()),
+            ..Selection::default()
+        });
+    }
+
+    #[test]
+    fn runs_merge_consecutive_ports() {
+        let nodes: Vec<String> = ["rpc-kusama-01", "rpc-kusama-02", "rpc-kusama-03"]
+            .map(str::to_string)
+            .to_vec();
+        let ranges = aggregate(&nodes, PortKind::P2p).unwrap();
+        assert_eq!(ranges.ports, vec![32001..=32003]);
+    }
+
+    #[test]
+    fn cidrs_never_overreach() {
+        let start = u32::from(Ipv4Addr::new(10, 0, 0, 1));
+        let end = u32::from(Ipv4Addr::new(10, 0, 0, 6));
+        let cidrs: Vec<String> = run_to_cidrs(start..=end)
+            .iter()
+            .map(Cidr::to_string)
+            .collect();
+        assert_eq!(
+            cidrs,
+            ["10.0.0.1/32", "10.0.0.2/31", "10.0.0.4/31", "10.0.0.6/32"]
+        );
+    }
+}
@@ -0,0 +1,36 @@
+// Redis commands publishing node addresses for service discovery, keyed by
+// the node name hierarchy: substrate:<network>[:<chain>]:<role>:<instance>
+use crate::{ChainId, Network, NodeInfo, NodeName, PortgenError};
+
+pub fn key(node: &NodeInfo) -> Result<String, PortgenError> {
+    let parsed = NodeName::parse(&node.name)?;
+    let network = parsed.network.parse::<Network>()?;
+    let mut parts = vec!["substrate".to_string(), network.to_string()];
+    if let Some(chain) = ChainId::from_str(parsed.chain.as_deref())?.name()? {
+        parts.push(chain.to_string());
+    }
+    parts.push(parsed.role);
+    parts.push(parsed.instance);
+    Ok(parts.join(":"))
+}
+
+// One `SET <key>:<field> "<value>"` per field
+pub fn set_commands(node: &NodeInfo) -> Result<String, PortgenError> {
+    let key = key(node)?;
+    let addr = node.address;
+    Ok(format!(
+        "SET {key}:address \"{addr}\"\nSET {key}:port \"{}\"\nSET {key}:ip \"{}\"\n",
+        addr.port, addr.ip
+    ))
+}
+
+// All fields in one hash
+pub fn hset_command(node: &NodeInfo) -> Result<String, PortgenError> {
+    let addr = node.address;
+    Ok(format!(
+        "HSET {} address {addr} port {} ip {}\n",
+        key(node)?,
+        addr.port,
+        addr.ip
+    ))
+}
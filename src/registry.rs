@@ -0,0 +1,340 @@
+// The chain registry as a sorted TOML or JSON dump, and the differences
+// between it and a dump from elsewhere, for teams reconciling their registries
+use crate::{chains, ChainId, Deprecation, PortgenError, MAX_CHAIN_ID};
+use serde_json::{json, Value};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub id: u16,
+    // Canonical name
+    pub name: String,
+    pub aliases: Vec<String>,
+    // Names (canonical or alias) on their way out
+    pub deprecated: Vec<String>,
+    // Where the entry is defined: `builtin` or `file:line` here, whatever a dump says otherwise
+    pub source: String,
+}
+
+impl Entry {
+    fn names(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.name.as_str()).chain(self.aliases.iter().map(String::as_str))
+    }
+}
+
+fn invalid(message: String) -> PortgenError {
+    PortgenError::InvalidRegistry(message)
+}
+
+// Every registered chain by id, aliases sorted
+pub fn effective() -> Vec<Entry> {
+    let mut entries: Vec<Entry> = chains::table()
+        .iter()
+        .map(|&(id, names)| {
+            let mut aliases: Vec<String> = names[1..].iter().map(|name| name.to_string()).collect();
+            aliases.sort();
+            Entry {
+                id,
+                name: names[0].to_string(),
+                aliases,
+                deprecated: names
+                    .iter()
+                    .filter(|name| Deprecation::get(name).is_some())
+                    .map(|name| name.to_string())
+                    .collect(),
+                source: chains::source(id).unwrap_or("builtin").to_string(),
+            }
+        })
+        .collect();
+    entries.sort_by_key(|entry| entry.id);
+    entries
+}
+
+fn to_value(entries: &[Entry]) -> Value {
+    let chains: Vec<Value> = entries
+        .iter()
+        .map(|entry| {
+            json!({
+                "id": entry.id,
+                "name": entry.name,
+                "aliases": entry.aliases,
+                "deprecated": entry.deprecated,
+                "source": entry.source,
+            })
+        })
+        .collect();
+    json!({ "chains": chains })
+}
+
+pub fn to_json(entries: &[Entry]) -> Result<String, PortgenError> {
+    serde_json::to_string_pretty(&to_value(entries))
+        .map(|json| json + "\n")
+        .map_err(|_| "failed to serialize registry".into())
+}
+
+pub fn to_toml(entries: &[Entry]) -> Result<String, PortgenError> {
+    toml::to_string(&to_value(entries)).map_err(|_| "failed to serialize registry".into())
+}
+
+fn strings(chain: &Value, key: &str, n: usize) -> Result<Vec<String>, PortgenError> {
+    match chain.get(key) {
+        None => Ok(Vec::new()),
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|item| item.as_str().map(str::to_string))
+            .collect::<Option<_>>()
+            .ok_or_else(|| invalid(format!("chain {n}: '{key}' must hold strings"))),
+        Some(_) => Err(invalid(format!("chain {n}: '{key}' must be a list"))),
+    }
+}
+
+// A dump written by `to_json` or `to_toml`, told apart by its first character
+pub fn parse(text: &str) -> Result<Vec<Entry>, PortgenError> {
+    let doc: Value = if text.trim_start().starts_with('{') {
+        serde_json::from_str(text).map_err(|e| invalid(e.to_string()))?
+    } else {
+        let table: toml::Table = text
+            .parse()
+            .map_err(|e: toml::de::Error| invalid(e.message().to_string()))?;
+        serde_json::to_value(table).map_err(|e| invalid(e.to_string()))?
+    };
+    let chains = match doc.get("chains") {
+        Some(Value::Array(chains)) => chains.as_slice(),
+        Some(_) => return Err(invalid("'chains' must be a list".to_string())),
+        None => &[],
+    };
+
+    let mut entries: Vec<Entry> = Vec::new();
+    for (i, chain) in chains.iter().enumerate() {
+        let n = i + 1;
+        let id = chain
+            .get("id")
+            .and_then(Value::as_u64)
+            .filter(|&id| (1..=u64::from(MAX_CHAIN_ID)).contains(&id))
+            .ok_or_else(|| invalid(format!("chain {n}: id must be 1-{MAX_CHAIN_ID}")))?
+            as u16;
+        let name = chain
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or_else(|| invalid(format!("chain {n}: missing string field 'name'")))?;
+        let entry = Entry {
+            id,
+            name: name.to_string(),
+            aliases: strings(chain, "aliases", n)?,
+            deprecated: strings(chain, "deprecated", n)?,
+            source: chain
+                .get("source")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown")
+                .to_string(),
+        };
+        for name in entry.names() {
+            ChainId::validate_name(name)?;
+            if entries
+                .iter()
+                .any(|known| known.names().any(|known| known == name))
+            {
+                return Err(invalid(format!("chain {n}: '{name}' is listed twice")));
+            }
+        }
+        if entries.iter().any(|known| known.id == id) {
+            return Err(invalid(format!("chain {n}: id {id} is listed twice")));
+        }
+        entries.push(entry);
+    }
+    entries.sort_by_key(|entry| entry.id);
+    Ok(entries)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+    // Incoming chain neither id nor names of which are known here
+    NewChain(Entry),
+    // Incoming alias of a chain known here under the same id
+    NewAlias {
+        id: u16,
+        name: String,
+        alias: String,
+    },
+    // Same canonical name, different id
+    IdConflict {
+        name: String,
+        local: u16,
+        incoming: u16,
+    },
+    // Same id, different canonical name
+    NameConflict {
+        id: u16,
+        local: String,
+        incoming: String,
+    },
+    // A name that is a different chain here
+    AliasClash {
+        alias: String,
+        local: u16,
+        incoming: u16,
+    },
+}
+
+impl Difference {
+    pub fn is_conflict(&self) -> bool {
+        !matches!(self, Self::NewChain(_) | Self::NewAlias { .. })
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NewChain(entry) if entry.aliases.is_empty() => {
+                write!(f, "+ chain {} {}", entry.id, entry.name)
+            }
+            Self::NewChain(entry) => write!(
+                f,
+                "+ chain {} {} (aliases {})",
+                entry.id,
+                entry.name,
+                entry.aliases.join(", ")
+            ),
+            Self::NewAlias { id, name, alias } => write!(f, "+ alias {alias} of chain {id} {name}"),
+            Self::IdConflict {
+                name,
+                local,
+                incoming,
+            } => write!(
+                f,
+                "conflict: '{name}' is chain {local} here, chain {incoming} incoming"
+            ),
+            Self::NameConflict {
+                id,
+                local,
+                incoming,
+            } => write!(
+                f,
+                "conflict: chain {id} is '{local}' here, '{incoming}' incoming"
+            ),
+            Self::AliasClash {
+                alias,
+                local,
+                incoming,
+            } => write!(
+                f,
+                "conflict: '{alias}' names chain {local} here, chain {incoming} incoming"
+            ),
+        }
+    }
+}
+
+// What taking `incoming` into `local` would change; chains only `local` has stay
+pub fn diff(local: &[Entry], incoming: &[Entry]) -> Vec<Difference> {
+    let id_of = |name: &str| {
+        local
+            .iter()
+            .find(|entry| entry.names().any(|known| known == name))
+            .map(|entry| entry.id)
+    };
+    let mut differences = Vec::new();
+    for entry in incoming {
+        let same_id = local.iter().find(|known| known.id == entry.id);
+        match (same_id, id_of(&entry.name)) {
+            (Some(known), _) if known.name != entry.name => {
+                differences.push(Difference::NameConflict {
+                    id: entry.id,
+                    local: known.name.clone(),
+                    incoming: entry.name.clone(),
+                })
+            }
+            (None, Some(local)) => differences.push(Difference::IdConflict {
+                name: entry.name.clone(),
+                local,
+                incoming: entry.id,
+            }),
+            _ => {}
+        }
+
+        let mut new_aliases = Vec::new();
+        for alias in &entry.aliases {
+            match id_of(alias) {
+                Some(local) if local != entry.id => differences.push(Difference::AliasClash {
+                    alias: alias.clone(),
+                    local,
+                    incoming: entry.id,
+                }),
+                Some(_) => {}
+                None => new_aliases.push(alias.clone()),
+            }
+        }
+        match same_id {
+            Some(known) => {
+                differences.extend(new_aliases.into_iter().map(|alias| Difference::NewAlias {
+                    id: known.id,
+                    name: known.name.clone(),
+                    alias,
+                }))
+            }
+            None if id_of(&entry.name).is_none() => differences.push(Difference::NewChain(Entry {
+                aliases: new_aliases,
+                ..entry.clone()
+            })),
+            None => {}
+        }
+    }
+    differences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dumps_round_trip_in_both_formats() {
+        let entries = effective();
+        assert_eq!(parse(&to_toml(&entries).unwrap()).unwrap(), entries);
+        assert_eq!(parse(&to_json(&entries).unwrap()).unwrap(), entries);
+        assert!(diff(&entries, &entries).is_empty());
+    }
+
+    #[test]
+    fn diff_reports_additions_and_every_kind_of_conflict() {
+        let incoming = parse(
+            r#"
+            [[chains]]
+            id = 1
+            name = "asset-hub"
+            aliases = ["statemint", "ah"]
+
+            [[chains]]
+            id = 20
+            name = "glmr"
+
+            [[chains]]
+            id = 31
+            name = "polimec"
+
+            [[chains]]
+            id = 32
+            name = "frequency"
+            aliases = ["kilt"]
+
+            [[chains]]
+            id = 33
+            name = "mythos"
+            "#,
+        )
+        .unwrap();
+        let lines: Vec<String> = diff(&effective(), &incoming)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "+ alias ah of chain 1 asset-hub",
+                "conflict: chain 20 is 'moonbeam' here, 'glmr' incoming",
+                "conflict: 'polimec' is chain 28 here, chain 31 incoming",
+                "conflict: 'kilt' names chain 24 here, chain 32 incoming",
+                "+ chain 32 frequency",
+                "+ chain 33 mythos",
+            ]
+        );
+    }
+}
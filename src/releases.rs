@@ -0,0 +1,100 @@
+// Which formula each portgen release computes addresses with, so an upgrade
+// across releases can be checked for moved nodes before it is rolled out
+use crate::{NodeAddress, PortSchema, PortgenError, PORT_SCHEMA_V1};
+use std::{fmt, str::FromStr};
+
+pub const CURRENT: &str = env!("CARGO_PKG_VERSION");
+
+// First release of each schema, oldest first. A release that changes the
+// formula adds an entry; v2 is still planned and ships in no release.
+pub const SCHEMA_HISTORY: &[(&str, PortSchema)] = &[("0.1.0", PORT_SCHEMA_V1)];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version(pub u16, pub u16, pub u16);
+
+impl FromStr for Version {
+    type Err = PortgenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.strip_prefix('v').unwrap_or(s);
+        let parts: Vec<u16> = s
+            .split('.')
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map_err(|_| "release version must be X.Y.Z")?;
+        match parts[..] {
+            [major, minor, patch] => Ok(Self(major, minor, patch)),
+            _ => Err("release version must be X.Y.Z".into()),
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+pub fn schema_of(version: Version) -> Result<PortSchema, PortgenError> {
+    if version > CURRENT.parse()? {
+        return Err("release is newer than this portgen, its formula is unknown".into());
+    }
+    SCHEMA_HISTORY
+        .iter()
+        .rev()
+        .find(|(first, _)| first.parse().is_ok_and(|first: Version| first <= version))
+        .map(|&(_, schema)| schema)
+        .ok_or_else(|| "release predates the first portgen release".into())
+}
+
+// Same ports and addresses for every node, whatever the version numbers say
+pub fn same_formula(a: &PortSchema, b: &PortSchema) -> bool {
+    PortSchema { version: 0, ..*a } == PortSchema { version: 0, ..*b }
+}
+
+// Nodes whose port or IP differs between two schemas
+pub fn changes(
+    names: &[String],
+    old: &PortSchema,
+    new: &PortSchema,
+) -> Result<Vec<(String, NodeAddress, NodeAddress)>, PortgenError> {
+    let mut changed = Vec::new();
+    for name in names {
+        let before = old.address(name)?;
+        let after = new.address(name)?;
+        if before.port != after.port || before.ip != after.ip {
+            changed.push((name.clone(), before, after));
+        }
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PORT_SCHEMA_V2;
+
+    #[test]
+    fn releases_map_to_their_schema() {
+        assert_eq!(schema_of("0.1.0".parse().unwrap()).unwrap(), PORT_SCHEMA_V1);
+        assert_eq!(schema_of(CURRENT.parse().unwrap()).unwrap(), PORT_SCHEMA_V1);
+        assert!(schema_of("0.0.9".parse().unwrap()).is_err());
+        assert!(schema_of(Version(999, 0, 0)).is_err());
+        for bad in ["0.6", "0.6.2.1", "0.x.2", ""] {
+            assert!(bad.parse::<Version>().is_err(), "{bad}");
+        }
+
+        let names = vec!["rpc-polkadot-01".to_string()];
+        assert!(same_formula(
+            &PORT_SCHEMA_V1,
+            &PortSchema {
+                version: 9,
+                ..PORT_SCHEMA_V1
+            }
+        ));
+        assert!(!same_formula(&PORT_SCHEMA_V1, &PORT_SCHEMA_V2));
+        let changed = changes(&names, &PORT_SCHEMA_V1, &PORT_SCHEMA_V2).unwrap();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].2.to_string(), "10.10.111.100:31001");
+    }
+}
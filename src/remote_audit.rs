@@ -0,0 +1,361 @@
+// Listener audit over ssh: runs `ss -ltnp` on each node's generated address
+// and compares what listens with the ports portgen assigned
+use crate::{
+    calculate_address, pool,
+    ssh::{ssh_args, SshOptions},
+    NodeAddress, PortKind, PortgenError, PORT_BASE,
+};
+use serde_json::{json, Value};
+use std::{fmt, net::Ipv4Addr, process::Command, time::Duration};
+
+pub const DEFAULT_COMMAND: &str = "ss -ltnp";
+
+// ssh exits with 255 for its own failures, anything else is the remote command's
+const SSH_FAILURE: i32 = 255;
+
+// One line of `ss -ltnp`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Listener {
+    // As printed, `*`, `0.0.0.0` and `::` for any address
+    pub address: String,
+    pub port: u16,
+    // `users:(("polkadot",pid=812,fd=23))`, empty without -p or privileges
+    pub process: String,
+}
+
+impl Listener {
+    fn is_wildcard(&self) -> bool {
+        matches!(self.address.as_str(), "*" | "0.0.0.0" | "::")
+    }
+
+    fn binds(&self, ip: Ipv4Addr) -> bool {
+        self.is_wildcard() || self.address.parse() == Ok(ip)
+    }
+}
+
+impl fmt::Display for Listener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.address.contains(':') {
+            write!(f, "[{}]:{}", self.address, self.port)
+        } else {
+            write!(f, "{}:{}", self.address, self.port)
+        }
+    }
+}
+
+// Listening sockets from `ss -ltn[p]` output, the header and other states skipped
+pub fn parse_ss(output: &str) -> Vec<Listener> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            if columns.next()? != "LISTEN" {
+                return None;
+            }
+            let local = columns.nth(2)?;
+            let (address, port) = local.rsplit_once(':')?;
+            let address = address.trim_start_matches('[').trim_end_matches(']');
+            // `127.0.0.53%lo` names the interface the socket is bound to
+            let address = address.split('%').next().unwrap_or(address);
+            Some(Listener {
+                address: address.to_string(),
+                port: port.parse().ok()?,
+                process: columns.skip(1).collect::<Vec<_>>().join(" "),
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditStatus {
+    Ok,
+    Mismatch {
+        // The p2p port is not listening on the node's address
+        missing: bool,
+        // The p2p port listens, but on other addresses only
+        elsewhere: Vec<Listener>,
+        // Listeners in portgen's ports that are not this node's
+        unexpected: Vec<Listener>,
+    },
+    // ssh could not connect or authenticate
+    Unreachable(String),
+    // Connected, but the command failed or printed no listeners
+    Command(String),
+}
+
+impl AuditStatus {
+    // Distinct exit codes, 1 and 2 stay reserved for errors and usage errors
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Ok => 0,
+            Self::Mismatch { .. } => 3,
+            Self::Unreachable(_) => 4,
+            Self::Command(_) => 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AuditReport {
+    pub name: String,
+    pub address: NodeAddress,
+    pub status: AuditStatus,
+}
+
+fn joined(listeners: &[Listener]) -> String {
+    let listeners: Vec<String> = listeners
+        .iter()
+        .map(|listener| match listener.process.as_str() {
+            "" => listener.to_string(),
+            process => format!("{listener} {process}"),
+        })
+        .collect();
+    listeners.join(", ")
+}
+
+impl fmt::Display for AuditReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} ", self.name, self.address.ip)?;
+        match &self.status {
+            AuditStatus::Ok => write!(f, "ok p2p {}", self.address.port),
+            AuditStatus::Mismatch {
+                missing,
+                elsewhere,
+                unexpected,
+            } => {
+                let mut problems = Vec::new();
+                if *missing && elsewhere.is_empty() {
+                    problems.push(format!("p2p {} not listening", self.address.port));
+                } else if *missing {
+                    problems.push(format!(
+                        "p2p {} listening on {} instead",
+                        self.address.port,
+                        joined(elsewhere)
+                    ));
+                }
+                if !unexpected.is_empty() {
+                    problems.push(format!("unexpected {}", joined(unexpected)));
+                }
+                write!(f, "mismatch ({})", problems.join("; "))
+            }
+            AuditStatus::Unreachable(e) => write!(f, "unreachable ({e})"),
+            AuditStatus::Command(e) => write!(f, "command failed ({e})"),
+        }
+    }
+}
+
+impl AuditReport {
+    pub fn to_json(&self) -> Value {
+        let listeners = |listeners: &[Listener]| -> Vec<Value> {
+            listeners
+                .iter()
+                .map(|listener| {
+                    json!({
+                        "address": listener.address,
+                        "port": listener.port,
+                        "process": listener.process,
+                    })
+                })
+                .collect()
+        };
+        let mut value = json!({
+            "name": self.name,
+            "ip": self.address.ip.to_string(),
+            "port": self.address.port.0,
+            "exit_code": self.status.exit_code(),
+        });
+        let fields = match &self.status {
+            AuditStatus::Ok => json!({ "status": "ok" }),
+            AuditStatus::Mismatch {
+                missing,
+                elsewhere,
+                unexpected,
+            } => json!({
+                "status": "mismatch",
+                "missing": missing,
+                "elsewhere": listeners(elsewhere),
+                "unexpected": listeners(unexpected),
+            }),
+            AuditStatus::Unreachable(e) => json!({ "status": "unreachable", "error": e }),
+            AuditStatus::Command(e) => json!({ "status": "command", "error": e }),
+        };
+        if let (Value::Object(value), Value::Object(fields)) = (&mut value, fields) {
+            value.extend(fields);
+        }
+        value
+    }
+}
+
+// The p2p port must listen on the node's address (or any address); its other
+// ports may listen anywhere, every other port from PORT_BASE up is unexpected
+pub fn evaluate(
+    address: &NodeAddress,
+    listeners: &[Listener],
+) -> Result<AuditStatus, PortgenError> {
+    let mut own = Vec::new();
+    for kind in PortKind::ALL {
+        own.push(address.port_of(kind)?.0);
+    }
+    let p2p: Vec<&Listener> = listeners
+        .iter()
+        .filter(|listener| listener.port == address.port.0)
+        .collect();
+    let missing = !p2p.iter().any(|listener| listener.binds(address.ip));
+    let elsewhere = if missing {
+        p2p.into_iter().cloned().collect()
+    } else {
+        Vec::new()
+    };
+    let mut unexpected: Vec<Listener> = listeners
+        .iter()
+        .filter(|listener| listener.port >= PORT_BASE && !own.contains(&listener.port))
+        .cloned()
+        .collect();
+    unexpected.sort_by_key(|listener| listener.port);
+    unexpected.dedup();
+    if !missing && unexpected.is_empty() {
+        return Ok(AuditStatus::Ok);
+    }
+    Ok(AuditStatus::Mismatch {
+        missing,
+        elsewhere,
+        unexpected,
+    })
+}
+
+pub struct AuditOptions<'a> {
+    pub ssh: &'a SshOptions,
+    // Run through the remote shell, `ss -ltnp` by default
+    pub command: &'a str,
+    pub timeout: Duration,
+}
+
+fn last_line(bytes: &[u8]) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    text.lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or("no output")
+        .trim()
+        .to_string()
+}
+
+fn probe(
+    name: &str,
+    address: &NodeAddress,
+    options: &AuditOptions,
+) -> Result<AuditStatus, PortgenError> {
+    // Never prompt, a password or host key question would hang the worker
+    let mut args = vec![
+        "-o".to_string(),
+        "BatchMode=yes".to_string(),
+        "-o".to_string(),
+        format!("ConnectTimeout={}", options.timeout.as_secs().max(1)),
+    ];
+    args.extend(ssh_args(name, options.ssh, &[options.command.to_string()])?);
+    let output = match Command::new("ssh").args(&args).output() {
+        Ok(output) => output,
+        Err(_) => return Err("failed to run ssh".into()),
+    };
+    match output.status.code() {
+        Some(0) => {}
+        Some(SSH_FAILURE) | None => {
+            return Ok(AuditStatus::Unreachable(last_line(&output.stderr)));
+        }
+        Some(code) => {
+            return Ok(AuditStatus::Command(format!(
+                "exit {code}: {}",
+                last_line(&output.stderr)
+            )));
+        }
+    }
+    let listeners = parse_ss(&String::from_utf8_lossy(&output.stdout));
+    if listeners.is_empty() {
+        return Ok(AuditStatus::Command(
+            "no listening sockets in the output".to_string(),
+        ));
+    }
+    evaluate(address, &listeners)
+}
+
+pub fn audit_node(name: &str, options: &AuditOptions) -> Result<AuditReport, PortgenError> {
+    let name = name.trim_end_matches(".yaml");
+    let address = calculate_address(name)?;
+    Ok(AuditReport {
+        name: name.to_string(),
+        status: probe(name, &address, options)?,
+        address,
+    })
+}
+
+pub fn audit_all(
+    names: &[String],
+    options: &AuditOptions,
+    jobs: usize,
+) -> Result<Vec<AuditReport>, PortgenError> {
+    pool::map_ordered(names, jobs, |name| audit_node(name, options))
+        .ok_or("audit worker panicked")?
+        .into_iter()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Port;
+
+    const SS: &str = "\
+State  Recv-Q Send-Q      Local Address:Port  Peer Address:Port Process
+LISTEN 0      4096       192.168.111.10:31001      0.0.0.0:*     users:((\"polkadot\",pid=812,fd=23))
+LISTEN 0      4096            127.0.0.1:41001      0.0.0.0:*     users:((\"polkadot\",pid=812,fd=25))
+LISTEN 0      4096        127.0.0.53%lo:53         0.0.0.0:*
+LISTEN 0      128                     *:31999            *:*     users:((\"nc\",pid=99,fd=3))
+LISTEN 0      4096                 [::]:22            [::]:*
+";
+
+    fn address() -> NodeAddress {
+        NodeAddress {
+            port: Port(31001),
+            ip: Ipv4Addr::new(192, 168, 111, 10),
+        }
+    }
+
+    #[test]
+    fn parses_ss_and_flags_foreign_ports() {
+        let listeners = parse_ss(SS);
+        assert_eq!(listeners.len(), 5);
+        assert_eq!(listeners[2].address, "127.0.0.53");
+        assert_eq!(listeners[4].address, "::");
+        assert_eq!(listeners[0].process, "users:((\"polkadot\",pid=812,fd=23))");
+
+        let AuditStatus::Mismatch {
+            missing,
+            unexpected,
+            ..
+        } = evaluate(&address(), &listeners).unwrap()
+        else {
+            panic!("expected a mismatch");
+        };
+        assert!(!missing);
+        assert_eq!(unexpected, vec![listeners[3].clone()]);
+        assert_eq!(
+            evaluate(&address(), &listeners[..3]).unwrap(),
+            AuditStatus::Ok
+        );
+    }
+
+    #[test]
+    fn p2p_on_another_address_is_missing() {
+        let listeners = parse_ss("LISTEN 0 4096 127.0.0.1:31001 0.0.0.0:*\n");
+        let status = evaluate(&address(), &listeners).unwrap();
+        assert_eq!(
+            status,
+            AuditStatus::Mismatch {
+                missing: true,
+                elsewhere: listeners,
+                unexpected: Vec::new(),
+            }
+        );
+        assert_eq!(status.exit_code(), 3);
+    }
+}
@@ -0,0 +1,76 @@
+// Template rendering of whole config files from node contexts, with strict
+// undefined handling so a typo in a variable name fails instead of rendering
+// an empty string
+use crate::{k8s, ChainId, Network, NodeInfo, NodeName, PortKind, PortgenError};
+use minijinja::{syntax::SyntaxConfig, value::Serde, Environment, UndefinedBehavior};
+use serde_json::{json, Map, Value};
+
+pub fn context(node: &NodeInfo) -> Result<Value, PortgenError> {
+    let parsed = NodeName::parse(&node.name)?;
+    let chain = ChainId::from_str(parsed.chain.as_deref())?.name()?;
+    let mut ports = Map::new();
+    for kind in PortKind::ALL {
+        ports.insert(kind.to_string(), node.address.port_of(kind)?.0.into());
+    }
+    Ok(json!({
+        "name": node.name.trim_end_matches(".yaml"),
+        "role": parsed.role,
+        "chain": chain.unwrap_or("relay"),
+        "network": parsed.network.parse::<Network>()?.to_string(),
+        "instance": parsed.instance,
+        "ip": node.address.ip.to_string(),
+        "port": node.address.port.0,
+        "address": node.address.to_string(),
+        "ports": ports,
+        "labels": k8s::labels(node)?,
+    }))
+}
+
+// 1-based column of byte `offset` in `source`
+fn column(source: &str, offset: usize) -> usize {
+    let line_start = source[..offset.min(source.len())]
+        .rfind('\n')
+        .map_or(0, |i| i + 1);
+    source[line_start..offset.min(source.len())].chars().count() + 1
+}
+
+fn template_error(name: &str, source: &str, e: &minijinja::Error) -> PortgenError {
+    let mut location = name.to_string();
+    if let Some(line) = e.line() {
+        location.push_str(&format!(":{line}"));
+        if let Some(range) = e.range() {
+            location.push_str(&format!(":{}", column(source, range.start)));
+        }
+    }
+    let message = match e.detail() {
+        Some(detail) => format!("{}: {detail}", e.kind()),
+        None => e.kind().to_string(),
+    };
+    PortgenError::Template { location, message }
+}
+
+// A single node is also exposed at the top level; `nodes` is always there so
+// aggregate templates work for one node too
+pub fn render(name: &str, source: &str, nodes: &[NodeInfo]) -> Result<String, PortgenError> {
+    let contexts = nodes.iter().map(context).collect::<Result<Vec<_>, _>>()?;
+    let mut ctx = match contexts.as_slice() {
+        [Value::Object(single)] => single.clone(),
+        _ => Map::new(),
+    };
+    ctx.insert("nodes".into(), Value::Array(contexts));
+
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    // Block tags on their own line leave no blank line behind
+    let syntax = SyntaxConfig::builder()
+        .trim_blocks(true)
+        .keep_trailing_newline(true)
+        .build()
+        .map_err(|_| "invalid template syntax config")?;
+    env.set_syntax(syntax);
+    env.add_template(name, source)
+        .map_err(|e| template_error(name, source, &e))?;
+    env.get_template(name)
+        .and_then(|template| template.render(Serde(Value::Object(ctx))))
+        .map_err(|e| template_error(name, source, &e))
+}
@@ -0,0 +1,235 @@
+// Self-contained HTML allocation report: no external assets, so it can be
+// dropped on an airgapped wiki. Tables are rendered server-side; the data
+// behind them is embedded as JSON for the search box and for scraping.
+use crate::{
+    canonical_name, chains, enumerate_chains, inventory,
+    lint::{Finding, Severity},
+    selection::Selection,
+    ChainId, Network, NodeInfo, NodeName, PortgenError,
+};
+use serde_json::{json, Value};
+use std::{collections::HashSet, fmt::Write};
+
+// Data the page is rendered from, also embedded verbatim
+pub fn data(selection: &Selection, findings: &[Finding]) -> Result<Value, PortgenError> {
+    let deployed = selection.resolve()?.nodes;
+    let slots = Selection {
+        dir: None,
+        ..selection.clone()
+    }
+    .resolve()?
+    .nodes;
+    let deployed_slots: HashSet<String> = deployed
+        .iter()
+        .map(|name| canonical_name(name))
+        .collect::<Result<_, _>>()?;
+
+    let mut networks = Vec::new();
+    for network in Network::ALL {
+        let on_network = |name: &&String| {
+            NodeName::parse(name)
+                .and_then(|node| node.network.parse::<Network>())
+                .is_ok_and(|n| n == network)
+        };
+        let nodes = deployed
+            .iter()
+            .filter(on_network)
+            .map(|name| inventory::record(&NodeInfo::new(name.clone())?, None))
+            .collect::<Result<Vec<_>, _>>()?;
+        let slots: Vec<Value> = slots
+            .iter()
+            .filter(on_network)
+            .map(|name| json!({ "name": name, "deployed": deployed_slots.contains(name) }))
+            .collect();
+        if !nodes.is_empty() || !slots.is_empty() {
+            networks.push(json!({
+                "network": network.to_string(),
+                "nodes": nodes,
+                "slots": slots,
+            }));
+        }
+    }
+
+    let chains = enumerate_chains()
+        .map(|chain| {
+            let id = ChainId::from_str(chain)?;
+            let aliases = chains::table()
+                .iter()
+                .find(|(chain_id, _)| *chain_id == id.0)
+                .map_or(&[][..], |(_, names)| &names[1..]);
+            Ok(json!({
+                "id": id.0,
+                "name": chain.unwrap_or("relay"),
+                "aliases": aliases,
+                "type": id.chain_type().to_string(),
+            }))
+        })
+        .collect::<Result<Vec<_>, PortgenError>>()?;
+
+    let findings: Vec<Value> = findings
+        .iter()
+        .map(|finding| {
+            json!({
+                "path": finding.path.display().to_string(),
+                "severity": if finding.severity == Severity::Error { "error" } else { "warning" },
+                "message": finding.message,
+            })
+        })
+        .collect();
+
+    Ok(json!({ "networks": networks, "chains": chains, "findings": findings }))
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::String(s) => escape(s),
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(cell).collect();
+            items.join(", ")
+        }
+        other => escape(&other.to_string()),
+    }
+}
+
+fn table(out: &mut String, headers: &[&str], rows: &[Value]) {
+    let _ = writeln!(out, "<table class=\"filterable\">\n<tr>");
+    for header in headers {
+        let _ = write!(out, "<th>{header}</th>");
+    }
+    let _ = writeln!(out, "</tr>");
+    for row in rows {
+        let _ = write!(out, "<tr>");
+        for header in headers {
+            let _ = write!(out, "<td>{}</td>", cell(&row[*header]));
+        }
+        let _ = writeln!(out, "</tr>");
+    }
+    let _ = writeln!(out, "</table>");
+}
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em}\
+table{border-collapse:collapse;margin-bottom:1.5em}\
+th,td{border:1px solid #ccc;padding:2px 8px;text-align:left}\
+.slots span{display:inline-block;margin:1px;padding:1px 4px;font-size:80%}\
+.deployed{background:#9d9}.free{background:#eee}\
+.error{color:#b00}.warning{color:#a60}";
+
+// Hides table rows and slots that do not contain the search text
+const SCRIPT: &str = "document.getElementById('search').addEventListener('input',function(e){\
+var q=e.target.value.toLowerCase();\
+document.querySelectorAll('table.filterable tr:not(:first-child), .slots span').forEach(function(el){\
+el.style.display=el.textContent.toLowerCase().indexOf(q)<0?'none':'';});});";
+
+pub fn render(data: &Value) -> Result<String, PortgenError> {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">"
+    );
+    let _ = writeln!(
+        out,
+        "<title>portgen allocations</title>\n<style>{STYLE}</style>"
+    );
+    let _ = writeln!(out, "</head>\n<body>\n<h1>portgen allocations</h1>");
+    let _ = writeln!(
+        out,
+        "<input id=\"search\" type=\"search\" placeholder=\"filter by name, ip, port...\">"
+    );
+
+    let findings = data["findings"].as_array().map_or(&[][..], Vec::as_slice);
+    if !findings.is_empty() {
+        let _ = writeln!(out, "<h2>Findings</h2>\n<ul>");
+        for finding in findings {
+            let severity = finding["severity"].as_str().unwrap_or("error");
+            let _ = writeln!(
+                out,
+                "<li class=\"{severity}\">{}: {severity}: {}</li>",
+                cell(&finding["path"]),
+                cell(&finding["message"])
+            );
+        }
+        let _ = writeln!(out, "</ul>");
+    }
+
+    for network in data["networks"].as_array().map_or(&[][..], Vec::as_slice) {
+        let name = cell(&network["network"]);
+        let _ = writeln!(out, "<h2>{name}</h2>");
+        let nodes = network["nodes"].as_array().map_or(&[][..], Vec::as_slice);
+        table(
+            &mut out,
+            &["name", "address", "ip", "port", "chain_type"],
+            nodes,
+        );
+        let _ = writeln!(out, "<h3>{name} slots</h3>\n<div class=\"slots\">");
+        for slot in network["slots"].as_array().map_or(&[][..], Vec::as_slice) {
+            let class = if slot["deployed"].as_bool() == Some(true) {
+                "deployed"
+            } else {
+                "free"
+            };
+            let _ = write!(
+                out,
+                "<span class=\"{class}\">{}</span>",
+                cell(&slot["name"])
+            );
+        }
+        let _ = writeln!(out, "\n</div>");
+    }
+
+    let _ = writeln!(out, "<h2>Chain registry</h2>");
+    let chains = data["chains"].as_array().map_or(&[][..], Vec::as_slice);
+    table(&mut out, &["id", "name", "aliases", "type"], chains);
+
+    // `</` would end the script element early
+    let blob = serde_json::to_string(data)
+        .map_err(|_| "failed to serialize report data")?
+        .replace("</", "<\\/");
+    let _ = writeln!(
+        out,
+        "<script type=\"application/json\" id=\"portgen-data\">{blob}</script>"
+    );
+    let _ = writeln!(out, "<script>{SCRIPT}</script>\n</body>\n</html>");
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn page_contains_every_deployed_node() {
+        let dir = std::env::temp_dir().join(format!("portgen-report-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let deployed = [
+            "boot-polkadot-00",
+            "rpc-asset-hub-kusama-02",
+            "val-westend-05",
+            "col-people-paseo-01",
+        ];
+        for name in deployed {
+            fs::write(dir.join(format!("{name}.yaml")), "").unwrap();
+        }
+
+        let selection = Selection {
+            dir: Some(dir.clone()),
+            ..Selection::default()
+        };
+        let page = render(&data(&selection, &[]).unwrap()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        for name in deployed {
+            assert!(
+                page.contains(&format!("<td>{name}</td>")),
+                "{name} missing from the report"
+            );
+        }
+    }
+}
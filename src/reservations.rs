@@ -0,0 +1,277 @@
+// Reserved node slots, kept in a JSON state file so an instance can be spoken
+// for (or quarantined after decommissioning) before its node file exists
+use crate::{
+    atomic::write_atomic, calculate_address, canonical_name, compose_name, time, Network,
+    PortgenError, RoleConstraints,
+};
+use serde_json::{json, Value};
+use std::{
+    fmt,
+    fs::{self, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reservation {
+    // Canonical node name
+    pub name: String,
+    pub by: String,
+    // RFC 3339 UTC timestamp
+    pub at: String,
+    pub reason: String,
+}
+
+impl Reservation {
+    pub fn age_secs(&self, now: i64) -> Option<i64> {
+        time::parse_rfc3339(&self.at).map(|at| now - at)
+    }
+
+    fn to_json(&self) -> Value {
+        json!({ "name": self.name, "by": self.by, "at": self.at, "reason": self.reason })
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        let field = |key: &str| value.get(key)?.as_str().map(str::to_string);
+        Some(Self {
+            name: field("name")?,
+            by: field("by")?,
+            at: field("at")?,
+            reason: field("reason").unwrap_or_default(),
+        })
+    }
+}
+
+impl fmt::Display for Reservation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "reserved by {} since {}", self.by, self.at)?;
+        if !self.reason.is_empty() {
+            write!(f, ": {}", self.reason)?;
+        }
+        Ok(())
+    }
+}
+
+// Name reservations are made under, $USER when it is set
+pub fn current_user() -> String {
+    std::env::var("USER").unwrap_or_else(|_| "unknown".to_string())
+}
+
+pub fn load(path: &Path) -> Result<Vec<Reservation>, PortgenError> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(_) => return Err("failed to read reservations file".into()),
+    };
+    let doc: Value = serde_json::from_str(&text).map_err(|_| "corrupt reservations file")?;
+    doc.as_array()
+        .ok_or("corrupt reservations file")?
+        .iter()
+        .map(|entry| Reservation::from_json(entry).ok_or("corrupt reservations file".into()))
+        .collect()
+}
+
+fn save(path: &Path, reservations: &[Reservation]) -> Result<(), PortgenError> {
+    let doc = Value::Array(reservations.iter().map(Reservation::to_json).collect());
+    let text = serde_json::to_string_pretty(&doc).map_err(|_| "failed to serialize")? + "\n";
+    write_atomic(path, &text).map_err(|_| "failed to write reservations file".into())
+}
+
+// Runs a read-modify-write of the state file under an exclusive lock on a
+// sidecar file, so the state file itself can still be replaced atomically
+fn update<T>(
+    path: &Path,
+    change: impl FnOnce(&mut Vec<Reservation>) -> Result<T, PortgenError>,
+) -> Result<T, PortgenError> {
+    let mut lock_path = PathBuf::from(path);
+    lock_path.as_mut_os_string().push(".lock");
+    let lock = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(|_| "failed to open reservations lock")?;
+    lock.lock()
+        .map_err(|_| "failed to lock reservations file")?;
+
+    let result = load(path).and_then(|mut reservations| {
+        let value = change(&mut reservations)?;
+        save(path, &reservations)?;
+        Ok(value)
+    });
+    let _ = lock.unlock();
+    result
+}
+
+pub fn reserve(
+    path: &Path,
+    node_str: &str,
+    by: &str,
+    reason: &str,
+) -> Result<Reservation, PortgenError> {
+    calculate_address(node_str)?;
+    let name = canonical_name(node_str)?;
+    update(path, |reservations| {
+        if reservations.iter().any(|r| r.name == name) {
+            return Err("node is already reserved".into());
+        }
+        let reservation = Reservation {
+            name,
+            by: by.to_string(),
+            at: time::now_rfc3339(),
+            reason: reason.to_string(),
+        };
+        reservations.push(reservation.clone());
+        Ok(reservation)
+    })
+}
+
+// Only the holder releases a reservation unless `force` is set
+pub fn release(
+    path: &Path,
+    node_str: &str,
+    by: &str,
+    force: bool,
+) -> Result<Reservation, PortgenError> {
+    let name = canonical_name(node_str)?;
+    update(path, |reservations| {
+        let index = reservations
+            .iter()
+            .position(|r| r.name == name)
+            .ok_or("node is not reserved")?;
+        if reservations[index].by != by && !force {
+            return Err("node is reserved by someone else (use --force)".into());
+        }
+        Ok(reservations.remove(index))
+    })
+}
+
+// The reservation on a node held by anyone but `user`
+pub fn held_by_other<'a>(
+    reservations: &'a [Reservation],
+    node_str: &str,
+    user: &str,
+) -> Result<Option<&'a Reservation>, PortgenError> {
+    let name = canonical_name(node_str)?;
+    Ok(reservations.iter().find(|r| r.name == name && r.by != user))
+}
+
+// The first slot of a role on a chain and network that no deployed node
+// (`taken`, canonical names) holds and nobody has reserved
+pub fn next_free(
+    role: &str,
+    chain: Option<&str>,
+    network: Network,
+    taken: &[String],
+    reservations: &[Reservation],
+) -> Result<Option<String>, PortgenError> {
+    let constraints = RoleConstraints::get(role).ok_or("unknown role")?;
+    for instance in constraints.instances.clone() {
+        let name = canonical_name(&compose_name(role, chain, network, instance))?;
+        if !taken.contains(&name) && !reservations.iter().any(|r| r.name == name) {
+            return Ok(Some(name));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Barrier, thread};
+
+    fn state_file(test: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "portgen-reservations-{test}-{}.json",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn reserve_release_and_next_free() {
+        let path = state_file("cycle");
+        let held = reserve(&path, "rpc-statemint-polkadot-02", "alice", "racking").unwrap();
+        assert_eq!(held.name, "rpc-asset-hub-polkadot-02");
+        assert_eq!(
+            reserve(&path, "rpc-asset-hub-polkadot-02", "bob", "").unwrap_err(),
+            PortgenError::Invalid("node is already reserved")
+        );
+
+        let loaded = load(&path).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0], held);
+        // Conflicts are someone else's reservations only
+        assert_eq!(
+            held_by_other(&loaded, "rpc-asset-hub-polkadot-02", "bob").unwrap(),
+            Some(&held)
+        );
+        assert_eq!(
+            held_by_other(&loaded, "rpc-asset-hub-polkadot-02", "alice").unwrap(),
+            None
+        );
+
+        // 01 is deployed and 02 reserved, so 03 is next
+        let deployed = ["rpc-asset-hub-polkadot-01".to_string()];
+        assert_eq!(
+            next_free(
+                "rpc",
+                Some("asset-hub"),
+                Network::Polkadot,
+                &deployed,
+                &loaded
+            )
+            .unwrap(),
+            Some("rpc-asset-hub-polkadot-03".to_string())
+        );
+
+        assert_eq!(
+            release(&path, "rpc-asset-hub-polkadot-02", "bob", false).unwrap_err(),
+            PortgenError::Invalid("node is reserved by someone else (use --force)")
+        );
+        release(&path, "rpc-asset-hub-polkadot-02", "bob", true).unwrap();
+        assert_eq!(load(&path).unwrap(), []);
+        let loaded = load(&path).unwrap();
+        assert_eq!(
+            next_free(
+                "rpc",
+                Some("asset-hub"),
+                Network::Polkadot,
+                &deployed,
+                &loaded
+            )
+            .unwrap(),
+            Some("rpc-asset-hub-polkadot-02".to_string())
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn concurrent_reservations_are_all_kept() {
+        let path = state_file("lock");
+        let barrier = Barrier::new(8);
+        thread::scope(|scope| {
+            for (network, instance) in ["polkadot", "kusama"]
+                .iter()
+                .flat_map(|n| (1..=4).map(move |i| (n, i)))
+            {
+                let (path, barrier) = (&path, &barrier);
+                scope.spawn(move || {
+                    barrier.wait();
+                    let name = format!("val-{network}-{instance:02}");
+                    reserve(path, &name, "alice", "").unwrap();
+                });
+            }
+        });
+        assert_eq!(load(&path).unwrap().len(), 8);
+        // The lock is released afterwards
+        reserve(&path, "rpc-kusama-01", "alice", "").unwrap();
+        assert_eq!(load(&path).unwrap().len(), 9);
+
+        let mut lock_path = path.clone().into_os_string();
+        lock_path.push(".lock");
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(lock_path).unwrap();
+    }
+}
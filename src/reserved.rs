@@ -0,0 +1,48 @@
+// `--reserved-nodes` peer sets for validators
+use crate::{enumerate_chain_nodes, ChainId, Network, NodeInfo, NodeName, PortgenError};
+use std::collections::HashMap;
+
+// Roles whose nodes a validator peers with when no policy is given
+pub const DEFAULT_PEER_ROLES: &[&str] = &["boot"];
+
+// `<node-name> <peer-id>` per line (`=` works as separator too), `#` comments
+pub fn parse_peer_ids(text: &str) -> Result<HashMap<String, String>, PortgenError> {
+    let mut peer_ids = HashMap::new();
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut parts = line.splitn(2, |c: char| c == '=' || c.is_whitespace());
+        let name = parts.next().unwrap_or("").trim();
+        let peer_id = parts.next().unwrap_or("").trim();
+        if name.is_empty() || peer_id.is_empty() {
+            return Err("peer id lines must be `<node-name> <peer-id>`".into());
+        }
+        peer_ids.insert(
+            name.trim_end_matches(".yaml").to_string(),
+            peer_id.to_string(),
+        );
+    }
+    Ok(peer_ids)
+}
+
+// Nodes of the validator's own chain and network holding one of `roles`
+pub fn reserved_peers(validator: &str, roles: &[String]) -> Result<Vec<NodeInfo>, PortgenError> {
+    let node = NodeName::parse(validator)?;
+    if !matches!(node.role.as_str(), "val" | "col") {
+        return Err("reserved nodes are generated for val or col nodes".into());
+    }
+    let network = node.network.parse::<Network>()?;
+    let chain = ChainId::from_str(node.chain.as_deref())?.name()?;
+
+    enumerate_chain_nodes(network, chain)
+        .into_iter()
+        .filter(|name| NodeName::parse(name).is_ok_and(|peer| roles.contains(&peer.role)))
+        .map(NodeInfo::new)
+        .collect()
+}
+
+pub fn multiaddr(node: &NodeInfo, peer_id: &str) -> String {
+    format!("{}/p2p/{peer_id}", node.address.multiaddr())
+}
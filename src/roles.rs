@@ -0,0 +1,254 @@
+// Roles from the config file merged with the built-in ones. The merged table
+// is installed once at start-up, before any name is parsed, and everything
+// role-related reads it through `table`.
+use crate::{synonyms, Network, PortgenError, RoleConstraints, ROLE_CONSTRAINTS};
+use std::{ops::RangeInclusive, sync::OnceLock};
+
+// What the encoding can hold: one port digit and one instance digit, and a
+// third IP octet of {ip digit}{network}{instance} that stays below 256
+pub const PORT_DIGITS: RangeInclusive<u16> = 0..=9;
+pub const INSTANCES: RangeInclusive<u8> = 0..=9;
+pub const IP_DIGITS: RangeInclusive<u8> = 0..=2;
+
+// A `[roles.<token>]` table of the config file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoleDef {
+    pub token: String,
+    pub name: Option<String>,
+    pub instances: RangeInclusive<u8>,
+    // One per instance, in order
+    pub port_digits: RangeInclusive<u16>,
+    pub ip_digit: u8,
+    pub parachain_only: bool,
+    // Needed to redefine a built-in role
+    pub override_builtin: bool,
+    // `file:line` of the table
+    pub location: String,
+}
+
+struct Table {
+    roles: Vec<RoleConstraints>,
+    // `builtin` or the location of the definition, per role
+    sources: Vec<String>,
+}
+
+static TABLE: OnceLock<Table> = OnceLock::new();
+
+// The effective roles: the built-in ones until `install` ran
+pub fn table() -> &'static [RoleConstraints] {
+    TABLE.get().map_or(ROLE_CONSTRAINTS, |table| &table.roles)
+}
+
+// Where the role was defined, `builtin` or `file:line`
+pub fn source(abbrev: &str) -> Option<&'static str> {
+    match TABLE.get() {
+        Some(table) => table
+            .roles
+            .iter()
+            .position(|role| role.abbrev == abbrev)
+            .map(|i| table.sources[i].as_str()),
+        None => RoleConstraints::get(abbrev).map(|_| "builtin"),
+    }
+}
+
+fn def_error(def: &RoleDef, message: String) -> PortgenError {
+    PortgenError::Config {
+        location: def.location.clone(),
+        key: Some(format!("roles.{}", def.token)),
+        message,
+    }
+}
+
+fn check_def(def: &RoleDef) -> Result<(), PortgenError> {
+    let mut chars = def.token.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_lowercase())
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit());
+    if !valid {
+        return Err(def_error(
+            def,
+            "role tokens are a lowercase letter followed by letters and digits".to_string(),
+        ));
+    }
+    if def.token.parse::<Network>().is_ok() {
+        return Err(def_error(def, "role token is a network name".to_string()));
+    }
+    if let Some((_, role)) = synonyms::BUILTIN.iter().find(|(s, _)| *s == def.token) {
+        return Err(def_error(
+            def,
+            format!("role token is a built-in synonym of '{role}'"),
+        ));
+    }
+    if def.instances.is_empty() || !INSTANCES.contains(def.instances.end()) {
+        return Err(def_error(def, "instances must be within 0-9".to_string()));
+    }
+    if def.port_digits.is_empty() || !PORT_DIGITS.contains(def.port_digits.end()) {
+        return Err(def_error(def, "port digits must be within 0-9".to_string()));
+    }
+    if def.port_digits.len() != def.instances.len() {
+        return Err(def_error(
+            def,
+            format!(
+                "{} instances need {0} port digits, got {}",
+                def.instances.len(),
+                def.port_digits.len()
+            ),
+        ));
+    }
+    // Port digits are `offset + instance`, like the built-in roles'
+    if def.port_digits.start() < &u16::from(*def.instances.start()) {
+        return Err(def_error(
+            def,
+            "the first port digit can't be below the first instance".to_string(),
+        ));
+    }
+    if !IP_DIGITS.contains(&def.ip_digit) {
+        return Err(def_error(def, "ip digit must be 0-2".to_string()));
+    }
+    Ok(())
+}
+
+fn constraints(def: &RoleDef) -> RoleConstraints {
+    let token: &'static str = Box::leak(def.token.clone().into_boxed_str());
+    let name = def.name.clone().unwrap_or_else(|| def.token.clone());
+    RoleConstraints {
+        abbrev: token,
+        name: Box::leak(name.into_boxed_str()),
+        instances: def.instances.clone(),
+        slots: Some(def.instances.clone()),
+        port_offset: def.port_digits.start() - u16::from(*def.instances.start()),
+        instance_in_port: true,
+        ip_digit: def.ip_digit,
+        parachain_only: def.parachain_only,
+    }
+}
+
+// (ip digit, instance) pairs of every valid name of the role; roles without
+// slots reuse another role's on purpose
+fn footprint(role: &RoleConstraints) -> Vec<(u8, u8)> {
+    if role.slots.is_none() {
+        return Vec::new();
+    }
+    role.ip_instances().map(|i| (role.ip_digit, i)).collect()
+}
+
+// The built-in roles with `defs` merged in, a redefined built-in keeping its
+// place, in the order the table would be installed with
+pub fn merge(defs: &[RoleDef]) -> Result<Vec<(RoleConstraints, String)>, PortgenError> {
+    let mut merged: Vec<(RoleConstraints, String)> = ROLE_CONSTRAINTS
+        .iter()
+        .map(|role| (role.clone(), "builtin".to_string()))
+        .collect();
+    for def in defs {
+        check_def(def)?;
+        let role = (constraints(def), def.location.clone());
+        match merged
+            .iter()
+            .position(|(known, _)| known.abbrev == def.token)
+        {
+            Some(i) if ROLE_CONSTRAINTS.iter().any(|b| b.abbrev == def.token) => {
+                if !def.override_builtin {
+                    return Err(def_error(
+                        def,
+                        "redefines a built-in role, set `override = true` to do so".to_string(),
+                    ));
+                }
+                merged[i] = role;
+            }
+            Some(i) => merged[i] = role,
+            None => merged.push(role),
+        }
+    }
+
+    // Two roles on one ip digit and instance would give nodes of the same
+    // chain and network the same IP, and reverse lookups two answers. Port
+    // digits may be shared, like col shares val's: the IPs tell them apart.
+    for (i, (role, location)) in merged.iter().enumerate() {
+        let ips = footprint(role);
+        for (other, other_location) in &merged[..i] {
+            let other_ips = footprint(other);
+            let Some((digit, instance)) = ips.iter().find(|p| other_ips.contains(p)) else {
+                continue;
+            };
+            let clash = format!("ip digit {digit} with instance {instance}");
+            // Blamed on the configured one of the two
+            let (role, location, other) = if location == "builtin" {
+                (other, other_location, role)
+            } else {
+                (role, location, other)
+            };
+            return Err(PortgenError::Config {
+                location: location.clone(),
+                key: Some(format!("roles.{}", role.abbrev)),
+                message: format!("{clash} is already used by role '{}'", other.abbrev),
+            });
+        }
+    }
+    Ok(merged)
+}
+
+// Merges `defs` and installs the result for the rest of the process
+pub fn install(defs: &[RoleDef]) -> Result<(), PortgenError> {
+    let (roles, sources) = merge(defs)?.into_iter().unzip();
+    TABLE
+        .set(Table { roles, sources })
+        .map_err(|_| "roles are already installed".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn def(token: &str, instances: RangeInclusive<u8>, ports: RangeInclusive<u16>) -> RoleDef {
+        RoleDef {
+            token: token.to_string(),
+            name: None,
+            instances,
+            port_digits: ports,
+            ip_digit: 2,
+            parachain_only: false,
+            override_builtin: false,
+            location: "config.toml:1".to_string(),
+        }
+    }
+
+    #[test]
+    fn merges_roles_without_overlaps() {
+        // Port digits may be shared, IP slots may not
+        assert!(merge(&[def("arch", 7..=9, 7..=9)]).is_ok());
+        let arch = def("arch", 4..=6, 7..=9);
+        let err = merge(std::slice::from_ref(&arch)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "config error at config.toml:1: roles.arch: ip digit 2 with instance 4 is already used by role 'val'"
+        );
+
+        let val = def("val", 1..=3, 4..=6);
+        let err = merge(&[val.clone(), arch.clone()]).unwrap_err();
+        assert!(err.to_string().ends_with("set `override = true` to do so"));
+
+        let val = RoleDef {
+            override_builtin: true,
+            ..val
+        };
+        let merged = merge(&[val, arch]).unwrap();
+        let abbrevs: Vec<&str> = merged.iter().map(|(role, _)| role.abbrev).collect();
+        assert_eq!(abbrevs, ["boot", "rpc", "val", "col", "arch"]);
+        let (arch, source) = &merged[4];
+        assert_eq!(source, "config.toml:1");
+        assert_eq!((arch.port_digit(4), arch.port_digit(6)), (7, 9));
+
+        for bad in [
+            def("Arch", 1..=1, 7..=7),
+            def("kusama", 1..=1, 7..=7),
+            def("validator", 1..=1, 7..=7),
+            def("arch", 1..=2, 7..=7),
+            def("arch", 1..=1, 10..=10),
+            RoleDef {
+                ip_digit: 3,
+                ..def("arch", 1..=1, 7..=7)
+            },
+        ] {
+            assert!(merge(std::slice::from_ref(&bad)).is_err(), "{bad:?}");
+        }
+    }
+}
@@ -0,0 +1,36 @@
+// Config for an RPC proxy in front of a node: the upstream is the node's ws
+// endpoint, the proxy listens on the same address PROXY_OFFSET ports higher
+use crate::{NodeInfo, PortKind, PortgenError};
+use serde_json::{json, Value};
+
+pub const PROXY_OFFSET: u16 = 1000;
+pub const MAX_BATCH_SIZE: u32 = 100;
+pub const TIMEOUT_MS: u64 = 30000;
+
+pub struct ProxyOptions {
+    pub max_batch_size: u32,
+    pub timeout_ms: u64,
+}
+
+pub fn config(node: &NodeInfo, options: &ProxyOptions) -> Result<Value, PortgenError> {
+    let ws = node.address.port_of(PortKind::Ws)?.0;
+    let listen = ws
+        .checked_add(PROXY_OFFSET)
+        .ok_or("port base too high, proxy port exceeds 65535")?;
+    Ok(json!({
+        "name": node.name,
+        "listen": { "host": node.address.ip.to_string(), "port": listen },
+        "upstream": format!("ws://{}:{ws}", node.address.ip),
+        "maxBatchSize": options.max_batch_size,
+        "timeoutMs": options.timeout_ms,
+    }))
+}
+
+// One config per node, as a JSON array
+pub fn configs(nodes: &[NodeInfo], options: &ProxyOptions) -> Result<String, PortgenError> {
+    let configs = nodes
+        .iter()
+        .map(|node| config(node, options))
+        .collect::<Result<Vec<_>, _>>()?;
+    serde_json::to_string_pretty(&configs).map_err(|_| "failed to serialize proxy config".into())
+}
@@ -0,0 +1,62 @@
+// New `<node-name>.yaml` files with the computed values filled in, from a
+// built-in skeleton or a template that sees the same context as `render`
+use crate::{
+    atomic::{write_new, write_output},
+    canonical_name, render, NodeInfo, PortgenError,
+};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+pub const SKELETON: &str = "\
+# Created by portgen new; ip and ports are computed from the name, do not edit
+name: {{ name }}
+role: {{ role }}
+chain: {{ chain }}
+network: {{ network }}
+instance: \"{{ instance }}\"
+ip: {{ ip }}
+port: {{ port }}
+ports:
+  p2p: {{ ports.p2p }}
+  rpc: {{ ports.rpc }}
+  ws: {{ ports.ws }}
+  prometheus: {{ ports.prometheus }}
+";
+
+// The node under its canonical name, so aliases never end up in file names
+pub fn node(node_str: &str) -> Result<NodeInfo, PortgenError> {
+    NodeInfo::new(canonical_name(node_str)?)
+}
+
+// `template` is (name for errors, source), the skeleton without one
+pub fn contents(node: &NodeInfo, template: Option<(&str, &str)>) -> Result<String, PortgenError> {
+    let (name, source) = template.unwrap_or(("skeleton", SKELETON));
+    render::render(name, source, std::slice::from_ref(node))
+}
+
+// Writes `<dir>/<name>.yaml`; an existing file stays unless `force` is set,
+// and is kept as `.bak` when `backup` is set too
+pub fn write(
+    dir: &Path,
+    node: &NodeInfo,
+    contents: &str,
+    force: bool,
+    backup: bool,
+) -> Result<PathBuf, PortgenError> {
+    fs::create_dir_all(dir).map_err(|_| "failed to create node directory")?;
+    let path = dir.join(format!("{}.yaml", node.name));
+    if force {
+        write_output(&path, contents.as_bytes(), backup)
+            .map_err(|_| "failed to write node file")?;
+        return Ok(path);
+    }
+    match write_new(&path, contents.as_bytes()) {
+        Ok(()) => Ok(path),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            Err("node file already exists (use --force to overwrite)".into())
+        }
+        Err(_) => Err("failed to write node file".into()),
+    }
+}
@@ -0,0 +1,54 @@
+// Substrate node CLI flags for the ports the scheme assigns to a node
+use crate::{calculate_address, NodeAddress, NodeInfo, PortKind, PortgenError};
+
+// Flags and values as separate words; `separate_ws` adds `--ws-port` for
+// binaries from before polkadot-sdk 1.0, newer ones serve HTTP and websocket
+// RPC both on `--rpc-port`
+pub fn port_flags(node_str: &str, separate_ws: bool) -> Result<Vec<String>, PortgenError> {
+    address_flags(&calculate_address(node_str)?, separate_ws)
+}
+
+// The same for an address computed under any schema
+pub fn address_flags(addr: &NodeAddress, separate_ws: bool) -> Result<Vec<String>, PortgenError> {
+    let mut flags = vec![
+        ("--port", addr.port.to_string()),
+        ("--rpc-port", addr.port_of(PortKind::Rpc)?.to_string()),
+    ];
+    if separate_ws {
+        flags.push(("--ws-port", addr.port_of(PortKind::Ws)?.to_string()));
+    }
+    flags.push((
+        "--prometheus-port",
+        addr.port_of(PortKind::Prometheus)?.to_string(),
+    ));
+
+    let mut words = Vec::new();
+    for (flag, value) in flags {
+        words.push(flag.to_string());
+        words.push(value);
+    }
+    words.push("--prometheus-external".to_string());
+    words.push("--listen-addr".to_string());
+    words.push(addr.multiaddr());
+    Ok(words)
+}
+
+pub fn script_args(node_str: &str, separate_ws: bool) -> Result<String, PortgenError> {
+    Ok(port_flags(node_str, separate_ws)?.join(" "))
+}
+
+// Inside a container the node listens on all interfaces and announces its
+// generated address instead, with its data under /data
+pub fn container_args(node: &NodeInfo) -> Result<Vec<String>, PortgenError> {
+    let addr = node.address;
+    Ok(vec![
+        "--base-path=/data".to_string(),
+        format!("--port={}", addr.port),
+        format!("--rpc-port={}", addr.port_of(PortKind::Rpc)?),
+        "--rpc-external".to_string(),
+        format!("--prometheus-port={}", addr.port_of(PortKind::Prometheus)?),
+        "--prometheus-external".to_string(),
+        format!("--listen-addr=/ip4/0.0.0.0/tcp/{}", addr.port),
+        format!("--public-addr={}", addr.multiaddr()),
+    ])
+}
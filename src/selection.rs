@@ -0,0 +1,216 @@
+// Picks node names either from a directory of node files or from the allocation
+use crate::{canonical_name, enumerate_nodes, ChainId, Network, NodeName, PortgenError};
+use std::{fs, path::PathBuf};
+
+// `*` matches any run of characters, `?` any one character
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name) = (pattern.as_bytes(), name.as_bytes());
+    let (mut p, mut n) = (0, 0);
+    // Last `*` seen and the name position it is currently stretched to
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+// Glob patterns over canonical node names: a node is kept when it matches any
+// `only` pattern (or there are none) and no `exclude` pattern
+#[derive(Debug, Clone, Default)]
+pub struct NameFilter {
+    pub only: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl NameFilter {
+    pub fn is_empty(&self) -> bool {
+        self.only.is_empty() && self.exclude.is_empty()
+    }
+
+    // Names that are not node names are never kept by a non-empty filter
+    pub fn keeps(&self, name: &str) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let Ok(name) = canonical_name(name) else {
+            return false;
+        };
+        (self.only.is_empty() || self.only.iter().any(|p| glob_match(p, &name)))
+            && !self.exclude.iter().any(|p| glob_match(p, &name))
+    }
+
+    // Patterns matching none of `names`, likely typos
+    pub fn unmatched<'a>(&'a self, names: &[String]) -> Vec<&'a str> {
+        let canonical: Vec<String> = names
+            .iter()
+            .filter_map(|name| canonical_name(name).ok())
+            .collect();
+        self.only
+            .iter()
+            .chain(&self.exclude)
+            .filter(|pattern| !canonical.iter().any(|name| glob_match(pattern, name)))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    // Directory of `<node-name>.yaml` files; without it every slot is a candidate
+    pub dir: Option<PathBuf>,
+    pub network: Option<Network>,
+    pub chain: Option<String>,
+    pub role: Option<String>,
+    // Slots of sunset chains are left out unless asked for, by this or --chain
+    pub include_deprecated: bool,
+    // Applied on top of the filters above
+    pub names: NameFilter,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Selected {
+    pub nodes: Vec<String>,
+    // Files in the directory whose name is not a node name
+    pub skipped: Vec<PathBuf>,
+    // --only and --exclude patterns matching none of the nodes the other
+    // filters select
+    pub unmatched: Vec<String>,
+}
+
+impl Selection {
+    fn matches(&self, name: &str) -> Result<bool, PortgenError> {
+        let node = NodeName::parse(name)?;
+        if let Some(network) = self.network {
+            if node.network.parse::<Network>()? != network {
+                return Ok(false);
+            }
+        }
+        if let Some(chain) = &self.chain {
+            let wanted = match chain.as_str() {
+                "relay" => ChainId(0),
+                name => ChainId::from_str(Some(name))?,
+            };
+            if ChainId::from_str(node.chain.as_deref())? != wanted {
+                return Ok(false);
+            }
+        }
+        if let Some(role) = &self.role {
+            if node.role != *role {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn candidates(&self) -> Result<Selected, PortgenError> {
+        let Some(dir) = &self.dir else {
+            let networks = match self.network {
+                Some(network) => vec![network],
+                None => Network::ALL.to_vec(),
+            };
+            let include_deprecated = self.include_deprecated || self.chain.is_some();
+            let nodes = networks
+                .into_iter()
+                .flat_map(enumerate_nodes)
+                .filter(|name| {
+                    include_deprecated
+                        || NodeName::parse(name)
+                            .and_then(|node| ChainId::from_str(node.chain.as_deref()))
+                            .is_ok_and(|chain| !chain.is_deprecated())
+                })
+                .collect();
+            return Ok(Selected {
+                nodes,
+                ..Selected::default()
+            });
+        };
+
+        let mut selected = Selected::default();
+        let entries = fs::read_dir(dir).map_err(|_| "failed to read node directory")?;
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "yaml"))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            let name = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .map(str::to_string);
+            match name {
+                Some(name) if crate::calculate_address(&name).is_ok() => selected.nodes.push(name),
+                _ => selected.skipped.push(path),
+            }
+        }
+        Ok(selected)
+    }
+
+    pub fn resolve(&self) -> Result<Selected, PortgenError> {
+        let mut selected = self.candidates()?;
+        let mut nodes = Vec::new();
+        for name in selected.nodes {
+            if self.matches(&name)? {
+                nodes.push(name);
+            }
+        }
+        selected.unmatched = self
+            .names
+            .unmatched(&nodes)
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        nodes.retain(|name| self.names.keeps(name));
+        selected.nodes = nodes;
+        Ok(selected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs_narrow_the_other_filters() {
+        assert!(glob_match("val-*-kusama-*", "val-bridge-hub-kusama-02"));
+        assert!(glob_match("*-0?", "rpc-polkadot-01"));
+        assert!(!glob_match("val-*-kusama-*", "val-kusama-02"));
+        assert!(!glob_match("rpc-*", "val-polkadot-02"));
+
+        let selection = Selection {
+            network: Some(Network::Kusama),
+            role: Some("val".to_string()),
+            names: NameFilter {
+                // Patterns see canonical names, the statemine alias matches nothing
+                only: vec!["*-asset-hub-*".to_string(), "*-statemine-*".to_string()],
+                // No rpc node is left after the role filter
+                exclude: vec!["rpc-*".to_string(), "*-02".to_string()],
+            },
+            ..Selection::default()
+        };
+        let selected = selection.resolve().unwrap();
+        assert!(!selected.nodes.is_empty());
+        assert!(selected
+            .nodes
+            .iter()
+            .all(|name| { name.starts_with("val-asset-hub-kusama-") && !name.ends_with("-02") }));
+        assert_eq!(selected.unmatched, ["*-statemine-*", "rpc-*"]);
+    }
+}
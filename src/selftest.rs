@@ -0,0 +1,41 @@
+// The documented examples, `portgen <name>  # Description (port, ip)` lines of
+// the help text, checked against the formula
+use crate::{calculate_address, PortgenError};
+use std::net::Ipv4Addr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Example {
+    pub name: String,
+    pub port: u16,
+    pub ip: Ipv4Addr,
+}
+
+fn parse(line: &str) -> Option<Example> {
+    let (command, comment) = line.trim().split_once('#')?;
+    let mut words = command.split_whitespace();
+    if words.next()? != "portgen" {
+        return None;
+    }
+    let name = words.next()?;
+    let (_, expected) = comment.rsplit_once('(')?;
+    let (port, ip) = expected.strip_suffix(')')?.split_once(',')?;
+    Some(Example {
+        name: name.to_string(),
+        port: port.trim().parse().ok()?,
+        ip: ip.trim().parse().ok()?,
+    })
+}
+
+pub fn examples(text: &str) -> Vec<Example> {
+    text.lines().filter_map(parse).collect()
+}
+
+// The port and IP the example computes to, when they differ from the text
+pub fn check(example: &Example) -> Result<Option<(u16, Ipv4Addr)>, PortgenError> {
+    let actual = calculate_address(&example.name)?;
+    if actual.port.0 == example.port && actual.ip == example.ip {
+        Ok(None)
+    } else {
+        Ok(Some((actual.port.0, actual.ip)))
+    }
+}
@@ -0,0 +1,118 @@
+// semanage port commands labelling node ports on SELinux hosts, with
+// contiguous ports of a type collapsed into one range record
+use crate::{
+    pcap::KindFilter,
+    ranges::{port_range, runs},
+    NodeInfo, NodeName, PortgenError, RoleConstraints,
+};
+use std::{fmt::Write, str::FromStr};
+
+pub const DEFAULT_TYPE: &str = "unreserved_port_t";
+
+fn check_type(port_type: &str) -> Result<(), PortgenError> {
+    let valid = !port_type.is_empty()
+        && port_type
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err("invalid SELinux type, expected letters, digits and underscores".into())
+    }
+}
+
+// `rpc=http_port_t`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoleType {
+    pub role: String,
+    pub port_type: String,
+}
+
+impl FromStr for RoleType {
+    type Err = PortgenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (role, port_type) = s.split_once('=').ok_or("expected ROLE=TYPE")?;
+        if RoleConstraints::get(role).is_none() {
+            return Err("unknown role in ROLE=TYPE".into());
+        }
+        check_type(port_type)?;
+        Ok(Self {
+            role: role.to_string(),
+            port_type: port_type.to_string(),
+        })
+    }
+}
+
+pub struct SelinuxOptions<'a> {
+    // Type of roles without an entry in `role_types`
+    pub default_type: &'a str,
+    pub role_types: &'a [RoleType],
+    pub kinds: KindFilter,
+    // `-m` instead of `-a`, for records that already exist
+    pub modify: bool,
+}
+
+// Port ranges per type, types in order of first appearance
+fn labels(
+    nodes: &[NodeInfo],
+    options: &SelinuxOptions,
+) -> Result<Vec<(String, Vec<String>)>, PortgenError> {
+    check_type(options.default_type)?;
+    let mut ports: Vec<(String, Vec<u16>)> = Vec::new();
+    for node in nodes {
+        let name = NodeName::parse(node.name.trim_end_matches(".yaml"))?;
+        let port_type = options
+            .role_types
+            .iter()
+            .rev()
+            .find(|entry| entry.role == name.role)
+            .map_or(options.default_type, |entry| entry.port_type.as_str());
+        let index = match ports.iter().position(|(known, _)| known == port_type) {
+            Some(index) => index,
+            None => {
+                ports.push((port_type.to_string(), Vec::new()));
+                ports.len() - 1
+            }
+        };
+        for kind in options.kinds.kinds() {
+            ports[index].1.push(node.address.port_of(kind)?.0);
+        }
+    }
+    Ok(ports
+        .into_iter()
+        .map(|(port_type, mut ports)| {
+            ports.sort_unstable();
+            ports.dedup();
+            let ranges = runs(&ports).iter().map(port_range).collect();
+            (port_type, ranges)
+        })
+        .collect())
+}
+
+// `semanage port -a -t unreserved_port_t -p tcp 31001`
+pub fn commands(nodes: &[NodeInfo], options: &SelinuxOptions) -> Result<String, PortgenError> {
+    let flag = if options.modify { "-m" } else { "-a" };
+    let mut out = String::new();
+    for (port_type, ranges) in labels(nodes, options)? {
+        for range in ranges {
+            let _ = writeln!(out, "semanage port {flag} -t {port_type} -p tcp {range}");
+        }
+    }
+    Ok(out)
+}
+
+// One grep per record, matching the `TYPE  tcp  PORT, RANGE, ...` lines of
+// `semanage port -l`
+pub fn checks(nodes: &[NodeInfo], options: &SelinuxOptions) -> Result<String, PortgenError> {
+    let mut out = String::new();
+    for (port_type, ranges) in labels(nodes, options)? {
+        for range in ranges {
+            let _ = writeln!(
+                out,
+                "semanage port -l | grep -E '^{port_type}[[:space:]]+tcp[[:space:]].*[[:space:]]{range}(,|$)'"
+            );
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,179 @@
+// /etc/services entries naming the nodes' p2p ports, and the portgen-managed
+// block inside a services file. Service names follow RFC 6335, at most 15
+// characters, so names that don't fit have their chain and network shortened.
+use crate::{
+    canonical_name,
+    hosts::{self, SyncOutcome},
+    Network, NodeInfo, NodeName, PortgenError,
+};
+use std::{collections::BTreeMap, path::Path};
+
+pub const MAX_NAME_LEN: usize = 15;
+
+// 1-15 letters, digits and single hyphens, with a letter, not starting or
+// ending with a hyphen
+pub fn validate_name(name: &str) -> Result<(), &'static str> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        Err("service names are 1-15 characters")
+    } else if !name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-') {
+        Err("service names hold only letters, digits and hyphens")
+    } else if !name.bytes().any(|b| b.is_ascii_alphabetic()) {
+        Err("service names need a letter")
+    } else if name.starts_with('-') || name.ends_with('-') || name.contains("--") {
+        Err("service names can't start or end with a hyphen or repeat one")
+    } else {
+        Ok(())
+    }
+}
+
+fn network_abbrev(network: Network) -> &'static str {
+    match network {
+        Network::Polkadot => "dot",
+        Network::Kusama => "ksm",
+        Network::Westend => "wnd",
+        Network::Paseo => "pas",
+    }
+}
+
+// Initials of a hyphenated chain name, the first three letters of a single
+// word: asset-hub -> ah, moonbeam -> moo
+fn chain_abbrev(chain: &str) -> String {
+    if chain.contains('-') {
+        chain
+            .split('-')
+            .filter_map(|word| word.chars().next())
+            .collect()
+    } else {
+        chain.chars().take(3).collect()
+    }
+}
+
+// The canonical node name when it is a valid service name, else the
+// shortened form: rpc-asset-hub-polkadot-01 -> rpc-ah-dot-01
+pub fn service_name(node_str: &str) -> Result<String, PortgenError> {
+    let canonical = canonical_name(node_str)?;
+    if validate_name(&canonical).is_ok() {
+        return Ok(canonical);
+    }
+    let node = NodeName::parse(&canonical)?;
+    let network = network_abbrev(node.network.parse()?);
+    let short = match &node.chain {
+        Some(chain) => format!(
+            "{}-{}-{network}-{}",
+            node.role,
+            chain_abbrev(chain),
+            node.instance
+        ),
+        None => format!("{}-{network}-{}", node.role, node.instance),
+    };
+    validate_name(&short)?;
+    Ok(short)
+}
+
+// Nodes sharing each service name, for names more than one node ends up with
+pub fn collisions(nodes: &[String]) -> Result<Vec<(String, Vec<String>)>, PortgenError> {
+    let mut by_name: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for name in nodes {
+        let canonical = canonical_name(name)?;
+        let owners = by_name.entry(service_name(&canonical)?).or_default();
+        if !owners.contains(&canonical) {
+            owners.push(canonical);
+        }
+    }
+    Ok(by_name
+        .into_iter()
+        .filter(|(_, owners)| owners.len() > 1)
+        .collect())
+}
+
+// `<service> <port>/tcp # portgen [<node>]` per node, the node named when the
+// service name is shortened; nodes listed twice (or by alias) come out once
+pub fn services_lines(nodes: &[String]) -> Result<String, PortgenError> {
+    if let Some((service, nodes)) = collisions(nodes)?.into_iter().next() {
+        return Err(PortgenError::ServiceNameCollision { service, nodes });
+    }
+    let mut out = String::new();
+    let mut seen = Vec::new();
+    for name in nodes {
+        let canonical = canonical_name(name)?;
+        if seen.contains(&canonical) {
+            continue;
+        }
+        let node = NodeInfo::new(canonical.clone())?;
+        let service = service_name(&canonical)?;
+        let comment = if service == canonical {
+            "# portgen".to_string()
+        } else {
+            format!("# portgen {canonical}")
+        };
+        out.push_str(&format!(
+            "{service:<15}  {}/tcp  {comment}\n",
+            node.address.port
+        ));
+        seen.push(canonical);
+    }
+    Ok(out)
+}
+
+pub fn sync(
+    path: &Path,
+    entries: &str,
+    dry_run: bool,
+    backup: bool,
+) -> Result<SyncOutcome, PortgenError> {
+    hosts::sync_block(
+        path,
+        entries,
+        dry_run,
+        backup,
+        (
+            "failed to read services file",
+            "failed to write services file",
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enumerate_nodes;
+
+    #[test]
+    fn names_fit_rfc_6335_and_stay_unique() {
+        for (node, service) in [
+            ("rpc-westend-01", "rpc-westend-01"),
+            ("boot-polkadot-00", "boot-dot-00"),
+            ("rpc-asset-hub-polkadot-01", "rpc-ah-dot-01"),
+            ("rpc-statemint-polkadot-01", "rpc-ah-dot-01"),
+            ("boot-hyperbridge-paseo-00", "boot-hyp-pas-00"),
+        ] {
+            assert_eq!(service_name(node).unwrap(), service, "{node}");
+        }
+        for bad in [
+            "",
+            "-rpc",
+            "rpc--01",
+            "0123",
+            "rpc_01",
+            "a-very-long-service",
+        ] {
+            assert!(validate_name(bad).is_err(), "{bad}");
+        }
+
+        // No two slots of the registry shorten to the same name
+        let all: Vec<String> = Network::ALL.into_iter().flat_map(enumerate_nodes).collect();
+        assert_eq!(collisions(&all).unwrap(), []);
+
+        let lines = services_lines(&[
+            "rpc-asset-hub-polkadot-01".to_string(),
+            "rpc-statemint-polkadot-01".to_string(),
+            "rpc-westend-01".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(
+            lines,
+            "rpc-ah-dot-01    31011/tcp  # portgen rpc-asset-hub-polkadot-01\n\
+             rpc-westend-01   33001/tcp  # portgen\n"
+        );
+    }
+}
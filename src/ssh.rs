@@ -0,0 +1,51 @@
+// ssh invocations for the generated address of a node
+use crate::{calculate_address, PortgenError};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Default)]
+pub struct SshOptions {
+    pub user: Option<String>,
+    pub identity_file: Option<PathBuf>,
+    // Passed to `ssh -J`
+    pub jump: Option<String>,
+}
+
+// Arguments after `ssh`; the name must parse so nothing ever dials a bogus host
+pub fn ssh_args(
+    node_str: &str,
+    options: &SshOptions,
+    remote_command: &[String],
+) -> Result<Vec<String>, PortgenError> {
+    let ip = calculate_address(node_str)?.ip;
+
+    let mut args = Vec::new();
+    if let Some(identity) = &options.identity_file {
+        args.push("-i".to_string());
+        args.push(identity.display().to_string());
+    }
+    if let Some(jump) = &options.jump {
+        args.push("-J".to_string());
+        args.push(jump.clone());
+    }
+    // Ends option parsing, anything after the destination is the remote command
+    args.push("--".to_string());
+    args.push(match &options.user {
+        Some(user) => format!("{user}@{ip}"),
+        None => ip.to_string(),
+    });
+    args.extend(remote_command.iter().cloned());
+    Ok(args)
+}
+
+// Single-quotes words the shell would otherwise split or expand
+pub fn shell_quote(word: &str) -> String {
+    let plain = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "@%+=:,./-_".contains(c));
+    if plain {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', r"'\''"))
+    }
+}
@@ -0,0 +1,256 @@
+// The IP plan as prefixes: the /24s each role and network can occupy,
+// aggregated into the fewest CIDR blocks, and what is left of the supernet
+use crate::{
+    compose_name,
+    ranges::{run_to_cidrs, runs, Cidr},
+    roles, Network, PortSchema, PortgenError,
+};
+use serde_json::json;
+use std::{net::Ipv4Addr, ops::RangeInclusive};
+
+// One aggregated block of /24s
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub cidr: Cidr,
+    pub network: Network,
+    // Roles sharing the block (validators and collators share an IP digit),
+    // each with its instances within the block
+    pub roles: Vec<(&'static str, RangeInclusive<u8>)>,
+}
+
+impl Block {
+    // `val 01-06, col 01-03 on polkadot`
+    pub fn meaning(&self) -> String {
+        let roles: Vec<String> = self
+            .roles
+            .iter()
+            .map(|(role, instances)| {
+                if instances.start() == instances.end() {
+                    format!("{role} {:02}", instances.start())
+                } else {
+                    format!("{role} {:02}-{:02}", instances.start(), instances.end())
+                }
+            })
+            .collect();
+        format!("{} on {}", roles.join(", "), self.network)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Plan {
+    pub supernet: Cidr,
+    pub used: Vec<Block>,
+    pub unused: Vec<Cidr>,
+}
+
+// The /16 the schema puts every node in
+pub fn default_supernet(schema: &PortSchema) -> Cidr {
+    let [a, b] = schema.ip_prefix;
+    Cidr {
+        ip: Ipv4Addr::new(a, b, 0, 0),
+        prefix: 16,
+    }
+}
+
+fn slash24(schema: &PortSchema, third_octet: u8) -> u32 {
+    let [a, b] = schema.ip_prefix;
+    u32::from(Ipv4Addr::new(a, b, third_octet, 0))
+}
+
+// Third octet of every role and instance on a network, from the schema's own
+// formula (bootnodes all share instance 0's /24)
+fn octets(
+    schema: &PortSchema,
+    network: Network,
+    digit: u8,
+) -> Result<Vec<(u8, &'static str, u8)>, PortgenError> {
+    let mut octets = Vec::new();
+    for role in roles::table().iter().filter(|role| role.ip_digit == digit) {
+        for instance in role.instances.clone() {
+            let name = compose_name(
+                role.abbrev,
+                role.parachain_only.then_some("asset-hub"),
+                network,
+                instance,
+            );
+            let [_, _, octet, _] = schema.address(&name)?.ip.octets();
+            octets.push((octet, role.abbrev, instance));
+        }
+    }
+    Ok(octets)
+}
+
+pub fn plan(schema: &PortSchema, supernet: Cidr) -> Result<Plan, PortgenError> {
+    let mut used = Vec::new();
+    let mut addresses = Vec::new();
+    let mut digits: Vec<u8> = roles::table().iter().map(|role| role.ip_digit).collect();
+    digits.sort_unstable();
+    digits.dedup();
+    for digit in digits {
+        for network in Network::ALL {
+            let slots = octets(schema, network, digit)?;
+            let mut third: Vec<u8> = slots.iter().map(|(octet, ..)| *octet).collect();
+            third.sort_unstable();
+            third.dedup();
+            for run in runs(&third) {
+                let first = slash24(schema, *run.start());
+                let last = slash24(schema, *run.end()) + 255;
+                addresses.push(first..=last);
+                for cidr in run_to_cidrs(first..=last) {
+                    if !supernet.contains(cidr.ip) || cidr.prefix < supernet.prefix {
+                        return Err("the scheme's addresses fall outside the supernet".into());
+                    }
+                    let [_, _, low, _] = cidr.ip.octets();
+                    let high = low + ((1u32 << (24 - cidr.prefix)) - 1) as u8;
+                    // Roles in order, each with the instances inside the block
+                    let mut roles: Vec<(&'static str, RangeInclusive<u8>)> = Vec::new();
+                    for &(octet, role, instance) in &slots {
+                        if !(low..=high).contains(&octet) {
+                            continue;
+                        }
+                        match roles.iter_mut().find(|(known, _)| *known == role) {
+                            Some((_, instances)) => {
+                                *instances = *instances.start()..=instance;
+                            }
+                            None => roles.push((role, instance..=instance)),
+                        }
+                    }
+                    used.push(Block {
+                        cidr,
+                        network,
+                        roles,
+                    });
+                }
+            }
+        }
+    }
+    used.sort_by_key(|block| u32::from(block.cidr.ip));
+    addresses.sort_by_key(|range| *range.start());
+
+    // The gaps between the used blocks
+    let mut unused = Vec::new();
+    let mut next = *supernet.addresses().start() as u64;
+    for range in &addresses {
+        if (*range.start() as u64) > next {
+            unused.extend(run_to_cidrs(next as u32..=range.start() - 1));
+        }
+        next = *range.end() as u64 + 1;
+    }
+    let end = *supernet.addresses().end() as u64;
+    if next <= end {
+        unused.extend(run_to_cidrs(next as u32..=end as u32));
+    }
+
+    Ok(Plan {
+        supernet,
+        used,
+        unused,
+    })
+}
+
+pub fn to_json(plan: &Plan) -> Result<String, PortgenError> {
+    let used: Vec<_> = plan
+        .used
+        .iter()
+        .map(|block| {
+            let roles: Vec<_> = block
+                .roles
+                .iter()
+                .map(|(role, instances)| {
+                    json!({ "role": role, "instances": [instances.start(), instances.end()] })
+                })
+                .collect();
+            json!({
+                "prefix": block.cidr.to_string(),
+                "network": block.network.to_string(),
+                "roles": roles,
+            })
+        })
+        .collect();
+    let unused: Vec<String> = plan.unused.iter().map(Cidr::to_string).collect();
+    serde_json::to_string_pretty(&json!({
+        "supernet": plan.supernet.to_string(),
+        "used": used,
+        "unused": unused,
+    }))
+    .map_err(|_| "failed to serialize subnet plan".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{enumerate_chains, PORT_SCHEMA_V1, PORT_SCHEMA_V2};
+    use std::collections::BTreeSet;
+
+    // Every address the schema hands out with the role, network and instance
+    // it belongs to, from the names of every role, network, instance and chain
+    fn enumerated(schema: &PortSchema) -> Vec<(u32, &'static str, Network, u8)> {
+        let chains: Vec<_> = enumerate_chains().collect();
+        let mut addresses = Vec::new();
+        for role in roles::table() {
+            for network in Network::ALL {
+                for instance in role.instances.clone() {
+                    for &chain in &chains {
+                        let name = compose_name(role.abbrev, chain, network, instance);
+                        if let Ok(address) = schema.address(&name) {
+                            let ip = u32::from(address.ip);
+                            addresses.push((ip, role.abbrev, network, instance));
+                        }
+                    }
+                }
+            }
+        }
+        addresses
+    }
+
+    #[test]
+    fn used_blocks_cover_exactly_the_enumerated_slash24s() {
+        for schema in PortSchema::ALL {
+            let plan = plan(&schema, default_supernet(&schema)).unwrap();
+            let addresses = enumerated(&schema);
+            let expected: BTreeSet<u32> = addresses.iter().map(|(ip, ..)| ip & !0xff).collect();
+            let mut covered = BTreeSet::new();
+            for block in &plan.used {
+                for address in block.cidr.addresses().step_by(256) {
+                    assert!(covered.insert(address), "{} overlaps", block.cidr);
+                }
+            }
+            assert_eq!(covered, expected, "schema v{}", schema.version);
+
+            // And each block means what its addresses are
+            for (ip, role, network, instance) in addresses {
+                let block = plan
+                    .used
+                    .iter()
+                    .find(|block| block.cidr.addresses().contains(&ip))
+                    .unwrap();
+                assert_eq!(block.network, network, "{}", block.cidr);
+                assert!(
+                    block
+                        .roles
+                        .iter()
+                        .any(|(r, instances)| *r == role && instances.contains(&instance)),
+                    "{} does not list {role} {instance:02}",
+                    block.cidr
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn used_and_unused_partition_the_supernet() {
+        for schema in [PORT_SCHEMA_V1, PORT_SCHEMA_V2] {
+            let supernet = default_supernet(&schema);
+            let plan = plan(&schema, supernet).unwrap();
+            let mut blocks: Vec<Cidr> = plan.used.iter().map(|block| block.cidr).collect();
+            blocks.extend(&plan.unused);
+            blocks.sort_by_key(|cidr| u32::from(cidr.ip));
+            let mut next = *supernet.addresses().start() as u64;
+            for cidr in blocks {
+                assert_eq!(*cidr.addresses().start() as u64, next, "gap before {cidr}");
+                next = *cidr.addresses().end() as u64 + 1;
+            }
+            assert_eq!(next, *supernet.addresses().end() as u64 + 1);
+        }
+    }
+}
@@ -0,0 +1,61 @@
+// Next-token completions for a partially typed node name
+use crate::{calculate_address, chains, compose_name, roles, Network};
+use std::collections::HashSet;
+
+// Every valid name as its tokens (role, chain, network, instance), chain aliases included
+fn candidates() -> Vec<Vec<String>> {
+    let chains: Vec<Option<&str>> = std::iter::once(None)
+        .chain(
+            chains::table()
+                .iter()
+                .flat_map(|(_, names)| names.iter().copied().map(Some)),
+        )
+        .collect();
+
+    let mut out = Vec::new();
+    for constraints in roles::table() {
+        let (role, instances) = (constraints.abbrev, &constraints.instances);
+        for &chain in &chains {
+            for network in Network::ALL {
+                for instance in instances.clone() {
+                    // Drops combinations the scheme rejects, like relay chain collators
+                    if calculate_address(&compose_name(role, chain, network, instance)).is_err() {
+                        continue;
+                    }
+                    let mut tokens = vec![role.to_string()];
+                    tokens.extend(chain.map(str::to_string));
+                    tokens.push(network.to_string());
+                    tokens.push(format!("{instance:02}"));
+                    out.push(tokens);
+                }
+            }
+        }
+    }
+    out
+}
+
+// Completes the token the partial name ends in, or the next whole token after
+// a trailing `-`; multi-word chain names count as one token
+pub fn suggest(partial: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut suggestions = Vec::new();
+    for tokens in candidates() {
+        let mut prefix = String::new();
+        for (i, token) in tokens.iter().enumerate() {
+            prefix.push_str(token);
+            if i + 1 < tokens.len() {
+                prefix.push('-');
+            }
+            if prefix.len() > partial.len() {
+                break;
+            }
+        }
+        if prefix.len() > partial.len()
+            && prefix.starts_with(partial)
+            && seen.insert(prefix.clone())
+        {
+            suggestions.push(prefix);
+        }
+    }
+    suggestions
+}
@@ -0,0 +1,63 @@
+// End-of-run summary of a batch lookup for CI logs: what resolved, what
+// failed and why, and which inputs ended up on the same address
+use crate::NodeInfo;
+use serde_json::{json, Value};
+use std::{collections::HashMap, fmt::Write};
+
+#[derive(Debug, Clone, Default)]
+pub struct Summary {
+    pub succeeded: usize,
+    // Input name and why it failed, in input order
+    pub failed: Vec<(String, String)>,
+    // Nodes whose address an earlier, differently named input already has
+    pub collisions: usize,
+}
+
+impl Summary {
+    pub fn new(nodes: &[NodeInfo], failed: Vec<(String, String)>) -> Self {
+        let mut seen: HashMap<String, &str> = HashMap::new();
+        let mut collisions = 0;
+        for node in nodes {
+            match seen.get(&node.address.to_string()) {
+                Some(first) if *first != node.name => collisions += 1,
+                Some(_) => {}
+                None => {
+                    seen.insert(node.address.to_string(), &node.name);
+                }
+            }
+        }
+        Self {
+            succeeded: nodes.len(),
+            failed,
+            collisions,
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::from("=== portgen summary ===\n");
+        let _ = writeln!(out, "  Succeeded: {}", self.succeeded);
+        let _ = writeln!(out, "  Failed: {}", self.failed.len());
+        let _ = writeln!(out, "  Collisions: {}", self.collisions);
+        if !self.failed.is_empty() {
+            let _ = writeln!(out, "=== Failed nodes ===");
+            for (name, error) in &self.failed {
+                let _ = writeln!(out, "  {name}: {error}");
+            }
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> Value {
+        let failed: Vec<Value> = self
+            .failed
+            .iter()
+            .map(|(name, error)| json!({ "name": name, "error": error }))
+            .collect();
+        json!({
+            "succeeded": self.succeeded,
+            "failed": self.failed.len(),
+            "collisions": self.collisions,
+            "failed_nodes": failed,
+        })
+    }
+}
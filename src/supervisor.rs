@@ -0,0 +1,19 @@
+// supervisord `[program:x]` sections for hosts without systemd
+use crate::{script_args::script_args, PortgenError};
+use std::fmt::Write;
+
+pub const LOG_ROOT: &str = "/var/log/substrate";
+
+pub fn program(node_str: &str, binary: &str) -> Result<String, PortgenError> {
+    let name = node_str.trim_end_matches(".yaml");
+    let args = script_args(name, false)?;
+
+    let mut out = String::new();
+    let _ = writeln!(out, "[program:{name}]");
+    let _ = writeln!(out, "command = {binary} {args}");
+    let _ = writeln!(out, "stdout_logfile = {LOG_ROOT}/{name}/stdout.log");
+    let _ = writeln!(out, "stderr_logfile = {LOG_ROOT}/{name}/stderr.log");
+    let _ = writeln!(out, "autorestart = true");
+    let _ = writeln!(out, "user = substrate");
+    Ok(out)
+}
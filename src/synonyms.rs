@@ -0,0 +1,116 @@
+// Role synonyms from older inventories and from typing by hand, replaced by
+// the role token before a name is parsed, so the canonical name comes out
+use crate::{PortgenError, RoleConstraints};
+use std::str::FromStr;
+
+// There is no full-node role, full nodes are the rpc role
+pub const BUILTIN: &[(&str, &str)] = &[
+    ("bootnode", "boot"),
+    ("bootstrap", "boot"),
+    ("validator", "val"),
+    ("collator", "col"),
+    ("full", "rpc"),
+    ("fullnode", "rpc"),
+    ("rpc-node", "rpc"),
+];
+
+// `validator=val`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoleSynonym {
+    pub synonym: String,
+    pub role: String,
+}
+
+impl FromStr for RoleSynonym {
+    type Err = PortgenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (synonym, role) = s.split_once('=').ok_or("expected SYNONYM=ROLE")?;
+        if RoleConstraints::get(role).is_none() {
+            return Err("unknown role in SYNONYM=ROLE".into());
+        }
+        if RoleConstraints::get(synonym).is_some() {
+            return Err("a role synonym must not be a role name".into());
+        }
+        let valid = synonym.split('-').all(|token| {
+            !token.is_empty()
+                && token
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        });
+        if !valid {
+            return Err("role synonyms are lowercase words joined by single hyphens".into());
+        }
+        Ok(Self {
+            synonym: synonym.to_string(),
+            role: role.to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Synonyms {
+    // Longest first, so `rpc-node` wins over a shorter prefix
+    entries: Vec<RoleSynonym>,
+}
+
+impl Synonyms {
+    // The built-in table, configured synonyms added or overriding it
+    pub fn new(configured: &[RoleSynonym]) -> Result<Self, PortgenError> {
+        let mut entries: Vec<RoleSynonym> = BUILTIN
+            .iter()
+            .map(|&(synonym, role)| RoleSynonym {
+                synonym: synonym.to_string(),
+                role: role.to_string(),
+            })
+            .collect();
+        for (i, entry) in configured.iter().enumerate() {
+            let clash = configured[..i]
+                .iter()
+                .any(|earlier| earlier.synonym == entry.synonym && earlier.role != entry.role);
+            if clash {
+                return Err("role synonym mapped to two roles".into());
+            }
+            entries.retain(|known| known.synonym != entry.synonym);
+            entries.push(entry.clone());
+        }
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.synonym.len()));
+        Ok(Self { entries })
+    }
+
+    // No synonyms at all, for --strict
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    pub fn entries(&self) -> &[RoleSynonym] {
+        &self.entries
+    }
+
+    // The name with a leading synonym replaced: validator-polkadot-04 -> val-polkadot-04
+    pub fn canonical(&self, name: &str) -> String {
+        for entry in &self.entries {
+            if let Some(rest) = name.strip_prefix(entry.synonym.as_str()) {
+                if rest.starts_with('-') {
+                    return format!("{}{rest}", entry.role);
+                }
+            }
+        }
+        name.to_string()
+    }
+
+    // Tokens with every synonym replaced by its role, wherever it stands
+    pub fn replace_tokens(&self, mut tokens: Vec<String>) -> Vec<String> {
+        for entry in &self.entries {
+            let synonym: Vec<&str> = entry.synonym.split('-').collect();
+            let mut i = 0;
+            while i + synonym.len() <= tokens.len() {
+                if tokens[i..i + synonym.len()] == synonym[..] {
+                    tokens.splice(i..i + synonym.len(), [entry.role.clone()]);
+                }
+                i += 1;
+            }
+        }
+        tokens
+    }
+}
@@ -0,0 +1,72 @@
+// Column-aligned rendering for multi-node output
+use std::fmt::Write;
+
+// Cut a cell down to `max` characters, marking the cut with an ellipsis
+pub fn truncate(cell: &str, max: usize) -> String {
+    if cell.chars().count() <= max {
+        return cell.to_string();
+    }
+    let mut cut: String = cell.chars().take(max.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
+
+fn widths(headers: &[&str], rows: &[Vec<String>]) -> Vec<usize> {
+    headers
+        .iter()
+        .enumerate()
+        .map(|(i, header)| {
+            rows.iter()
+                .filter_map(|row| row.get(i))
+                .map(|cell| cell.chars().count())
+                .chain([header.chars().count()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect()
+}
+
+fn pad(cell: &str, width: usize) -> String {
+    let fill = width.saturating_sub(cell.chars().count());
+    format!("{cell}{}", " ".repeat(fill))
+}
+
+pub fn render(headers: &[&str], rows: &[Vec<String>], fancy: bool) -> String {
+    let widths = widths(headers, rows);
+    let header: Vec<String> = headers.iter().map(|h| h.to_string()).collect();
+    let mut out = String::new();
+
+    if !fancy {
+        for row in std::iter::once(&header).chain(rows) {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, &width)| pad(cell, width))
+                .collect();
+            let _ = writeln!(out, "{}", cells.join("  ").trim_end());
+        }
+        return out;
+    }
+
+    let rule = |left: &str, mid: &str, right: &str| {
+        let segments: Vec<String> = widths.iter().map(|&w| "─".repeat(w + 2)).collect();
+        format!("{left}{}{right}", segments.join(mid))
+    };
+    let line = |row: &[String]| {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!(" {} ", pad(cell, width)))
+            .collect();
+        format!("│{}│", cells.join("│"))
+    };
+
+    let _ = writeln!(out, "{}", rule("┌", "┬", "┐"));
+    let _ = writeln!(out, "{}", line(&header));
+    let _ = writeln!(out, "{}", rule("├", "┼", "┤"));
+    for row in rows {
+        let _ = writeln!(out, "{}", line(row));
+    }
+    let _ = writeln!(out, "{}", rule("└", "┴", "┘"));
+    out
+}
@@ -0,0 +1,45 @@
+// Tailscale ACL policy rules, one accept rule per role so the policy stays
+// small however many nodes a role has
+use crate::{NodeInfo, NodeName, PortKind, PortgenError};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+// Bare tag names get the `tag:` prefix Tailscale requires
+fn tag(name: &str) -> String {
+    if name.starts_with("tag:") {
+        name.to_string()
+    } else {
+        format!("tag:{name}")
+    }
+}
+
+// Without `allow_tags` each role is opened to its own `tag:substrate-<role>`
+pub fn acls(nodes: &[NodeInfo], allow_tags: &[String]) -> Result<String, PortgenError> {
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for node in nodes {
+        let role = NodeName::parse(&node.name)?.role;
+        let ip = node.address.ip;
+        let dst = groups.entry(role).or_default();
+        for kind in [PortKind::P2p, PortKind::Ws] {
+            let entry = format!("{ip}:{}", node.address.port_of(kind)?);
+            if !dst.contains(&entry) {
+                dst.push(entry);
+            }
+        }
+    }
+
+    let rules: Vec<_> = groups
+        .into_iter()
+        .map(|(role, dst)| {
+            let src: Vec<String> = if allow_tags.is_empty() {
+                vec![tag(&format!("substrate-{role}"))]
+            } else {
+                allow_tags.iter().map(|t| tag(t)).collect()
+            };
+            json!({ "action": "accept", "src": src, "dst": dst })
+        })
+        .collect();
+    serde_json::to_string_pretty(&json!({ "acls": rules }))
+        .map(|s| s + "\n")
+        .map_err(|_| "failed to serialize ACLs".into())
+}
@@ -0,0 +1,110 @@
+// Deployment manifest: every node of a network plus who peers with whom
+use crate::{
+    enumerate_chain_nodes, enumerate_chains, time, ChainId, Network, NodeInfo, NodeName,
+    PortgenError,
+};
+use serde_json::{json, Value};
+use std::fmt::Write;
+
+#[derive(Debug, Clone)]
+pub struct Connection {
+    pub from: String,
+    pub to: String,
+    pub reason: &'static str,
+}
+
+pub struct Topology {
+    pub network: Network,
+    pub nodes: Vec<NodeInfo>,
+    pub connections: Vec<Connection>,
+}
+
+// Within each chain the bootnode dials everyone and everyone dials the bootnode
+pub fn build(network: Network) -> Result<Topology, PortgenError> {
+    let mut nodes = Vec::new();
+    let mut connections = Vec::new();
+
+    for chain in enumerate_chains() {
+        let names = enumerate_chain_nodes(network, chain);
+        let Some((boot, others)) = names.split_first() else {
+            continue;
+        };
+        for name in others {
+            let reason = match NodeName::parse(name)?.role.as_str() {
+                "rpc" => "rpc-to-bootnode",
+                _ => "val-to-bootnode",
+            };
+            connections.push(Connection {
+                from: boot.clone(),
+                to: name.clone(),
+                reason: "bootnode-to-all",
+            });
+            connections.push(Connection {
+                from: name.clone(),
+                to: boot.clone(),
+                reason,
+            });
+        }
+        for name in names {
+            nodes.push(NodeInfo::new(name)?);
+        }
+    }
+
+    Ok(Topology {
+        network,
+        nodes,
+        connections,
+    })
+}
+
+fn node_json(node: &NodeInfo) -> Result<Value, PortgenError> {
+    let parsed = NodeName::parse(&node.name)?;
+    let chain_id = ChainId::from_str(parsed.chain.as_deref())?;
+    let chain = chain_id.name()?;
+    Ok(json!({
+        "name": node.name,
+        "role": parsed.role,
+        "chain": chain.unwrap_or("relay"),
+        "chain_type": chain_id.chain_type().to_string(),
+        "instance": parsed.instance,
+        "port": node.address.port.0,
+        "ip": node.address.ip.to_string(),
+        "address": node.address.to_string(),
+    }))
+}
+
+pub fn to_json(topology: &Topology) -> Result<String, PortgenError> {
+    let nodes = topology
+        .nodes
+        .iter()
+        .map(node_json)
+        .collect::<Result<Vec<_>, _>>()?;
+    let connections: Vec<Value> = topology
+        .connections
+        .iter()
+        .map(|c| json!({ "from": c.from, "to": c.to, "reason": c.reason }))
+        .collect();
+    let document = json!({
+        "metadata": {
+            "network": topology.network.to_string(),
+            "created_at": time::now_rfc3339(),
+            "portgen_version": env!("CARGO_PKG_VERSION"),
+        },
+        "nodes": nodes,
+        "connections": connections,
+    });
+    serde_json::to_string_pretty(&document).map_err(|_| "failed to serialize topology".into())
+}
+
+pub fn to_text(topology: &Topology) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# nodes");
+    for node in &topology.nodes {
+        let _ = writeln!(out, "{node}");
+    }
+    let _ = writeln!(out, "\n# connections");
+    for c in &topology.connections {
+        let _ = writeln!(out, "{} -> {} ({})", c.from, c.to, c.reason);
+    }
+    out
+}
@@ -0,0 +1,214 @@
+// Read-only check of the address fields inside node files: each configured key
+// path is compared with the value computed from the file's name. Unlike
+// `apply`, nothing is written, and files `apply` would refuse are told apart.
+use crate::{
+    apply::{scalar, KeyMapping},
+    calculate_address, lint,
+    selection::NameFilter,
+    PortgenError,
+};
+use serde::Deserialize;
+use serde_yaml::Value;
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyCheck {
+    Match,
+    Mismatch { expected: String, found: String },
+    Missing,
+    // The key holds a mapping, sequence or null
+    NotScalar(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileStatus {
+    // One check per key path, in `--keys` order
+    Checked(Vec<(String, KeyCheck)>),
+    // serde_yaml's message, with line and column
+    Unparsable(String),
+    MultipleDocuments(usize),
+    // The document is a scalar or a sequence
+    NotMapping(&'static str),
+}
+
+impl FileStatus {
+    // Distinct exit codes, 1 and 2 stay reserved for errors and usage errors
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Checked(checks) => checks
+                .iter()
+                .map(|(_, check)| match check {
+                    KeyCheck::Match => 0,
+                    KeyCheck::Missing => 3,
+                    KeyCheck::NotScalar(_) => 4,
+                    KeyCheck::Mismatch { .. } => 5,
+                })
+                .max()
+                .unwrap_or(0),
+            Self::Unparsable(_) | Self::MultipleDocuments(_) | Self::NotMapping(_) => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VerifyReport {
+    pub path: PathBuf,
+    pub status: FileStatus,
+}
+
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.path.display())?;
+        let checks = match &self.status {
+            FileStatus::Unparsable(e) => return write!(f, "invalid YAML ({e})"),
+            FileStatus::MultipleDocuments(n) => {
+                return write!(f, "{n} YAML documents, node files hold one")
+            }
+            FileStatus::NotMapping(kind) => {
+                return write!(f, "document is a {kind}, not a mapping")
+            }
+            FileStatus::Checked(checks) => checks,
+        };
+        let problems: Vec<String> = checks
+            .iter()
+            .filter_map(|(path, check)| match check {
+                KeyCheck::Match => None,
+                KeyCheck::Mismatch { expected, found } => {
+                    Some(format!("{path} expected {expected}, found {found}"))
+                }
+                KeyCheck::Missing => Some(format!("{path} missing")),
+                KeyCheck::NotScalar(kind) => Some(format!("{path} holds a {kind}, not a value")),
+            })
+            .collect();
+        if problems.is_empty() {
+            f.write_str("ok")
+        } else {
+            f.write_str(&problems.join("; "))
+        }
+    }
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Sequence(_) => "sequence",
+        Value::Mapping(_) => "mapping",
+        Value::Tagged(_) => "tagged value",
+        _ => "scalar",
+    }
+}
+
+fn lookup<'a>(doc: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(doc, |value, key| value.as_mapping()?.get(key))
+}
+
+pub fn verify_text(
+    text: &str,
+    name: &str,
+    mappings: &[KeyMapping],
+) -> Result<FileStatus, PortgenError> {
+    let address = calculate_address(name)?;
+    let mut docs = Vec::new();
+    for document in serde_yaml::Deserializer::from_str(text) {
+        match Value::deserialize(document) {
+            Ok(doc) => docs.push(doc),
+            Err(e) => return Ok(FileStatus::Unparsable(e.to_string())),
+        }
+    }
+    let doc = match docs.len() {
+        0 => Value::Null,
+        1 => docs.remove(0),
+        n => return Ok(FileStatus::MultipleDocuments(n)),
+    };
+    // An empty file is an empty mapping with every key missing
+    if !matches!(doc, Value::Mapping(_) | Value::Null) {
+        return Ok(FileStatus::NotMapping(kind(&doc)));
+    }
+
+    let mut checks = Vec::new();
+    for mapping in mappings {
+        let check = match lookup(&doc, &mapping.path) {
+            None => KeyCheck::Missing,
+            Some(value) => match (scalar(value), scalar(&mapping.field.value(&address)?)) {
+                // Compared as text, like `apply`: "31001" and 31001 are the same port
+                (Some(found), Some(expected)) if found == expected => KeyCheck::Match,
+                (Some(found), Some(expected)) => KeyCheck::Mismatch { expected, found },
+                _ => KeyCheck::NotScalar(kind(value)),
+            },
+        };
+        checks.push((mapping.path.clone(), check));
+    }
+    Ok(FileStatus::Checked(checks))
+}
+
+// Every `.yaml` in `dir` whose file name is a node name the filter keeps, and
+// the paths skipped for not being one
+pub fn verify_dir(
+    dir: &Path,
+    mappings: &[KeyMapping],
+    names: &NameFilter,
+) -> Result<(Vec<VerifyReport>, Vec<PathBuf>), PortgenError> {
+    let mut reports = Vec::new();
+    let mut skipped = Vec::new();
+    for path in lint::node_files(dir)? {
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("");
+        if calculate_address(name).is_err() {
+            skipped.push(path);
+            continue;
+        }
+        if !names.keeps(name) {
+            continue;
+        }
+        let text = fs::read_to_string(&path).map_err(|_| "failed to read node file")?;
+        let status = verify_text(&text, name, mappings)?;
+        reports.push(VerifyReport { path, status });
+    }
+    Ok((reports, skipped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tells_mismatches_missing_keys_and_bad_files_apart() {
+        let mappings: Vec<KeyMapping> = ["network.ip", "network.p2p_port", "rpc_port=rpc.port"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        let verify = |text| verify_text(text, "rpc-polkadot-01", &mappings).unwrap();
+
+        let status =
+            verify("network:\n  ip: 192.168.111.99\n  p2p_port: \"31001\"\nrpc: {port: []}\n");
+        assert_eq!(
+            status,
+            FileStatus::Checked(vec![
+                (
+                    "network.ip".into(),
+                    KeyCheck::Mismatch {
+                        expected: "192.168.111.10".into(),
+                        found: "192.168.111.99".into(),
+                    }
+                ),
+                ("network.p2p_port".into(), KeyCheck::Match),
+                ("rpc.port".into(), KeyCheck::NotScalar("sequence")),
+            ])
+        );
+        assert_eq!(status.exit_code(), 5);
+        assert_eq!(verify("").exit_code(), 3);
+
+        assert_eq!(
+            verify("a: 1\n---\nb: 2\n"),
+            FileStatus::MultipleDocuments(2)
+        );
+        assert_eq!(verify("- 1\n"), FileStatus::NotMapping("sequence"));
+        assert!(matches!(verify("a: [1\n"), FileStatus::Unparsable(_)));
+    }
+}
@@ -0,0 +1,121 @@
+// Argument parsing as the process sees it: global flags may go before or after
+// a subcommand, and bare node names still look addresses up
+use std::{
+    fs,
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+fn portgen(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_portgen"))
+        .args(args)
+        .env_remove("PORTGEN_CONFIG")
+        .env_remove("PORTGEN_QUIET")
+        .env("XDG_CONFIG_HOME", env!("CARGO_TARGET_TMPDIR"))
+        .output()
+        .unwrap()
+}
+
+fn stdout(args: &[&str]) -> String {
+    let output = portgen(args);
+    assert!(output.status.success(), "{args:?}: {output:?}");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn global_flags_go_before_subcommands() {
+    let config = format!("{}/global-flags.toml", env!("CARGO_TARGET_TMPDIR"));
+    fs::write(&config, "[chains.my-chain]\nid = 60\n").unwrap();
+    for args in [
+        ["--config", &config, "list", "chains"],
+        ["list", "chains", "--config", &config],
+    ] {
+        assert!(stdout(&args).contains("60 my-chain"), "{args:?}");
+    }
+
+    let roles = stdout(&["list", "roles"]);
+    assert_eq!(
+        stdout(&["--no-deprecation-warnings", "list", "roles"]),
+        roles
+    );
+    assert_eq!(stdout(&["-v", "list", "roles"]), roles);
+    let output = portgen(&["-q", "list", "roles"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+
+    assert_eq!(
+        stdout(&["--strict", "rpc-polkadot-01", "rpc-kusama-01"]),
+        "192.168.111.10:31001\n192.168.121.10:32001\n"
+    );
+}
+
+#[test]
+fn schema_applies_to_every_subcommand() {
+    let v2 = "--listen-addr /ip4/10.10.111.100/tcp/31001";
+    for args in [
+        ["script-args", "--schema", "v2", "rpc-polkadot-01"],
+        ["--schema", "v2", "script-args", "rpc-polkadot-01"],
+    ] {
+        assert!(stdout(&args).trim_end().ends_with(v2), "{args:?}");
+    }
+    assert!(stdout(&["script-args", "rpc-polkadot-01"]).contains("/ip4/192.168.111.10/"));
+
+    let mut annotate = Command::new(env!("CARGO_BIN_EXE_portgen"))
+        .args(["annotate", "--schema", "v2"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    annotate
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"dial 10.10.111.100:31001 failed\n")
+        .unwrap();
+    let output = annotate.wait_with_output().unwrap();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "dial rpc-polkadot-01(10.10.111.100:31001) failed\n"
+    );
+
+    let names = format!("{}/schema-names.txt", env!("CARGO_TARGET_TMPDIR"));
+    fs::write(&names, "rpc-polkadot-01\nval-kusama-04\n").unwrap();
+    assert_eq!(
+        stdout(&["--schema", "v2", "consistency-check", &names]),
+        "rpc-polkadot-01: ok\nval-kusama-04: ok\n"
+    );
+}
+
+#[test]
+fn streamed_stdin_warns_like_buffered_names() {
+    let piped = |args: &[&str]| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_portgen"))
+            .args(args)
+            .env_remove("PORTGEN_CONFIG")
+            .env("XDG_CONFIG_HOME", env!("CARGO_TARGET_TMPDIR"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(b"rpc-statemint-polkadot-01\nrpc-polkadot-01\n")
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success(), "{args:?}: {output:?}");
+        String::from_utf8(output.stderr).unwrap()
+    };
+    let warning = "warning: rpc-statemint-polkadot-01: chain name 'statemint' is deprecated \
+                   (renamed to Asset Hub Polkadot), use 'asset-hub'\n";
+    // Plain output streams, json buffers the names first
+    assert_eq!(piped(&["-"]), warning);
+    assert_eq!(piped(&["-", "--format", "json"]), warning);
+    assert_eq!(piped(&["--no-deprecation-warnings", "-"]), "");
+    assert_eq!(
+        piped(&["-", "--format", "json", "--no-deprecation-warnings"]),
+        ""
+    );
+}
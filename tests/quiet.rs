@@ -0,0 +1,120 @@
+// --quiet as the process sees it: nothing on stdout or stderr, whatever the
+// outcome, with the exit status as the only report
+use std::{
+    fs,
+    net::TcpListener,
+    process::{Command, Output},
+};
+
+fn portgen(args: &[&str], quiet_env: Option<&str>) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_portgen"));
+    command
+        .args(args)
+        .env_remove("PORTGEN_CONFIG")
+        .env_remove("PORTGEN_QUIET")
+        .env("XDG_CONFIG_HOME", env!("CARGO_TARGET_TMPDIR"));
+    if let Some(value) = quiet_env {
+        command.env("PORTGEN_QUIET", value);
+    }
+    command.output().unwrap()
+}
+
+#[test]
+fn quiet_runs_print_nothing() {
+    let cases: &[(&[&str], Option<&str>, i32)] = &[
+        (&["-q", "rpc-polkadot-01"], None, 0),
+        (&["-qv", "rpc-polkadot-01"], None, 2),
+        (&["-vq", "not-a-node"], None, 2),
+        (&["--quiet", "not-a-node"], None, 1),
+        (&["-q", "--no-such-flag"], None, 2),
+        (&["rpc-polkadot-01"], Some("1"), 0),
+        (&["not-a-node"], Some("yes"), 1),
+        (&["--no-such-flag"], Some("true"), 2),
+    ];
+    for &(args, quiet_env, code) in cases {
+        let output = portgen(args, quiet_env);
+        let context = format!("{args:?} PORTGEN_QUIET={quiet_env:?}");
+        assert_eq!(output.status.code(), Some(code), "{context}");
+        assert!(output.stdout.is_empty(), "stdout of {context}");
+        assert!(output.stderr.is_empty(), "stderr of {context}");
+    }
+
+    // The same run without --quiet does print
+    let output = portgen(&["rpc-polkadot-01"], Some("off"));
+    assert_eq!(output.stdout, b"192.168.111.10:31001\n");
+}
+
+// Each subcommand through success and failure, with -q before and after it
+#[test]
+fn quiet_subcommands_keep_their_exit_codes() {
+    let tmp = env!("CARGO_TARGET_TMPDIR");
+    let bad_config = format!("{tmp}/quiet-bad-chain.toml");
+    fs::write(&bad_config, "[chains.Bad_Name]\n").unwrap();
+    let audit_log = format!("{tmp}/quiet-audit.jsonl");
+    let _ = fs::remove_file(&audit_log);
+    assert!(
+        portgen(&["--audit-log", &audit_log, "rpc-polkadot-01"], None)
+            .status
+            .success()
+    );
+    // A port in use fails bind-check with 3, the same port base without it passes
+    let base_of =
+        |listener: &TcpListener| (listener.local_addr().unwrap().port() - 1001).to_string();
+    let listener = TcpListener::bind("0.0.0.0:0").unwrap();
+    let busy_base = base_of(&listener);
+    let free_base = base_of(&TcpListener::bind("0.0.0.0:0").unwrap());
+
+    let cases: &[(&[&str], i32)] = &[
+        (&["check", "rpc-polkadot-01", "val-kusama-04"], 0),
+        (&["check", "rpc-polkadot-01", "not-a-node"], 1),
+        (&["validate-config"], 0),
+        (&["--config", &bad_config, "validate-config"], 1),
+        (
+            &[
+                "audit-log",
+                "show",
+                "--name",
+                "rpc-polkadot-01",
+                "--audit-log",
+                &audit_log,
+            ],
+            0,
+        ),
+        (
+            &[
+                "audit-log",
+                "show",
+                "--name",
+                "not-a-node",
+                "--audit-log",
+                &audit_log,
+            ],
+            1,
+        ),
+        (
+            &["bind-check", "rpc-polkadot-01", "--port-base", &free_base],
+            0,
+        ),
+        (
+            &["bind-check", "rpc-polkadot-01", "--port-base", &busy_base],
+            3,
+        ),
+    ];
+    for &(args, code) in cases {
+        let mut before = vec!["-q"];
+        before.extend(args);
+        let mut after = args.to_vec();
+        after.push("--quiet");
+        for args in [before, after] {
+            let output = portgen(&args, None);
+            assert_eq!(output.status.code(), Some(code), "{args:?}");
+            assert!(output.stdout.is_empty(), "stdout of {args:?}");
+            assert!(output.stderr.is_empty(), "stderr of {args:?}");
+        }
+        // Without --quiet, the failures do say why
+        let output = portgen(args, None);
+        assert_eq!(output.status.code(), Some(code), "{args:?}");
+        assert_eq!(output.stderr.is_empty(), code != 1, "stderr of {args:?}");
+    }
+    drop(listener);
+}